@@ -0,0 +1,4 @@
+-- Server-side encryption mode of the stored content: `AES256` (server key) or `SSE-C` (customer key)
+ALTER TABLE metadata ADD COLUMN encryption TEXT;
+-- Base64 MD5 of the customer key, used to validate `SSE-C` requests
+ALTER TABLE metadata ADD COLUMN encryption_key_md5 TEXT;
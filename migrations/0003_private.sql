@@ -0,0 +1,2 @@
+-- Private objects are never redirected to the public gateway nor advertised with `x-ipfs-*` headers
+ALTER TABLE metadata ADD COLUMN private BOOLEAN NOT NULL DEFAULT FALSE;
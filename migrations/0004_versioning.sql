@@ -0,0 +1,30 @@
+-- Per-bucket settings. Buckets without a row use defaults
+CREATE TABLE IF NOT EXISTS buckets (
+    bucket TEXT NOT NULL PRIMARY KEY,
+    -- `Enabled` or `Suspended`. Versioning was never enabled when NULL
+    versioning TEXT
+);
+
+-- Version of the current object, NULL when stored while versioning was not enabled
+ALTER TABLE metadata ADD COLUMN version_id TEXT;
+
+-- Every version of objects stored while versioning was enabled, including the current one
+CREATE TABLE IF NOT EXISTS versions (
+    -- Ordering of versions, as timestamps may collide
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    version_id TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    object_key TEXT NOT NULL,
+    -- Empty for delete markers
+    cid TEXT NOT NULL DEFAULT '',
+    content_type TEXT NOT NULL DEFAULT 'application/octect-stream',
+    size INTEGER NOT NULL DEFAULT 0,
+    encryption TEXT,
+    encryption_key_md5 TEXT,
+    private BOOLEAN NOT NULL DEFAULT FALSE,
+    delete_marker BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_versions_bucket_key_version ON versions(bucket, object_key, version_id);
+CREATE INDEX IF NOT EXISTS idx_versions_cid ON versions(cid);
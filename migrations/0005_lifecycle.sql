@@ -0,0 +1,13 @@
+-- Lifecycle rules of `PutBucketLifecycleConfiguration`, applied by a background task
+CREATE TABLE IF NOT EXISTS lifecycle_rules (
+    bucket TEXT NOT NULL,
+    rule_id TEXT NOT NULL,
+    prefix TEXT NOT NULL DEFAULT '',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Days after which current objects are deleted
+    expiration_days INTEGER,
+    -- Days after which versions that are no longer current are permanently deleted
+    noncurrent_days INTEGER,
+
+    PRIMARY KEY (bucket, rule_id)
+);
@@ -0,0 +1,15 @@
+-- Tags of the current object, set on `PutObject` (`x-amz-tagging`) or `PutObjectTagging`
+CREATE TABLE IF NOT EXISTS tags (
+    bucket TEXT NOT NULL,
+    object_key TEXT NOT NULL,
+    tag_key TEXT NOT NULL,
+    tag_value TEXT NOT NULL DEFAULT '',
+
+    PRIMARY KEY (bucket, object_key, tag_key)
+);
+
+-- Tags belong to the object, and are removed with it on any deletion path
+CREATE TRIGGER IF NOT EXISTS delete_object_tags AFTER DELETE ON metadata
+BEGIN
+    DELETE FROM tags WHERE bucket = OLD.bucket AND object_key = OLD.object_key;
+END;
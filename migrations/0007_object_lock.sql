@@ -0,0 +1,9 @@
+-- Object Lock of the current object and of each version
+-- `retention_mode` is `GOVERNANCE` or `COMPLIANCE`, protecting the content until `retain_until`
+ALTER TABLE metadata ADD COLUMN retention_mode TEXT;
+ALTER TABLE metadata ADD COLUMN retain_until TIMESTAMP;
+ALTER TABLE metadata ADD COLUMN legal_hold BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE versions ADD COLUMN retention_mode TEXT;
+ALTER TABLE versions ADD COLUMN retain_until TIMESTAMP;
+ALTER TABLE versions ADD COLUMN legal_hold BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,10 @@
+-- Additional checksums of the current object and of each version
+-- `checksum` is base64 encoded, computed with `checksum_algorithm` (eg: `CRC32`, `SHA256`) over the whole object,
+-- or over the checksums of each part when `checksum_type` is `COMPOSITE`
+ALTER TABLE metadata ADD COLUMN checksum_algorithm TEXT;
+ALTER TABLE metadata ADD COLUMN checksum TEXT;
+ALTER TABLE metadata ADD COLUMN checksum_type TEXT;
+
+ALTER TABLE versions ADD COLUMN checksum_algorithm TEXT;
+ALTER TABLE versions ADD COLUMN checksum TEXT;
+ALTER TABLE versions ADD COLUMN checksum_type TEXT;
@@ -0,0 +1,6 @@
+-- S3 compatible ETag of the current object and of each version
+-- Hex encoded MD5 of the content, or MD5 of the MD5 of each part with a `-N` suffix for multipart uploads
+-- Objects stored before have no ETag, and keep returning the CID-based weak ETag
+ALTER TABLE metadata ADD COLUMN etag TEXT;
+
+ALTER TABLE versions ADD COLUMN etag TEXT;
@@ -0,0 +1,5 @@
+-- Parts of objects stored from multipart uploads, for `GetObjectAttributes` and `HeadObject` with `partNumber`
+-- JSON encoded array of `{"number", "size", "checksum"}`, NULL for objects uploaded at once
+ALTER TABLE metadata ADD COLUMN parts TEXT;
+
+ALTER TABLE versions ADD COLUMN parts TEXT;
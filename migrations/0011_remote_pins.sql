@@ -0,0 +1,12 @@
+-- Content pinned on a remote Pinning Service, tracked to retry failed requests
+CREATE TABLE IF NOT EXISTS remote_pins (
+    cid TEXT PRIMARY KEY,
+    -- `requestid` assigned by the Pinning Service
+    request_id TEXT,
+    -- `pending` until requested, then `queued`, `pinning`, `pinned` or `failed` as reported by the service.
+    -- `unpin` once no object references the CID anymore, until the remote pin is removed
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
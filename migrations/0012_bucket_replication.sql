@@ -0,0 +1,3 @@
+-- Replication factor of content pinned on IPFS Cluster. Cluster defaults apply when NULL
+ALTER TABLE buckets ADD COLUMN replication_min INTEGER;
+ALTER TABLE buckets ADD COLUMN replication_max INTEGER;
@@ -0,0 +1,7 @@
+-- Options used when adding content of a bucket to IPFS. Instance defaults apply when NULL
+ALTER TABLE buckets ADD COLUMN add_cid_version INTEGER;
+ALTER TABLE buckets ADD COLUMN add_hash TEXT;
+ALTER TABLE buckets ADD COLUMN add_chunker TEXT;
+ALTER TABLE buckets ADD COLUMN add_raw_leaves BOOLEAN;
+ALTER TABLE buckets ADD COLUMN add_inline BOOLEAN;
+ALTER TABLE buckets ADD COLUMN add_trickle BOOLEAN;
@@ -0,0 +1,3 @@
+-- Key of the IPFS Node keystore publishing the bucket root over IPNS, and its IPNS name. Not published when NULL
+ALTER TABLE buckets ADD COLUMN ipns_key TEXT;
+ALTER TABLE buckets ADD COLUMN ipns_name TEXT;
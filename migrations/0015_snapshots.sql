@@ -0,0 +1,9 @@
+-- Point-in-time root CIDs of buckets, pinned while recorded
+CREATE TABLE IF NOT EXISTS snapshots (
+    bucket TEXT NOT NULL,
+    label TEXT NOT NULL,
+    cid TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (bucket, label)
+);
@@ -0,0 +1,184 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use crate::database::{BucketIpns, Replication};
+use crate::ipfs::AddOptions;
+
+/// Settings of a bucket not covered by the S3 API
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BucketSettings {
+    /// Minimum number of IPFS Cluster peers pinning new content of the bucket
+    #[serde(default)]
+    replication_min: Option<i64>,
+    /// Maximum number of IPFS Cluster peers pinning new content of the bucket
+    #[serde(default)]
+    replication_max: Option<i64>,
+    /// Options to add new content of the bucket to IPFS, unset ones use the instance defaults
+    #[serde(default)]
+    add: AddOptions,
+    /// Key of the IPFS Node keystore publishing the bucket root over IPNS. Not published when unset
+    #[serde(default)]
+    ipns_key: Option<String>,
+    /// IPNS name of `ipns_key`
+    #[serde(default, skip_deserializing)]
+    ipns_name: Option<String>,
+    /// Current CID of the bucket directory on MFS, unset before the first object is stored
+    #[serde(default, skip_deserializing)]
+    root_cid: Option<String>,
+}
+
+/// Current CID of the bucket directory on MFS
+async fn root_cid(state: &AppState, bucket: &str) -> Option<String> {
+    let path = crate::s3::bucket_path(&state.config.folder_prefix, bucket).ok()?;
+    state
+        .ipfs_client
+        .stat(&path.to_string_lossy())
+        .await
+        .map(|stat| stat.hash)
+        .inspect_err(|e| tracing::debug!(bucket, error = %e, "Bucket directory not found"))
+        .ok()
+}
+
+/// Resolves the IPNS name of `ipns_key`, which must exist on the IPFS Node keystore
+/// Private buckets can't be published, as their root links the private objects
+async fn ipns(state: &AppState, bucket: &str, key: Option<&str>) -> Result<BucketIpns, StatusCode> {
+    let Some(key) = key else {
+        return Ok(BucketIpns::default());
+    };
+
+    match crate::s3::is_private_bucket(&state.db, &state.config, bucket).await {
+        Ok(false) => {}
+        Ok(true) => {
+            tracing::warn!(
+                bucket,
+                key,
+                "Refusing to publish a bucket with private objects"
+            );
+            return Err(StatusCode::CONFLICT);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to count private objects");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match state.ipfs_client.key_name(key).await {
+        Ok(Some(name)) => Ok(BucketIpns {
+            ipns_key: Some(key.to_string()),
+            ipns_name: Some(name),
+        }),
+        Ok(None) => {
+            tracing::warn!(bucket, key, "IPNS key not found on the IPFS Node keystore");
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to list IPNS keys");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[axum::debug_handler]
+/// Returns the settings of a bucket
+///
+/// Eg: `GET /_admin/buckets/banana-bucket`
+pub async fn get_bucket(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<BucketSettings>, StatusCode> {
+    let settings = match state.db.get_bucket_replication(&bucket).await {
+        Ok(replication) => BucketSettings {
+            replication_min: replication.replication_min,
+            replication_max: replication.replication_max,
+            ..Default::default()
+        },
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve bucket replication");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let add = match state.db.get_bucket_add_options(&bucket).await {
+        Ok(add) => add,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve bucket add options");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let ipns = match state.db.get_bucket_ipns(&bucket).await {
+        Ok(ipns) => ipns,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve bucket IPNS");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(Json(BucketSettings {
+        add,
+        ipns_key: ipns.ipns_key,
+        ipns_name: ipns.ipns_name,
+        root_cid: root_cid(&state, &bucket).await,
+        ..settings
+    }))
+}
+
+#[axum::debug_handler]
+/// Replaces the settings of a bucket. Replication and add options only apply to content added afterwards
+/// The bucket root is published over IPNS shortly after setting `ipns_key`
+///
+/// Eg: `PUT /_admin/buckets/banana-bucket` with `{"replication_min": 2, "replication_max": 3}`
+pub async fn put_bucket(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(settings): Json<BucketSettings>,
+) -> Result<Json<BucketSettings>, StatusCode> {
+    let replication = Replication {
+        replication_min: settings.replication_min,
+        replication_max: settings.replication_max,
+    };
+    if let (Some(min), Some(max)) = (replication.replication_min, replication.replication_max)
+        && min > max
+        && max != -1
+    {
+        tracing::warn!(bucket, min, max, "Invalid replication factor");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = settings.add.validate() {
+        tracing::warn!(bucket, error = %e, "Invalid add options");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let ipns = ipns(&state, &bucket, settings.ipns_key.as_deref()).await?;
+
+    if let Err(e) = state.db.put_bucket_replication(&bucket, replication).await {
+        tracing::error!(error = %e, "Failed to store bucket replication");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if let Err(e) = state
+        .db
+        .put_bucket_add_options(&bucket, &settings.add)
+        .await
+    {
+        tracing::error!(error = %e, "Failed to store bucket add options");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if let Err(e) = state.db.put_bucket_ipns(&bucket, &ipns).await {
+        tracing::error!(error = %e, "Failed to store bucket IPNS");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    // Publishes the root with the new key right away, or forgets the last publication
+    state.ipns.notify(&bucket);
+
+    Ok(Json(BucketSettings {
+        ipns_name: ipns.ipns_name,
+        root_cid: root_cid(&state, &bucket).await,
+        ..settings
+    }))
+}
@@ -0,0 +1,106 @@
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use futures::TryStreamExt;
+use serde::Serialize;
+
+use crate::AppState;
+use crate::car::{self, ImportReport, Manifest};
+
+#[derive(Serialize)]
+pub struct ImportedCar {
+    root: String,
+}
+
+fn status_code(bucket: &str, e: &car::Error) -> StatusCode {
+    let status = e.status_code();
+    if status.is_server_error() {
+        tracing::error!(bucket, error = %e, "Bucket transfer failed");
+    } else {
+        tracing::warn!(bucket, error = %e, "Invalid bucket transfer");
+    }
+    status
+}
+
+#[axum::debug_handler]
+/// Streams the directory of a bucket as a CAR, with its root on `x-ipfs-roots`
+///
+/// Eg: `GET /_admin/buckets/banana-bucket/export`
+pub async fn export_car(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Response, StatusCode> {
+    let path = crate::s3::bucket_path(&state.config.folder_prefix, &bucket)
+        .map_err(|e| status_code(&bucket, &e.into()))?;
+    let root = state
+        .ipfs_client
+        .directory(&path)
+        .await
+        .map_err(|e| status_code(&bucket, &e.into()))?;
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/vnd.ipld.car")
+        .header("x-ipfs-roots", &root)
+        .body(Body::from_stream(state.ipfs_client.export_car(&root)))
+        .unwrap_or_default())
+}
+
+#[axum::debug_handler]
+/// Returns the metadata sidecar of a bucket, listing its current objects
+///
+/// Eg: `GET /_admin/buckets/banana-bucket/export/metadata`
+pub async fn export_metadata(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<Manifest>, StatusCode> {
+    car::manifest(&state.db, &state.ipfs_client, &state.config, &bucket)
+        .await
+        .map(Json)
+        .map_err(|e| status_code(&bucket, &e))
+}
+
+#[axum::debug_handler]
+/// Imports the blocks of a CAR streamed on the body, pinning its root until its metadata is imported
+///
+/// Eg: `POST /_admin/buckets/banana-bucket/import` with `--data-binary @banana-bucket.car`
+pub async fn import_car(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    body: Body,
+) -> Result<Json<ImportedCar>, StatusCode> {
+    let car = body.into_data_stream().map_err(std::io::Error::other);
+    state
+        .ipfs_client
+        .import_car(car)
+        .await
+        .map(|root| Json(ImportedCar { root }))
+        .map_err(|e| status_code(&bucket, &e.into()))
+}
+
+#[axum::debug_handler]
+/// Links the root of an imported CAR as the directory of an empty bucket, restoring the objects of its sidecar,
+/// holding writes to the bucket meanwhile
+///
+/// Eg: `POST /_admin/buckets/banana-bucket/import/metadata` with `--data-binary @banana-bucket.metadata.json`
+pub async fn import_metadata(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(manifest): Json<Manifest>,
+) -> Result<Json<ImportReport>, StatusCode> {
+    let _restore = state.bucket_locks.restore(&bucket).await;
+    let report = car::import_objects(
+        &state.db,
+        &state.ipfs_client,
+        state.cluster.as_ref(),
+        &state.config,
+        &bucket,
+        &manifest,
+    )
+    .await
+    .map_err(|e| status_code(&bucket, &e))?;
+
+    state.ipns.notify(&bucket);
+    Ok(Json(report))
+}
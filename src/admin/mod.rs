@@ -0,0 +1,52 @@
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post};
+
+use crate::AppState;
+
+mod buckets;
+mod car;
+mod presign;
+mod snapshots;
+
+/// Administrative endpoints, only exposed when `admin_api` is enabled and `auth` is configured
+///
+/// Requests are authenticated with the same `SigV4` credentials as the S3 endpoints.
+/// Eg: `curl --aws-sigv4 "aws:amz:us-east-1:s3" --user "$AUTH_ACCESS_KEY:$AUTH_SECRET_KEY" ...`
+pub fn routes(config: &crate::cli::RunConfig) -> axum::Router<AppState> {
+    if !config.admin_api.unwrap_or_default() {
+        return axum::Router::new();
+    }
+
+    let Some(auth) = config.auth.clone() else {
+        tracing::warn!("Admin API requires auth credentials to be configured. Not exposing it");
+        return axum::Router::new();
+    };
+
+    axum::Router::new()
+        // Bucket names can't start with `_`, so this never overlaps with S3 routes
+        .route("/_admin/presign", get(presign::presign))
+        .route(
+            "/_admin/buckets/{bucket}",
+            get(buckets::get_bucket).put(buckets::put_bucket),
+        )
+        .route(
+            "/_admin/buckets/{bucket}/snapshots",
+            get(snapshots::list_snapshots).post(snapshots::create_snapshot),
+        )
+        .route(
+            "/_admin/buckets/{bucket}/restore",
+            post(snapshots::restore_snapshot),
+        )
+        .route("/_admin/buckets/{bucket}/export", get(car::export_car))
+        .route(
+            "/_admin/buckets/{bucket}/export/metadata",
+            get(car::export_metadata),
+        )
+        .route("/_admin/buckets/{bucket}/import", post(car::import_car))
+        .route(
+            "/_admin/buckets/{bucket}/import/metadata",
+            // Sidecars of large buckets exceed the default limit
+            post(car::import_metadata).layer(DefaultBodyLimit::disable()),
+        )
+        .layer(crate::s3::authorization::AuthorizationLayer::new(auth))
+}
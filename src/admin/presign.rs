@@ -0,0 +1,88 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum_extra::TypedHeader;
+use axum_extra::headers::Host;
+use http::{Method, Uri};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+
+use crate::AppState;
+use crate::s3::authorization::{self, PresignRequest};
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+pub struct PresignParams {
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "default_method")]
+    method: Method,
+    bucket: String,
+    key: String,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "default_expires")]
+    expires: humantime::Duration,
+    #[serde(default = "default_region")]
+    region: String,
+}
+
+fn default_method() -> Method {
+    Method::GET
+}
+
+fn default_expires() -> humantime::Duration {
+    std::time::Duration::from_hours(1).into()
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+#[derive(Serialize)]
+pub struct PresignResponse {
+    url: String,
+    expires_at: i64,
+}
+
+#[axum::debug_handler]
+/// Generates a presigned URL using the configured credentials
+///
+/// Eg: `GET /_admin/presign?method=PUT&bucket=b&key=path/to/file&expires=15m`
+pub async fn presign(
+    State(state): State<AppState>,
+    TypedHeader(host): TypedHeader<Host>,
+    Query(params): Query<PresignParams>,
+) -> Result<Json<PresignResponse>, StatusCode> {
+    // Routes are only registered with auth present
+    let Some(auth) = &state.config.auth else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let endpoint = match &state.config.public_endpoint {
+        Some(endpoint) => endpoint.clone(),
+        None => format!("http://{host}")
+            .parse::<Uri>()
+            .map_err(|_| StatusCode::BAD_REQUEST)?,
+    };
+
+    let now = chrono::Utc::now();
+    let request = PresignRequest {
+        endpoint: &endpoint,
+        method: &params.method,
+        bucket: &params.bucket,
+        key: &params.key,
+        region: &params.region,
+        expires: params.expires.into(),
+        now,
+    };
+
+    match authorization::presign(auth, &request) {
+        Ok(url) => Ok(Json(PresignResponse {
+            url: url.to_string(),
+            expires_at: (now + request.expires).timestamp(),
+        })),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to generate presigned URL");
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
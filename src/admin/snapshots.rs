@@ -0,0 +1,98 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+
+use crate::AppState;
+use crate::snapshot::{self, RestoreReport, Snapshot, Source};
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CreateSnapshot {
+    /// Defaults to the current time
+    #[serde(default)]
+    label: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct RestoreSnapshot {
+    /// Label of a recorded snapshot
+    #[serde(default)]
+    label: Option<String>,
+    /// CID of a directory to restore instead of a recorded snapshot
+    #[serde(default)]
+    cid: Option<String>,
+}
+
+fn status_code(bucket: &str, e: &snapshot::Error) -> StatusCode {
+    let status = e.status_code();
+    if status.is_server_error() {
+        tracing::error!(bucket, error = %e, "Snapshot failed");
+    } else {
+        tracing::warn!(bucket, error = %e, "Invalid snapshot request");
+    }
+    status
+}
+
+#[axum::debug_handler]
+/// Lists the snapshots of a bucket, oldest first
+///
+/// Eg: `GET /_admin/buckets/banana-bucket/snapshots`
+pub async fn list_snapshots(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<Vec<Snapshot>>, StatusCode> {
+    snapshot::list_snapshots(&state.db, &bucket)
+        .await
+        .map(Json)
+        .map_err(|e| status_code(&bucket, &e))
+}
+
+#[axum::debug_handler]
+/// Records the current root CID of a bucket, pinning it, holding writes to the bucket meanwhile
+///
+/// Eg: `POST /_admin/buckets/banana-bucket/snapshots` with `{"label": "before-migration"}`
+pub async fn create_snapshot(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(request): Json<CreateSnapshot>,
+) -> Result<(StatusCode, Json<Snapshot>), StatusCode> {
+    let _writes = state.bucket_locks.restore(&bucket).await;
+    snapshot::create_snapshot(
+        &state.db,
+        &state.ipfs_client,
+        state.cluster.as_ref(),
+        &state.config,
+        &bucket,
+        request.label.as_deref(),
+    )
+    .await
+    .map(|snapshot| (StatusCode::CREATED, Json(snapshot)))
+    .map_err(|e| status_code(&bucket, &e))
+}
+
+#[axum::debug_handler]
+/// Replaces the content and object metadata of a bucket with a snapshot, holding writes to the bucket meanwhile
+///
+/// Eg: `POST /_admin/buckets/banana-bucket/restore` with `{"label": "before-migration"}`
+pub async fn restore_snapshot(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(request): Json<RestoreSnapshot>,
+) -> Result<Json<RestoreReport>, StatusCode> {
+    let source = Source::new(request.label.as_deref(), request.cid.as_deref())
+        .map_err(|e| status_code(&bucket, &e))?;
+    let _restore = state.bucket_locks.restore(&bucket).await;
+    let report = snapshot::restore_snapshot(
+        &state.db,
+        &state.ipfs_client,
+        state.cluster.as_ref(),
+        &state.config,
+        &bucket,
+        source,
+    )
+    .await
+    .map_err(|e| status_code(&bucket, &e))?;
+
+    state.ipns.notify(&bucket);
+    Ok(Json(report))
+}
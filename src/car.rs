@@ -0,0 +1,430 @@
+// CAR export and import of buckets, to move them between instances or keep offline backups
+// The bucket directory is exported with `dag export`, alongside a JSON sidecar with the metadata of its objects.
+// Importing runs `dag import`, links the root as the bucket directory and restores the objects of the sidecar.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use axum::http::StatusCode;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::cli::{CarCommand, RunConfig};
+use crate::cluster::ClusterClient;
+use crate::database::{Database, DatabaseError, NewObjectMetadata, ObjectTag};
+use crate::ipfs::{self, IpfsClient};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("IPFS error: {0}")]
+    Ipfs(#[from] ipfs::Error),
+
+    #[error("Invalid path: {0}")]
+    Path(#[from] typed_path::CheckedPathError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid metadata: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Bucket {0} already has objects")]
+    NotEmpty(String),
+
+    #[error("Bucket {0} has versioning, which importing would bypass")]
+    Versioned(String),
+
+    #[error("{0} is not a directory")]
+    NotDirectory(String),
+
+    #[error("CAR root {car} doesn't match the metadata root {metadata}")]
+    RootMismatch { car: String, metadata: String },
+
+    #[error("Failed to pin: {0}")]
+    Pin(String),
+}
+
+impl Error {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::NotEmpty(_) | Error::Versioned(_) => StatusCode::CONFLICT,
+            Error::Json(_) | Error::NotDirectory(_) | Error::RootMismatch { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+            Error::Database(_) | Error::Ipfs(_) | Error::Path(_) | Error::Io(_) | Error::Pin(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+/// Metadata sidecar of an exported bucket
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub bucket: String,
+    /// CID of the bucket directory, root of the CAR
+    pub root: String,
+    pub exported_at: i64,
+    pub objects: Vec<ExportedObject>,
+}
+
+/// Current object of an exported bucket. Versions and Object Lock are not exported
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedObject {
+    pub key: String,
+    pub cid: String,
+    pub size: i64,
+    pub content_type: String,
+    #[serde(default)]
+    pub encryption: Option<String>,
+    #[serde(default)]
+    pub encryption_key_md5: Option<String>,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub checksum_algorithm: Option<String>,
+    #[serde(default)]
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub checksum_type: Option<String>,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub parts: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub bucket: String,
+    pub root: String,
+    pub imported: usize,
+    /// Objects of the sidecar missing from the CAR, or with other content
+    pub missing: Vec<String>,
+    /// Objects encrypted with a server key other than the one of this instance, skipped as they would be unreadable
+    pub encrypted: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+enum Outcome {
+    Imported,
+    Encrypted,
+}
+
+/// Sidecar path, defaulting to `{bucket}.metadata.json`
+fn metadata_path(command: &CarCommand) -> PathBuf {
+    command
+        .metadata
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{}.metadata.json", command.bucket)))
+}
+
+/// Runs `aricanduva export`, writing the CAR to stdout and the sidecar to `--metadata`
+/// Returns the exit code
+pub async fn export_command(db: &Database, ipfs: &IpfsClient, command: &CarCommand) -> i32 {
+    let result = async {
+        let manifest = manifest(db, ipfs, &command.config, &command.bucket).await?;
+        tokio::fs::write(
+            metadata_path(command),
+            serde_json::to_vec_pretty(&manifest)?,
+        )
+        .await?;
+
+        let mut stdout = tokio::io::stdout();
+        let mut car = std::pin::pin!(ipfs.export_car(&manifest.root));
+        while let Some(chunk) = car.next().await {
+            stdout.write_all(&chunk?).await?;
+        }
+        stdout.flush().await?;
+        Ok::<_, Error>(())
+    };
+
+    match result.await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Failed to export bucket: {e}");
+            1
+        }
+    }
+}
+
+/// Runs `aricanduva import`, reading the CAR from stdin and the sidecar from `--metadata`
+/// Returns the exit code, failing when objects could not be imported
+pub async fn import_command(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    command: &CarCommand,
+) -> i32 {
+    let result = async {
+        let manifest: Manifest =
+            serde_json::from_slice(&tokio::fs::read(metadata_path(command)).await?)?;
+        let root = ipfs.import_car(stdin()).await?;
+        if root != manifest.root {
+            return Err(Error::RootMismatch {
+                car: root,
+                metadata: manifest.root,
+            });
+        }
+        import_objects(
+            db,
+            ipfs,
+            cluster,
+            &command.config,
+            &command.bucket,
+            &manifest,
+        )
+        .await
+    };
+
+    match result.await {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            i32::from(
+                !report.missing.is_empty()
+                    || !report.encrypted.is_empty()
+                    || !report.errors.is_empty(),
+            )
+        }
+        Err(e) => {
+            eprintln!("Failed to import bucket: {e}");
+            1
+        }
+    }
+}
+
+fn stdin() -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static {
+    futures::stream::try_unfold(tokio::io::stdin(), |mut stdin| async move {
+        let mut buffer = BytesMut::with_capacity(64 * 1024);
+        let read = stdin.read_buf(&mut buffer).await?;
+        Ok((read > 0).then(|| (buffer.freeze(), stdin)))
+    })
+}
+
+/// Current root CID and objects of a bucket
+pub async fn manifest(
+    db: &Database,
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    bucket: &str,
+) -> Result<Manifest, Error> {
+    let path = crate::s3::bucket_path(&config.folder_prefix, bucket)?;
+    let root = ipfs.directory(&path).await?;
+
+    let mut objects = Vec::new();
+    for object in db.list_bucket_objects(bucket).await? {
+        let tags = db.get_object_tags(bucket, &object.key).await?;
+        objects.push(ExportedObject {
+            key: object.key,
+            cid: object.cid,
+            size: object.size,
+            content_type: object.content_type,
+            encryption: object.encryption,
+            encryption_key_md5: object.encryption_key_md5,
+            private: object.private,
+            checksum_algorithm: object.checksum_algorithm,
+            checksum: object.checksum,
+            checksum_type: object.checksum_type,
+            etag: object.etag,
+            parts: object.parts,
+            tags: tags.into_iter().map(|tag| (tag.key, tag.value)).collect(),
+        });
+    }
+
+    Ok(Manifest {
+        bucket: bucket.to_string(),
+        root,
+        exported_at: chrono::Utc::now().timestamp(),
+        objects,
+    })
+}
+
+/// Links the sidecar root as the directory of an empty bucket and stores the metadata of its objects
+///
+/// Objects are only stored when the root links their content at their key. Public ones are pinned on the configured
+/// pinning backend, and the root pinned by `dag import` is unpinned, unless it is a snapshot, even when the import fails.
+/// Objects encrypted with the server key are only stored when this instance has the same key.
+/// Callers hold writes to the bucket meanwhile, as its directory is replaced.
+pub async fn import_objects(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    manifest: &Manifest,
+) -> Result<ImportReport, Error> {
+    let result = link_objects(db, ipfs, cluster, config, bucket, manifest).await;
+    // The root pin is recursive, so it would keep and announce private objects too
+    let unpinned = unpin_root(db, ipfs, &manifest.root).await;
+    let mut report = result?;
+    if let Err(e) = unpinned {
+        report.errors.push(format!("{}: {e}", manifest.root));
+    }
+
+    tracing::info!(
+        bucket,
+        root = report.root,
+        imported = report.imported,
+        missing = report.missing.len(),
+        encrypted = report.encrypted.len(),
+        "Imported bucket"
+    );
+    Ok(report)
+}
+
+/// Unpins the root pinned by `dag import`, unless it is a snapshot
+async fn unpin_root(db: &Database, ipfs: &IpfsClient, root: &str) -> Result<(), Error> {
+    if !db.is_snapshot(root).await? && ipfs.pinned().await?.contains(root) {
+        ipfs.unpin(root).await?;
+    }
+    Ok(())
+}
+
+async fn link_objects(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    manifest: &Manifest,
+) -> Result<ImportReport, Error> {
+    if db.get_bucket_versioning(bucket).await?.is_some() {
+        return Err(Error::Versioned(bucket.to_string()));
+    }
+    if db.count_objects_with_prefix(bucket, "").await? > 0 {
+        return Err(Error::NotEmpty(bucket.to_string()));
+    }
+
+    let stat = ipfs.stat(&format!("/ipfs/{}", manifest.root)).await?;
+    if stat.typ != "directory" {
+        return Err(Error::NotDirectory(manifest.root.clone()));
+    }
+
+    let path = crate::s3::bucket_path(&config.folder_prefix, bucket)?;
+    if ipfs.stat(&path.to_string_lossy()).await.is_ok() {
+        ipfs.unlink(&path).await?;
+    }
+    ipfs.link(&stat.hash, &path).await?;
+
+    let linked: HashMap<String, String> = ipfs
+        .walk(&path)
+        .await?
+        .into_iter()
+        .filter(|entry| !entry.directory)
+        .filter_map(|entry| {
+            let key = entry.path.strip_prefix(&path).ok()?.to_string_lossy();
+            Some((key.into_owned(), entry.cid))
+        })
+        .collect();
+
+    let mut report = ImportReport {
+        bucket: bucket.to_string(),
+        root: stat.hash.clone(),
+        ..Default::default()
+    };
+    for object in &manifest.objects {
+        if linked.get(&object.key) != Some(&object.cid) {
+            report.missing.push(object.key.clone());
+            continue;
+        }
+        match import_object(db, ipfs, cluster, config, bucket, object).await {
+            Ok(Outcome::Imported) => report.imported += 1,
+            Ok(Outcome::Encrypted) => report.encrypted.push(object.key.clone()),
+            Err(e) => report.errors.push(format!("{}: {e}", object.key)),
+        }
+    }
+    Ok(report)
+}
+
+async fn import_object(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    object: &ExportedObject,
+) -> Result<Outcome, Error> {
+    // Content encrypted with customer keys is readable with the same keys, but `SSE-S3` needs the server key
+    if object.encryption.is_some() && object.encryption_key_md5.is_none() {
+        let Some(server) = config.encryption.server() else {
+            return Ok(Outcome::Encrypted);
+        };
+        if !server.decrypts(ipfs.get_content(&object.cid)).await {
+            return Ok(Outcome::Encrypted);
+        }
+    }
+
+    // Private content is only kept by its MFS link
+    if !object.private {
+        let path = crate::s3::normalized_path(&config.folder_prefix, bucket, &object.key)?;
+        crate::pinning::pin_existing(
+            db,
+            ipfs,
+            cluster,
+            config,
+            bucket,
+            &path.to_string_lossy(),
+            &object.cid,
+        )
+        .await
+        .map_err(Error::Pin)?;
+    }
+
+    let tags: Vec<ObjectTag> = object
+        .tags
+        .iter()
+        .map(|(key, value)| ObjectTag {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect();
+    db.store_object_metadata(&NewObjectMetadata {
+        bucket,
+        key: &object.key,
+        cid: &object.cid,
+        size: object.size,
+        content_type: &object.content_type,
+        encryption: object.encryption.as_deref(),
+        encryption_key_md5: object.encryption_key_md5.as_deref(),
+        private: object.private,
+        version_id: None,
+        retention_mode: None,
+        retain_until: None,
+        legal_hold: false,
+        checksum_algorithm: object.checksum_algorithm.as_deref(),
+        checksum: object.checksum.as_deref(),
+        checksum_type: object.checksum_type.as_deref(),
+        etag: object.etag.as_deref(),
+        parts: object.parts.as_deref(),
+        tags: &tags,
+    })
+    .await?;
+    Ok(Outcome::Imported)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Manifest;
+
+    #[test]
+    fn test_manifest_optional_fields() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{"bucket": "b", "root": "bafybeiroot", "exported_at": 0,
+                "objects": [{"key": "a.txt", "cid": "bafkreia", "size": 3, "content_type": "text/plain"}]}"#,
+        )
+        .unwrap();
+        let object = &manifest.objects[0];
+        assert!(!object.private);
+        assert!(object.encryption.is_none());
+        assert!(object.tags.is_empty());
+    }
+}
@@ -62,6 +62,60 @@ impl FromStr for OperationMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum EtagMode {
+    Md5,
+    Cid,
+}
+
+impl FromStr for EtagMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            b"md5" => Ok(EtagMode::Md5),
+            b"cid" => Ok(EtagMode::Cid),
+            _ => Err(format!("{s} is not an etag mode")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum PinningBackend {
+    Kubo,
+    Cluster,
+}
+
+impl FromStr for PinningBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            b"kubo" => Ok(PinningBackend::Kubo),
+            b"cluster" => Ok(PinningBackend::Cluster),
+            _ => Err(format!("{s} is not a pinning backend")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum GatewayStyle {
+    Path,
+    Subdomain,
+}
+
+impl FromStr for GatewayStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            b"path" => Ok(GatewayStyle::Path),
+            b"subdomain" => Ok(GatewayStyle::Subdomain),
+            _ => Err(format!("{s} is not a gateway style")),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Conf)]
 pub struct ExperimentalFlags {
     #[conf(long, env, default(true))]
@@ -124,9 +178,80 @@ pub struct RunConfig {
     /// Optional username and password to access the IPFS Node
     pub rpc_credentials: Option<RpcCredentials>,
 
-    #[conf(long, env, default_value = "https://dweb.link")]
-    /// Public Gateway used as `HTTP 307` responses for `GetObject` in [`mode: redirect` and `mode: auto`]
-    pub gateway: http::Uri,
+    #[conf(repeat, long, env)]
+    /// Additional IPFS Nodes serving reads, balanced with the `rpc_address` node and used as fallback when it is unavailable
+    /// Writes are only sent to `rpc_address`. Replicas must be able to retrieve the content (eg: IPFS Cluster peers)
+    /// Flag can be used multiple times
+    pub rpc_replica: Vec<http::Uri>,
+
+    #[conf(long, env, default(3))]
+    /// Consecutive failed requests before an IPFS Node is considered unavailable
+    pub rpc_failure_threshold: u32,
+
+    #[conf(long, env, default_value = "30s")]
+    /// How long an unavailable IPFS Node is skipped before being retried
+    pub rpc_cooldown: humantime::Duration,
+
+    #[conf(long, env, default_value = "30s")]
+    /// How long reads wait for the first chunk of content before trying the next IPFS Node
+    pub rpc_read_timeout: humantime::Duration,
+
+    #[conf(flatten, prefix)]
+    /// Options used when adding content to IPFS, unless configured on the bucket
+    pub add: crate::ipfs::AddOptions,
+
+    #[conf(long, env, default_value = "5m")]
+    /// How long importing a CID with `x-ipfs-import-cid` may take, including fetching its content to pin it
+    /// Also bounds re-linking and re-pinning content on `fsck`
+    pub import_timeout: humantime::Duration,
+
+    #[conf(long, env, default_value = "30s")]
+    /// How long writes to a bucket published over IPNS must settle down before publishing its new root
+    /// Continuous writes are published at least every 10 times this delay
+    pub ipns_debounce: humantime::Duration,
+
+    #[conf(flatten, prefix)]
+    /// Consistency check between metadata, the MFS tree and pinned content
+    pub fsck: crate::fsck::FsckConfig,
+
+    #[conf(long, env, default_value = "kubo")]
+    /// Where content is pinned
+    /// Options:
+    /// - kubo: Pinned on the IPFS Node on `rpc_address`
+    /// - cluster: Added to the IPFS Node and pinned through IPFS Cluster, replicated across its peers
+    pub pinning_backend: PinningBackend,
+
+    #[conf(flatten, prefix)]
+    /// IPFS Cluster used with `pinning_backend: cluster`
+    pub cluster: Option<crate::cluster::ClusterConfig>,
+
+    #[conf(repeat, long, env)]
+    /// Public Gateways used as `HTTP 307` responses for `GetObject` in [`mode: redirect` and `mode: auto`]
+    /// Redirects use the fastest healthy gateway. Defaults to `https://dweb.link`
+    /// Flag can be used multiple times
+    pub gateway: Vec<http::Uri>,
+
+    #[conf(long, env, default_value = "1m")]
+    /// How often gateways are probed for health and latency
+    pub gateway_probe_interval: humantime::Duration,
+
+    #[conf(long, env, default_value = "bafkqaaa")]
+    /// CID fetched from gateways when probing them
+    /// Defaults to the empty identity CID, served by gateways without fetching content from the network
+    pub gateway_probe_cid: String,
+
+    #[conf(long, env, default_value = "path")]
+    /// How redirects address content on the `gateway`
+    /// Options:
+    /// - path: `https://dweb.link/ipfs/{cid}`, all content shares the gateway origin
+    /// - subdomain: `https://{cid}.ipfs.dweb.link`, isolating the origin of each CID. Requires a gateway supporting subdomains
+    pub gateway_style: GatewayStyle,
+
+    #[conf(long, env, default(false))]
+    /// Redirect the current version of objects in buckets published over IPNS to their path under the
+    /// IPNS name of the bucket: `/ipns/{name}/{key}`
+    /// Objects are redirected by CID while the latest writes to their bucket are not published yet
+    pub gateway_ipns_redirect: Option<bool>,
 
     #[conf(long, env, default_value = "auto")]
     /// Operation mode to run the server
@@ -136,10 +261,32 @@ pub struct RunConfig {
     /// - auto: Redirect to public `gateway` on public requests and returns the content on private connections
     pub mode: OperationMode,
 
+    #[conf(long, env, default_value = "md5")]
+    /// `ETag` returned for objects
+    /// Options:
+    /// - md5: Strong `ETag` with the MD5 of the content, as S3. Multipart uploads have a `-N` suffix with the number of parts
+    /// - cid: Weak `ETag` with the CID of the content
+    pub etag_mode: EtagMode,
+
+    #[conf(long, env)]
+    /// Base domain to accept virtual-hosted-style requests, where the `Host` header selects the bucket
+    /// Eg: `s3.example.com` serves `bucket.s3.example.com/key` as `s3.example.com/bucket/key`
+    pub base_domain: Option<String>,
+
     #[conf(long, env, default_value = "buckets")]
     /// Which root folder should be used on the IPFS Node MFS storage
     pub folder_prefix: String,
 
+    #[conf(repeat, long, env)]
+    /// Buckets whose objects are private unless uploaded with `x-amz-acl: public-read`
+    /// Private objects are never redirected to the public `gateway`
+    /// Flag can be used multiple times
+    pub private_buckets: Vec<String>,
+
+    #[conf(long, env, default_value = "1h")]
+    /// How often bucket lifecycle rules are applied, deleting expired objects and versions
+    pub lifecycle_interval: humantime::Duration,
+
     #[conf(flatten, prefix = "experimental", help_prefix = "(experimental)")]
     pub experimental: ExperimentalFlags,
 
@@ -164,6 +311,23 @@ pub struct RunConfig {
     #[conf(flatten, prefix)]
     /// Customize `SQlite` database
     pub sqlite: SqliteConfig,
+
+    #[conf(flatten, prefix)]
+    /// Server-side encryption of content added to IPFS
+    pub encryption: crate::encryption::EncryptionConfig,
+
+    #[conf(flatten, prefix)]
+    /// Remote Pinning Service (IPFS Pinning Services API) also pinning added content, for redundancy
+    pub remote_pinning: Option<crate::pinning::RemotePinningConfig>,
+
+    #[conf(long, env, default(false))]
+    /// Expose administrative endpoints under `/_admin`. Requires `auth` to be configured
+    pub admin_api: Option<bool>,
+
+    #[conf(long, env)]
+    /// Public address clients use to reach the service (eg: `https://s3.example.com`)
+    /// Used to build presigned URLs on the admin endpoints. Defaults to the request `Host` header
+    pub public_endpoint: Option<http::Uri>,
 }
 
 impl RunConfig {
@@ -203,6 +367,114 @@ impl RunConfig {
     }
 }
 
+#[derive(Debug, Conf)]
+pub struct PresignConfig {
+    #[conf(long, env, default_value = "http://localhost:3000")]
+    /// Public address clients use to reach the service
+    pub endpoint: http::Uri,
+
+    #[conf(long, default_value = "GET")]
+    /// HTTP method allowed by the presigned URL
+    pub method: http::Method,
+
+    #[conf(long)]
+    /// Bucket of the object
+    pub bucket: String,
+
+    #[conf(long)]
+    /// Key of the object
+    pub key: String,
+
+    #[conf(long, default_value = "1h")]
+    /// How long the URL is valid for (eg: `15m`, `1h`, `7d`). At most 7 days
+    pub expires: humantime::Duration,
+
+    #[conf(long, env, default_value = "us-east-1")]
+    /// Region used on the signature scope
+    pub region: String,
+
+    #[conf(flatten, prefix)]
+    /// Credentials used to sign the URL
+    pub auth: crate::s3::authorization::AuthConfig,
+}
+
+#[derive(Debug, Conf)]
+pub struct RebuildCommand {
+    #[conf(long, default(false))]
+    /// Restore objects as public, except in `private_buckets`
+    /// Object ACLs only live on `SQLite`, so restored objects are private by default
+    pub public: Option<bool>,
+
+    #[conf(flatten)]
+    pub config: RunConfig,
+}
+
+#[derive(Debug, Conf)]
+pub struct SnapshotCommand {
+    #[conf(subcommands)]
+    pub action: SnapshotAction,
+
+    #[conf(flatten)]
+    pub config: RunConfig,
+}
+
+#[derive(Debug, Subcommands)]
+pub enum SnapshotAction {
+    /// Record the current root CID of a bucket, pinning it
+    Create(SnapshotCreate),
+    /// List the snapshots of a bucket
+    List(SnapshotBucket),
+    /// Replace the content and object metadata of a bucket with a snapshot
+    Restore(SnapshotRestore),
+}
+
+#[derive(Debug, Conf)]
+pub struct SnapshotBucket {
+    #[conf(long)]
+    /// Bucket of the snapshots
+    pub bucket: String,
+}
+
+#[derive(Debug, Conf)]
+pub struct SnapshotCreate {
+    #[conf(long)]
+    /// Bucket to snapshot
+    pub bucket: String,
+
+    #[conf(long)]
+    /// Label of the snapshot, unique per bucket. Defaults to the current time (eg: `2025-01-31T12:00:00Z`)
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Conf)]
+pub struct SnapshotRestore {
+    #[conf(long)]
+    /// Bucket to restore
+    pub bucket: String,
+
+    #[conf(long)]
+    /// Label of the snapshot to restore
+    pub label: Option<String>,
+
+    #[conf(long)]
+    /// CID of a directory to restore instead of a recorded snapshot, eg: a snapshot from another instance
+    pub cid: Option<String>,
+}
+
+#[derive(Debug, Conf)]
+pub struct CarCommand {
+    #[conf(long)]
+    /// Bucket to export or import
+    pub bucket: String,
+
+    #[conf(long)]
+    /// Path of the JSON metadata sidecar. Defaults to `{bucket}.metadata.json`
+    pub metadata: Option<std::path::PathBuf>,
+
+    #[conf(flatten)]
+    pub config: RunConfig,
+}
+
 #[derive(Debug, Subcommands)]
 pub enum CliOperations {
     /// Start the server. [Default]
@@ -211,6 +483,18 @@ pub enum CliOperations {
     Config(RunConfig),
     /// Generate credentials to use with config
     Credentials,
+    /// Generate a presigned URL for an object
+    Presign(PresignConfig),
+    /// Check consistency between metadata, the MFS tree and pinned content, repairing it with `--fsck-repair`
+    Fsck(RunConfig),
+    /// Recreate missing object metadata from the MFS tree, eg: after losing the `SQLite` database
+    Rebuild(RebuildCommand),
+    /// Create, list and restore point-in-time snapshots of buckets
+    Snapshot(SnapshotCommand),
+    /// Export a bucket as a CAR to stdout, writing the metadata sidecar to `--metadata`
+    Export(CarCommand),
+    /// Import a bucket from a CAR on stdin, restoring objects from the metadata sidecar on `--metadata`
+    Import(CarCommand),
 }
 
 const HELP_STYLES: conf::Styles = conf::Styles::styled()
@@ -0,0 +1,206 @@
+// IPFS Cluster integration
+// Content is added to the Kubo node and pinned through the cluster REST API, replicating it across peers
+// Ref <https://ipfscluster.io/documentation/reference/api/>
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::Bytes;
+use conf::Conf;
+use http::{Method, Request, StatusCode, Uri, header};
+use http_body_util::{BodyExt, Empty};
+
+use crate::database::Replication;
+use crate::https::HttpsClient;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid request: {0}")]
+    Request(#[from] http::Error),
+
+    #[error("Cluster unreachable: {0}")]
+    Client(#[from] hyper_util::client::legacy::Error),
+
+    #[error("Failed to read response: {0}")]
+    Body(#[from] hyper::Error),
+
+    #[error("Cluster returned {0}: {1}")]
+    Status(StatusCode, String),
+}
+
+#[derive(Debug, Conf, Clone)]
+pub struct ClusterConfig {
+    #[conf(long, env)]
+    /// Address of the IPFS Cluster REST API (eg: `http://localhost:9094`)
+    pub address: Uri,
+
+    #[conf(flatten, prefix)]
+    /// Optional username and password of the REST API basic authentication
+    pub credentials: Option<crate::cli::RpcCredentials>,
+
+    #[conf(long, env)]
+    /// Minimum number of peers pinning content, unless configured on the bucket. Uses the cluster default when unset
+    pub replication_min: Option<i64>,
+
+    #[conf(long, env)]
+    /// Maximum number of peers pinning content, unless configured on the bucket. Uses the cluster default when unset
+    pub replication_max: Option<i64>,
+}
+
+#[derive(Clone)]
+pub struct ClusterClient {
+    address: Uri,
+    authorization: Option<String>,
+    replication: Replication,
+    client: HttpsClient<Empty<Bytes>>,
+}
+
+impl ClusterClient {
+    pub fn new(config: &ClusterConfig) -> Result<Self, std::io::Error> {
+        Ok(ClusterClient {
+            address: config.address.clone(),
+            authorization: config.credentials.clone().map(|credentials| {
+                let (username, password): (String, String) = credentials.into();
+                format!(
+                    "Basic {}",
+                    BASE64_STANDARD.encode(format!("{username}:{password}"))
+                )
+            }),
+            replication: Replication {
+                replication_min: config.replication_min,
+                replication_max: config.replication_max,
+            },
+            client: crate::https::client()?,
+        })
+    }
+
+    async fn request(&self, method: Method, path_and_query: &str) -> Result<StatusCode, Error> {
+        let uri = format!(
+            "{}{path_and_query}",
+            self.address.to_string().trim_end_matches('/')
+        );
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(authorization) = &self.authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+
+        let response = self.client.request(request.body(Empty::new())?).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        if status.is_success() || status == StatusCode::NOT_FOUND {
+            Ok(status)
+        } else {
+            Err(Error::Status(
+                status,
+                String::from_utf8_lossy(&body).into_owned(),
+            ))
+        }
+    }
+
+    /// Pin content on the cluster, replicated as configured on the bucket or the cluster defaults
+    #[tracing::instrument(err, skip(self, replication))]
+    pub async fn pin(&self, cid: &str, name: &str, replication: Replication) -> Result<(), Error> {
+        let min = replication
+            .replication_min
+            .or(self.replication.replication_min);
+        let max = replication
+            .replication_max
+            .or(self.replication.replication_max);
+        let query = {
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            query.append_pair("name", name);
+            if let Some(min) = min {
+                query.append_pair("replication-min", &min.to_string());
+            }
+            if let Some(max) = max {
+                query.append_pair("replication-max", &max.to_string());
+            }
+            query.finish()
+        };
+
+        let status = self
+            .request(Method::POST, &format!("/pins/ipfs/{cid}?{query}"))
+            .await?;
+        if status == StatusCode::NOT_FOUND {
+            return Err(Error::Status(status, String::new()));
+        }
+        tracing::debug!("pinned on cluster");
+        Ok(())
+    }
+
+    /// Unpin content from every cluster peer. Content not pinned is ignored
+    #[tracing::instrument(err, skip(self))]
+    pub async fn unpin(&self, cid: &str) -> Result<(), Error> {
+        self.request(Method::DELETE, &format!("/pins/ipfs/{cid}"))
+            .await?;
+        tracing::debug!("unpinned from cluster");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use axum::Router;
+    use axum::extract::{Path, Query, State};
+    use axum::http::StatusCode;
+    use axum::routing::post;
+
+    use super::{ClusterClient, ClusterConfig};
+    use crate::database::Replication;
+
+    type Pins = Arc<Mutex<HashMap<String, HashMap<String, String>>>>;
+
+    async fn pin(
+        State(pins): State<Pins>,
+        Path(cid): Path<String>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> StatusCode {
+        pins.lock().unwrap().insert(cid, params);
+        StatusCode::OK
+    }
+
+    async fn unpin(State(pins): State<Pins>, Path(cid): Path<String>) -> StatusCode {
+        match pins.lock().unwrap().remove(&cid) {
+            Some(_) => StatusCode::OK,
+            None => StatusCode::NOT_FOUND,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pins_with_replication() {
+        let pins = Pins::default();
+        let app = Router::new()
+            .route("/pins/ipfs/{cid}", post(pin).delete(unpin))
+            .with_state(pins.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = ClusterClient::new(&ClusterConfig {
+            address: format!("http://{address}").parse().unwrap(),
+            credentials: None,
+            replication_min: Some(1),
+            replication_max: Some(2),
+        })
+        .unwrap();
+
+        let replication = Replication {
+            replication_min: None,
+            replication_max: Some(3),
+        };
+        client
+            .pin("bafkqaaa", "/buckets/bucket/key", replication)
+            .await
+            .unwrap();
+        let params = pins.lock().unwrap().get("bafkqaaa").cloned().unwrap();
+        assert_eq!(params["name"], "/buckets/bucket/key");
+        assert_eq!(params["replication-min"], "1");
+        assert_eq!(params["replication-max"], "3");
+
+        client.unpin("bafkqaaa").await.unwrap();
+        assert!(pins.lock().unwrap().is_empty());
+        // Content not pinned is ignored
+        client.unpin("bafkqaaa").await.unwrap();
+    }
+}
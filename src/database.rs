@@ -35,6 +35,162 @@ pub struct MetadataResponse {
     pub key: String,
     pub bucket: String,
     pub updated_at: NaiveDateTime,
+    pub encryption: Option<String>,
+    pub encryption_key_md5: Option<String>,
+    pub private: bool,
+    /// `None` when stored while versioning was not enabled on the bucket
+    pub version_id: Option<String>,
+    pub retention_mode: Option<String>,
+    pub retain_until: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+    /// Additional checksum algorithm, as sent on `x-amz-checksum-algorithm`
+    pub checksum_algorithm: Option<String>,
+    /// Base64 encoded checksum, with a `-N` suffix for composite checksums of `N` parts
+    pub checksum: Option<String>,
+    /// `FULL_OBJECT` or `COMPOSITE`
+    pub checksum_type: Option<String>,
+    /// Hex encoded MD5 of the content, with a `-N` suffix for multipart uploads of `N` parts
+    pub etag: Option<String>,
+    /// JSON encoded parts of multipart uploads
+    pub parts: Option<String>,
+}
+
+/// A stored version of an object, or a delete marker
+pub struct ObjectVersion {
+    pub version_id: String,
+    /// Empty for delete markers
+    pub cid: String,
+    pub size: i64,
+    pub content_type: String,
+    pub key: String,
+    pub bucket: String,
+    pub created_at: NaiveDateTime,
+    pub encryption: Option<String>,
+    pub encryption_key_md5: Option<String>,
+    pub private: bool,
+    pub delete_marker: bool,
+    pub retention_mode: Option<String>,
+    pub retain_until: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+    pub checksum_algorithm: Option<String>,
+    pub checksum: Option<String>,
+    pub checksum_type: Option<String>,
+    pub etag: Option<String>,
+    pub parts: Option<String>,
+}
+
+impl From<ObjectVersion> for MetadataResponse {
+    fn from(version: ObjectVersion) -> Self {
+        MetadataResponse {
+            cid: version.cid,
+            size: version.size,
+            content_type: version.content_type,
+            key: version.key,
+            bucket: version.bucket,
+            updated_at: version.created_at,
+            encryption: version.encryption,
+            encryption_key_md5: version.encryption_key_md5,
+            private: version.private,
+            version_id: Some(version.version_id),
+            retention_mode: version.retention_mode,
+            retain_until: version.retain_until,
+            legal_hold: version.legal_hold,
+            checksum_algorithm: version.checksum_algorithm,
+            checksum: version.checksum,
+            checksum_type: version.checksum_type,
+            etag: version.etag,
+            parts: version.parts,
+        }
+    }
+}
+
+/// Current object before and after permanently deleting one of its versions
+pub struct VersionDeletion {
+    pub deleted: ObjectVersion,
+    pub previous: Option<MetadataResponse>,
+    pub current: Option<MetadataResponse>,
+}
+
+/// Expiration rule of a bucket lifecycle configuration
+pub struct LifecycleRule {
+    pub bucket: String,
+    pub rule_id: String,
+    pub prefix: String,
+    pub enabled: bool,
+    pub expiration_days: Option<i64>,
+    pub noncurrent_days: Option<i64>,
+}
+
+/// Tag of an object
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ObjectTag {
+    pub key: String,
+    pub value: String,
+}
+
+/// Replication factor of content pinned on IPFS Cluster
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Replication {
+    pub replication_min: Option<i64>,
+    pub replication_max: Option<i64>,
+}
+
+/// IPNS publishing of a bucket root
+#[derive(Clone, Debug, Default)]
+pub struct BucketIpns {
+    /// Name of the key on the IPFS Node keystore
+    pub ipns_key: Option<String>,
+    /// IPNS name of the key
+    pub ipns_name: Option<String>,
+}
+
+/// Root CID of a bucket recorded at a point in time
+pub struct Snapshot {
+    pub bucket: String,
+    pub label: String,
+    pub cid: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Location and content of a current object
+pub struct StoredObject {
+    pub bucket: String,
+    pub key: String,
+    pub cid: String,
+}
+
+/// Content tracked for remote pinning
+pub struct RemotePin {
+    pub cid: String,
+    pub request_id: Option<String>,
+    pub status: String,
+    pub attempts: i64,
+}
+
+/// Attributes of an object being stored
+pub struct NewObjectMetadata<'a> {
+    pub bucket: &'a str,
+    pub key: &'a str,
+    pub cid: &'a str,
+    /// Size of the plaintext content
+    pub size: i64,
+    pub content_type: &'a str,
+    pub encryption: Option<&'a str>,
+    pub encryption_key_md5: Option<&'a str>,
+    pub private: bool,
+    /// Also stored as a new version when present
+    pub version_id: Option<&'a str>,
+    pub retention_mode: Option<&'a str>,
+    pub retain_until: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+    pub checksum_algorithm: Option<&'a str>,
+    pub checksum: Option<&'a str>,
+    pub checksum_type: Option<&'a str>,
+    /// MD5 of the content, unknown for imported CIDs
+    pub etag: Option<&'a str>,
+    pub parts: Option<&'a str>,
+    /// Replaces the tags of any previous object
+    pub tags: &'a [ObjectTag],
 }
 
 impl Database {
@@ -97,32 +253,148 @@ impl Database {
     }
 
     /// Store metadata for an S3 object
+    ///
+    /// Returns the version replaced by this one when it reuses the `null` version id,
+    /// so its content can be unpinned.
     pub async fn store_object_metadata(
         &self,
+        object: &NewObjectMetadata<'_>,
+    ) -> Result<Option<MetadataResponse>, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        let replaced = Self::insert_object(&mut tx, object).await?;
+        tx.commit().await?;
+        Ok(replaced)
+    }
+
+    /// Stores an object within a transaction, returning the version it replaced
+    async fn insert_object(
+        tx: &mut sqlx::SqliteConnection,
+        object: &NewObjectMetadata<'_>,
+    ) -> Result<Option<MetadataResponse>, DatabaseError> {
+        let mut replaced = None;
+        if let Some(version_id) = object.version_id {
+            Self::preserve_unversioned(&mut *tx, object.bucket, object.key).await?;
+            replaced = Self::take_version(&mut *tx, object.bucket, object.key, version_id).await?;
+
+            sqlx::query!(
+                "INSERT INTO versions (version_id, bucket, object_key, cid, content_type, size, encryption, encryption_key_md5, private, retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)",
+                version_id,
+                object.bucket,
+                object.key,
+                object.cid,
+                object.content_type,
+                object.size,
+                object.encryption,
+                object.encryption_key_md5,
+                object.private,
+                object.retention_mode,
+                object.retain_until,
+                object.legal_hold,
+                object.checksum_algorithm,
+                object.checksum,
+                object.checksum_type,
+                object.etag,
+                object.parts,
+            )
+            .execute(&mut *tx)
+            .inspect_ok(|_| tracing::trace!("stored version"))
+            .instrument(tracing::debug_span!("store version", key = object.key, version_id))
+            .await?;
+        }
+
+        sqlx::query!(
+            "INSERT INTO metadata (cid, bucket, object_key, content_type, size, encryption, encryption_key_md5, private, version_id, retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            ON CONFLICT DO UPDATE SET cid = excluded.cid, size = excluded.size, content_type = excluded.content_type, updated_at = excluded.updated_at,
+                encryption = excluded.encryption, encryption_key_md5 = excluded.encryption_key_md5, private = excluded.private, version_id = excluded.version_id,
+                retention_mode = excluded.retention_mode, retain_until = excluded.retain_until, legal_hold = excluded.legal_hold,
+                checksum_algorithm = excluded.checksum_algorithm, checksum = excluded.checksum, checksum_type = excluded.checksum_type, etag = excluded.etag, parts = excluded.parts",
+            object.cid,
+            object.bucket,
+            object.key,
+            object.content_type,
+            object.size,
+            object.encryption,
+            object.encryption_key_md5,
+            object.private,
+            object.version_id,
+            object.retention_mode,
+            object.retain_until,
+            object.legal_hold,
+            object.checksum_algorithm,
+            object.checksum,
+            object.checksum_type,
+            object.etag,
+            object.parts,
+        )
+        .execute(&mut *tx)
+        .inspect_ok(|_| tracing::trace!("stored metadata"))
+        .instrument(tracing::debug_span!("store metadata", key = object.key))
+        .await
+        ?;
+
+        Self::replace_tags(&mut *tx, object.bucket, object.key, object.tags).await?;
+
+        Ok(replaced.filter(|v| !v.delete_marker).map(Into::into))
+    }
+
+    /// Keeps the current object as the `null` version if it was stored before versioning was enabled
+    async fn preserve_unversioned(
+        tx: &mut sqlx::SqliteConnection,
         bucket: &str,
         key: &str,
-        cid: &str,
-        size: i64,
-        content_type: &str,
     ) -> Result<(), DatabaseError> {
         sqlx::query!(
-            "INSERT INTO metadata (cid, bucket, object_key, content_type, size) VALUES ($1, $2, $3, $4, $5)
-            ON CONFLICT DO UPDATE SET cid = excluded.cid, size = excluded.size, content_type = excluded.content_type, updated_at = excluded.updated_at",
-            cid,
+            "INSERT INTO versions (version_id, bucket, object_key, cid, content_type, size, encryption, encryption_key_md5, private, created_at, retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts)
+            SELECT 'null', bucket, object_key, cid, content_type, size, encryption, encryption_key_md5, private, updated_at, retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM metadata
+            WHERE bucket = ? AND object_key = ? AND version_id IS NULL
+            ON CONFLICT DO NOTHING",
             bucket,
             key,
-            content_type,
-            size
         )
-        .execute(&self.pool)
-        .inspect_ok(|_| tracing::trace!("stored metadata"))
-        .instrument(tracing::debug_span!("store metadata", key))
-        .await
-        ?;
+        .execute(tx)
+        .inspect_ok(|r| tracing::trace!(rows = r.rows_affected(), "preserved unversioned object"))
+        .instrument(tracing::debug_span!("preserve unversioned", key))
+        .await?;
 
         Ok(())
     }
 
+    /// Removes a version, returning it if present
+    async fn take_version(
+        tx: &mut sqlx::SqliteConnection,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+    ) -> Result<Option<ObjectVersion>, DatabaseError> {
+        let version = sqlx::query_as!(
+            ObjectVersion,
+            r#"SELECT version_id, cid, size, content_type, object_key as key, bucket, created_at, encryption, encryption_key_md5, private, delete_marker,
+                retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM versions WHERE bucket = ? AND object_key = ? AND version_id = ?"#,
+            bucket,
+            key,
+            version_id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if version.is_some() {
+            sqlx::query!(
+                "DELETE FROM versions WHERE bucket = ? AND object_key = ? AND version_id = ?",
+                bucket,
+                key,
+                version_id,
+            )
+            .execute(&mut *tx)
+            .inspect_ok(|_| tracing::trace!("deleted version"))
+            .instrument(tracing::debug_span!("delete version", key, version_id))
+            .await?;
+        }
+
+        Ok(version)
+    }
+
     /// Retrieve metadata for an S3 object
     pub async fn get_object_metadata(
         &self,
@@ -131,7 +403,8 @@ impl Database {
     ) -> Result<Option<MetadataResponse>, DatabaseError> {
         let record = sqlx::query_as!(
             MetadataResponse,
-            r#"SELECT cid, size, content_type, bucket, object_key as key, updated_at FROM metadata WHERE bucket = ? AND object_key = ?"#,
+            r#"SELECT cid, size, content_type, bucket, object_key as key, updated_at, encryption, encryption_key_md5, private, version_id,
+                retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM metadata WHERE bucket = ? AND object_key = ?"#,
             bucket,
             key
         )
@@ -158,17 +431,991 @@ impl Database {
         Ok(())
     }
 
-    /// Count how many objects reference a CID
+    /// Replaces the current object with a delete marker
+    ///
+    /// Returns the version replaced by the marker when it reuses the `null` version id,
+    /// so its content can be unpinned.
+    pub async fn create_delete_marker(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+    ) -> Result<Option<MetadataResponse>, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        Self::preserve_unversioned(&mut tx, bucket, key).await?;
+        let replaced = Self::take_version(&mut tx, bucket, key, version_id).await?;
+
+        sqlx::query!(
+            "INSERT INTO versions (version_id, bucket, object_key, delete_marker) VALUES (?, ?, ?, TRUE)",
+            version_id,
+            bucket,
+            key,
+        )
+        .execute(&mut *tx)
+        .inspect_ok(|_| tracing::trace!("stored delete marker"))
+        .instrument(tracing::debug_span!("store delete marker", key, version_id))
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM metadata WHERE bucket = ? AND object_key = ?",
+            bucket,
+            key
+        )
+        .execute(&mut *tx)
+        .inspect_ok(|_| tracing::trace!("deleted"))
+        .instrument(tracing::debug_span!("delete object", key))
+        .await?;
+
+        tx.commit().await?;
+        Ok(replaced.filter(|v| !v.delete_marker).map(Into::into))
+    }
+
+    /// Whether the latest version of an object is a delete marker
+    pub async fn is_delete_marker_latest(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<bool, DatabaseError> {
+        let delete_marker = sqlx::query_scalar!(
+            "SELECT delete_marker FROM versions WHERE bucket = ? AND object_key = ? ORDER BY seq DESC LIMIT 1",
+            bucket,
+            key,
+        )
+        .fetch_optional(&self.pool)
+        .instrument(tracing::debug_span!("get latest version", key))
+        .await?;
+
+        Ok(delete_marker.unwrap_or_default())
+    }
+
+    /// Retrieve a specific version of an S3 object, which might be a delete marker
+    pub async fn get_object_version(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+    ) -> Result<Option<ObjectVersion>, DatabaseError> {
+        let record = sqlx::query_as!(
+            ObjectVersion,
+            r#"SELECT version_id, cid, size, content_type, object_key as key, bucket, created_at, encryption, encryption_key_md5, private, delete_marker,
+                retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM versions WHERE bucket = ? AND object_key = ? AND version_id = ?"#,
+            bucket,
+            key,
+            version_id,
+        )
+        .fetch_optional(&self.pool)
+        .inspect_ok(|_| tracing::trace!("retrieved"))
+        .instrument(tracing::debug_span!("get object version", key, version_id))
+        .await?;
+
+        // Objects stored before versioning was enabled are the `null` version
+        if record.is_none() && version_id == "null" {
+            let current = self.get_object_metadata(bucket, key).await?;
+            return Ok(current
+                .filter(|current| current.version_id.is_none())
+                .map(|current| ObjectVersion {
+                    version_id: version_id.to_string(),
+                    cid: current.cid,
+                    size: current.size,
+                    content_type: current.content_type,
+                    key: current.key,
+                    bucket: current.bucket,
+                    created_at: current.updated_at,
+                    encryption: current.encryption,
+                    encryption_key_md5: current.encryption_key_md5,
+                    private: current.private,
+                    delete_marker: false,
+                    retention_mode: current.retention_mode,
+                    retain_until: current.retain_until,
+                    legal_hold: current.legal_hold,
+                    checksum_algorithm: current.checksum_algorithm,
+                    checksum: current.checksum,
+                    checksum_type: current.checksum_type,
+                    etag: current.etag,
+                    parts: current.parts,
+                }));
+        }
+
+        Ok(record)
+    }
+
+    /// Permanently deletes a version of an S3 object, making the latest remaining version current
+    pub async fn delete_object_version(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+    ) -> Result<Option<VersionDeletion>, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        let previous = sqlx::query_as!(
+            MetadataResponse,
+            r#"SELECT cid, size, content_type, bucket, object_key as key, updated_at, encryption, encryption_key_md5, private, version_id,
+                retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM metadata WHERE bucket = ? AND object_key = ?"#,
+            bucket,
+            key
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        Self::preserve_unversioned(&mut tx, bucket, key).await?;
+        let Some(deleted) = Self::take_version(&mut tx, bucket, key, version_id).await? else {
+            return Ok(None);
+        };
+
+        let latest = sqlx::query_as!(
+            ObjectVersion,
+            r#"SELECT version_id, cid, size, content_type, object_key as key, bucket, created_at, encryption, encryption_key_md5, private, delete_marker,
+                retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM versions WHERE bucket = ? AND object_key = ? ORDER BY seq DESC LIMIT 1"#,
+            bucket,
+            key,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let current = match latest {
+            Some(latest) if !latest.delete_marker => {
+                sqlx::query!(
+                    "INSERT INTO metadata (cid, bucket, object_key, content_type, size, encryption, encryption_key_md5, private, version_id, updated_at, retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+                    ON CONFLICT DO UPDATE SET cid = excluded.cid, size = excluded.size, content_type = excluded.content_type, updated_at = excluded.updated_at,
+                        encryption = excluded.encryption, encryption_key_md5 = excluded.encryption_key_md5, private = excluded.private, version_id = excluded.version_id,
+                        retention_mode = excluded.retention_mode, retain_until = excluded.retain_until, legal_hold = excluded.legal_hold,
+                        checksum_algorithm = excluded.checksum_algorithm, checksum = excluded.checksum, checksum_type = excluded.checksum_type, etag = excluded.etag, parts = excluded.parts",
+                    latest.cid,
+                    latest.bucket,
+                    latest.key,
+                    latest.content_type,
+                    latest.size,
+                    latest.encryption,
+                    latest.encryption_key_md5,
+                    latest.private,
+                    latest.version_id,
+                    latest.created_at,
+                    latest.retention_mode,
+                    latest.retain_until,
+                    latest.legal_hold,
+                    latest.checksum_algorithm,
+                    latest.checksum,
+                    latest.checksum_type,
+                    latest.etag,
+                    latest.parts,
+                )
+                .execute(&mut *tx)
+                .inspect_ok(|_| tracing::trace!("restored version"))
+                .instrument(tracing::debug_span!("restore version", key, version_id = latest.version_id))
+                .await?;
+                Some(latest.into())
+            }
+            _ => {
+                sqlx::query!(
+                    "DELETE FROM metadata WHERE bucket = ? AND object_key = ?",
+                    bucket,
+                    key
+                )
+                .execute(&mut *tx)
+                .inspect_ok(|_| tracing::trace!("deleted"))
+                .instrument(tracing::debug_span!("delete object", key))
+                .await?;
+                None
+            }
+        };
+
+        tx.commit().await?;
+        Ok(Some(VersionDeletion {
+            deleted,
+            previous,
+            current,
+        }))
+    }
+
+    /// List every version and delete marker of a bucket, newest first for each key
+    pub async fn list_object_versions(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<ObjectVersion>, DatabaseError> {
+        let records = sqlx::query_as!(
+            ObjectVersion,
+            r#"SELECT version_id as "version_id!: String", cid as "cid!: String", size as "size!: i64", content_type as "content_type!: String",
+                key as "key!: String", bucket as "bucket!: String", created_at as "created_at!: NaiveDateTime",
+                encryption as "encryption: String", encryption_key_md5 as "encryption_key_md5: String", private as "private!: bool", delete_marker as "delete_marker!: bool",
+                retention_mode as "retention_mode: String", retain_until as "retain_until: NaiveDateTime", legal_hold as "legal_hold!: bool",
+                checksum_algorithm as "checksum_algorithm: String", checksum as "checksum: String", checksum_type as "checksum_type: String", etag as "etag: String", parts as "parts: String"
+            FROM (
+                SELECT version_id, cid, size, content_type, object_key as key, bucket, created_at, encryption, encryption_key_md5, private, delete_marker, seq,
+                    retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts
+                FROM versions WHERE bucket = $1 AND substr(object_key, 1, length($2)) = $2
+                UNION ALL
+                -- Objects stored before versioning was enabled are the `null` version
+                SELECT 'null', cid, size, content_type, object_key, bucket, updated_at, encryption, encryption_key_md5, private, FALSE, 9223372036854775807,
+                    retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts
+                FROM metadata WHERE bucket = $1 AND substr(object_key, 1, length($2)) = $2 AND version_id IS NULL
+            )
+            ORDER BY key, seq DESC"#,
+            bucket,
+            prefix,
+        )
+        .fetch_all(&self.pool)
+        .inspect_ok(|records| tracing::trace!(total = records.len(), "listed versions"))
+        .instrument(tracing::debug_span!("list object versions", bucket, prefix))
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Versioning state of a bucket, `None` if it was never enabled
+    pub async fn get_bucket_versioning(
+        &self,
+        bucket: &str,
+    ) -> Result<Option<String>, DatabaseError> {
+        let versioning =
+            sqlx::query_scalar!("SELECT versioning FROM buckets WHERE bucket = ?", bucket)
+                .fetch_optional(&self.pool)
+                .instrument(tracing::debug_span!("get bucket versioning", bucket))
+                .await?;
+
+        Ok(versioning.flatten())
+    }
+
+    /// Store the versioning state of a bucket
+    pub async fn put_bucket_versioning(
+        &self,
+        bucket: &str,
+        versioning: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "INSERT INTO buckets (bucket, versioning) VALUES (?, ?) ON CONFLICT DO UPDATE SET versioning = excluded.versioning",
+            bucket,
+            versioning,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("stored bucket versioning"))
+        .instrument(tracing::debug_span!("put bucket versioning", bucket, versioning))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replication factor configured for a bucket
+    pub async fn get_bucket_replication(&self, bucket: &str) -> Result<Replication, DatabaseError> {
+        let replication = sqlx::query_as!(
+            Replication,
+            "SELECT replication_min, replication_max FROM buckets WHERE bucket = ?",
+            bucket
+        )
+        .fetch_optional(&self.pool)
+        .instrument(tracing::debug_span!("get bucket replication", bucket))
+        .await?;
+
+        Ok(replication.unwrap_or_default())
+    }
+
+    /// Store the replication factor of a bucket
+    pub async fn put_bucket_replication(
+        &self,
+        bucket: &str,
+        replication: Replication,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "INSERT INTO buckets (bucket, replication_min, replication_max) VALUES (?, ?, ?) ON CONFLICT DO UPDATE SET replication_min = excluded.replication_min, replication_max = excluded.replication_max",
+            bucket,
+            replication.replication_min,
+            replication.replication_max,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("stored bucket replication"))
+        .instrument(tracing::debug_span!("put bucket replication", bucket))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Options to add content of a bucket to IPFS, unset ones use the instance defaults
+    pub async fn get_bucket_add_options(
+        &self,
+        bucket: &str,
+    ) -> Result<crate::ipfs::AddOptions, DatabaseError> {
+        let options = sqlx::query_as!(
+            crate::ipfs::AddOptions,
+            r#"SELECT add_cid_version AS "cid_version: u32", add_hash AS hash, add_chunker AS chunker, add_raw_leaves AS raw_leaves, add_inline AS inline, add_trickle AS trickle FROM buckets WHERE bucket = ?"#,
+            bucket
+        )
+        .fetch_optional(&self.pool)
+        .instrument(tracing::debug_span!("get bucket add options", bucket))
+        .await?;
+
+        Ok(options.unwrap_or_default())
+    }
+
+    /// Store the options to add content of a bucket to IPFS
+    pub async fn put_bucket_add_options(
+        &self,
+        bucket: &str,
+        options: &crate::ipfs::AddOptions,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "INSERT INTO buckets (bucket, add_cid_version, add_hash, add_chunker, add_raw_leaves, add_inline, add_trickle) VALUES (?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO UPDATE SET add_cid_version = excluded.add_cid_version, add_hash = excluded.add_hash, add_chunker = excluded.add_chunker, add_raw_leaves = excluded.add_raw_leaves, add_inline = excluded.add_inline, add_trickle = excluded.add_trickle",
+            bucket,
+            options.cid_version,
+            options.hash,
+            options.chunker,
+            options.raw_leaves,
+            options.inline,
+            options.trickle,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("stored bucket add options"))
+        .instrument(tracing::debug_span!("put bucket add options", bucket))
+        .await?;
+
+        Ok(())
+    }
+
+    /// IPNS publishing of a bucket root, unset when not published
+    pub async fn get_bucket_ipns(&self, bucket: &str) -> Result<BucketIpns, DatabaseError> {
+        let ipns = sqlx::query_as!(
+            BucketIpns,
+            "SELECT ipns_key, ipns_name FROM buckets WHERE bucket = ?",
+            bucket
+        )
+        .fetch_optional(&self.pool)
+        .instrument(tracing::debug_span!("get bucket ipns", bucket))
+        .await?;
+
+        Ok(ipns.unwrap_or_default())
+    }
+
+    /// Store the IPNS publishing of a bucket root
+    pub async fn put_bucket_ipns(
+        &self,
+        bucket: &str,
+        ipns: &BucketIpns,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "INSERT INTO buckets (bucket, ipns_key, ipns_name) VALUES (?, ?, ?) ON CONFLICT DO UPDATE SET ipns_key = excluded.ipns_key, ipns_name = excluded.ipns_name",
+            bucket,
+            ipns.ipns_key,
+            ipns.ipns_name,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("stored bucket ipns"))
+        .instrument(tracing::debug_span!("put bucket ipns", bucket))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Buckets with their root published over IPNS
+    pub async fn list_ipns_buckets(&self) -> Result<Vec<String>, DatabaseError> {
+        let buckets = sqlx::query_scalar!(
+            "SELECT bucket FROM buckets WHERE ipns_key IS NOT NULL ORDER BY bucket"
+        )
+        .fetch_all(&self.pool)
+        .instrument(tracing::debug_span!("list ipns buckets"))
+        .await?;
+
+        Ok(buckets)
+    }
+
+    /// Replace the lifecycle rules of a bucket
+    pub async fn put_lifecycle_rules(
+        &self,
+        bucket: &str,
+        rules: &[LifecycleRule],
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("DELETE FROM lifecycle_rules WHERE bucket = ?", bucket)
+            .execute(&mut *tx)
+            .await?;
+
+        for rule in rules {
+            sqlx::query!(
+                "INSERT INTO lifecycle_rules (bucket, rule_id, prefix, enabled, expiration_days, noncurrent_days) VALUES (?, ?, ?, ?, ?, ?)",
+                bucket,
+                rule.rule_id,
+                rule.prefix,
+                rule.enabled,
+                rule.expiration_days,
+                rule.noncurrent_days,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit()
+            .inspect_ok(|()| tracing::trace!(total = rules.len(), "stored lifecycle rules"))
+            .instrument(tracing::debug_span!("put lifecycle rules", bucket))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Lifecycle rules of a bucket
+    pub async fn get_lifecycle_rules(
+        &self,
+        bucket: &str,
+    ) -> Result<Vec<LifecycleRule>, DatabaseError> {
+        let rules = sqlx::query_as!(
+            LifecycleRule,
+            "SELECT bucket, rule_id, prefix, enabled, expiration_days, noncurrent_days FROM lifecycle_rules WHERE bucket = ? ORDER BY rule_id",
+            bucket,
+        )
+        .fetch_all(&self.pool)
+        .instrument(tracing::debug_span!("get lifecycle rules", bucket))
+        .await?;
+
+        Ok(rules)
+    }
+
+    /// Enabled lifecycle rules of every bucket
+    pub async fn list_enabled_lifecycle_rules(&self) -> Result<Vec<LifecycleRule>, DatabaseError> {
+        let rules = sqlx::query_as!(
+            LifecycleRule,
+            "SELECT bucket, rule_id, prefix, enabled, expiration_days, noncurrent_days FROM lifecycle_rules WHERE enabled ORDER BY bucket, rule_id",
+        )
+        .fetch_all(&self.pool)
+        .instrument(tracing::debug_span!("list lifecycle rules"))
+        .await?;
+
+        Ok(rules)
+    }
+
+    /// Remove the lifecycle rules of a bucket
+    pub async fn delete_lifecycle_rules(&self, bucket: &str) -> Result<(), DatabaseError> {
+        sqlx::query!("DELETE FROM lifecycle_rules WHERE bucket = ?", bucket)
+            .execute(&self.pool)
+            .inspect_ok(|_| tracing::trace!("deleted lifecycle rules"))
+            .instrument(tracing::debug_span!("delete lifecycle rules", bucket))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Keys of current objects under a prefix last modified before a date
+    pub async fn find_objects_modified_before(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        before: NaiveDateTime,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let keys = sqlx::query_scalar!(
+            "SELECT object_key FROM metadata WHERE bucket = $1 AND substr(object_key, 1, length($2)) = $2 AND updated_at < $3",
+            bucket,
+            prefix,
+            before,
+        )
+        .fetch_all(&self.pool)
+        .inspect_ok(|keys| tracing::trace!(total = keys.len(), "found objects"))
+        .instrument(tracing::debug_span!("find objects modified before", bucket, prefix, %before))
+        .await?;
+
+        Ok(keys)
+    }
+
+    /// Keys and version ids of versions under a prefix that stopped being current before a date,
+    /// that is, when the next version was created
+    pub async fn find_noncurrent_versions_before(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        before: NaiveDateTime,
+    ) -> Result<Vec<(String, String)>, DatabaseError> {
+        let versions = sqlx::query!(
+            "SELECT v.object_key as key, v.version_id FROM versions v
+            WHERE v.bucket = $1 AND substr(v.object_key, 1, length($2)) = $2
+                AND (SELECT MIN(n.created_at) FROM versions n WHERE n.bucket = v.bucket AND n.object_key = v.object_key AND n.seq > v.seq) < $3",
+            bucket,
+            prefix,
+            before,
+        )
+        .fetch_all(&self.pool)
+        .inspect_ok(|versions| tracing::trace!(total = versions.len(), "found versions"))
+        .instrument(tracing::debug_span!("find noncurrent versions before", bucket, prefix, %before))
+        .await?;
+
+        Ok(versions
+            .into_iter()
+            .map(|version| (version.key, version.version_id))
+            .collect())
+    }
+
+    async fn replace_tags(
+        tx: &mut sqlx::SqliteConnection,
+        bucket: &str,
+        key: &str,
+        tags: &[ObjectTag],
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "DELETE FROM tags WHERE bucket = ? AND object_key = ?",
+            bucket,
+            key
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for tag in tags {
+            sqlx::query!(
+                "INSERT INTO tags (bucket, object_key, tag_key, tag_value) VALUES (?, ?, ?, ?)",
+                bucket,
+                key,
+                tag.key,
+                tag.value,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tracing::trace!(total = tags.len(), "stored tags");
+        Ok(())
+    }
+
+    /// Replace the tags of an object
+    pub async fn put_object_tags(
+        &self,
+        bucket: &str,
+        key: &str,
+        tags: &[ObjectTag],
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        Self::replace_tags(&mut tx, bucket, key, tags)
+            .instrument(tracing::debug_span!("put object tags", key))
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Tags of an object, sorted by key
+    pub async fn get_object_tags(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Vec<ObjectTag>, DatabaseError> {
+        let tags = sqlx::query_as!(
+            ObjectTag,
+            "SELECT tag_key as key, tag_value as value FROM tags WHERE bucket = ? AND object_key = ? ORDER BY tag_key",
+            bucket,
+            key,
+        )
+        .fetch_all(&self.pool)
+        .inspect_ok(|tags| tracing::trace!(total = tags.len(), "retrieved tags"))
+        .instrument(tracing::debug_span!("get object tags", key))
+        .await?;
+
+        Ok(tags)
+    }
+
+    /// Set the retention of the current object or one of its versions.
+    /// Objects stored before versioning was enabled are the `null` version
+    pub async fn put_object_retention(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+        mode: Option<&str>,
+        retain_until: Option<NaiveDateTime>,
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE metadata SET retention_mode = $4, retain_until = $5 WHERE bucket = $1 AND object_key = $2 AND COALESCE(version_id, 'null') = COALESCE($3, 'null')",
+            bucket,
+            key,
+            version_id,
+            mode,
+            retain_until,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE versions SET retention_mode = $4, retain_until = $5 WHERE bucket = $1 AND object_key = $2 AND version_id = COALESCE($3, 'null')",
+            bucket,
+            key,
+            version_id,
+            mode,
+            retain_until,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit()
+            .inspect_ok(|()| tracing::trace!("stored retention"))
+            .instrument(tracing::debug_span!(
+                "put object retention",
+                key,
+                version_id,
+                mode
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set the legal hold of the current object or one of its versions.
+    /// Objects stored before versioning was enabled are the `null` version
+    pub async fn put_object_legal_hold(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+        legal_hold: bool,
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE metadata SET legal_hold = $4 WHERE bucket = $1 AND object_key = $2 AND COALESCE(version_id, 'null') = COALESCE($3, 'null')",
+            bucket,
+            key,
+            version_id,
+            legal_hold,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE versions SET legal_hold = $4 WHERE bucket = $1 AND object_key = $2 AND version_id = COALESCE($3, 'null')",
+            bucket,
+            key,
+            version_id,
+            legal_hold,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit()
+            .inspect_ok(|()| tracing::trace!("stored legal hold"))
+            .instrument(tracing::debug_span!(
+                "put object legal hold",
+                key,
+                version_id,
+                legal_hold
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Count how many objects and versions reference a CID
     pub async fn cid_count(&self, cid: &str) -> Result<i64, DatabaseError> {
-        let count = sqlx::query_scalar!("SELECT COUNT(1) FROM metadata WHERE cid = ?", cid)
-            .fetch_one(&self.pool)
-            .inspect_ok(|total| tracing::debug!(total, "Stored CID count"))
-            .instrument(tracing::debug_span!("counting", cid))
+        let count = sqlx::query_scalar!(
+            r#"SELECT (SELECT COUNT(1) FROM metadata WHERE cid = $1) + (SELECT COUNT(1) FROM versions WHERE cid = $1) as "count!: i64""#,
+            cid
+        )
+        .fetch_one(&self.pool)
+        .inspect_ok(|total| tracing::debug!(total, "Stored CID count"))
+        .instrument(tracing::debug_span!("counting", cid))
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Bucket, key and CID of every current object
+    pub async fn list_current_objects(&self) -> Result<Vec<StoredObject>, DatabaseError> {
+        let objects = sqlx::query_as!(
+            StoredObject,
+            "SELECT bucket, object_key AS key, cid FROM metadata"
+        )
+        .fetch_all(&self.pool)
+        .instrument(tracing::debug_span!("list current objects"))
+        .await?;
+
+        Ok(objects)
+    }
+
+    /// Every CID referenced by current objects, their versions or snapshots
+    /// Private content is left out, as it is only kept by its MFS link
+    pub async fn list_referenced_cids(&self) -> Result<Vec<String>, DatabaseError> {
+        let cids = sqlx::query_scalar!(
+            "SELECT cid FROM metadata WHERE private = FALSE UNION SELECT cid FROM versions WHERE delete_marker = FALSE AND private = FALSE UNION SELECT cid FROM snapshots"
+        )
+        .fetch_all(&self.pool)
+        .instrument(tracing::debug_span!("list referenced cids"))
+        .await?;
+
+        Ok(cids)
+    }
+
+    /// Metadata of every current object of a bucket, ordered by key
+    pub async fn list_bucket_objects(
+        &self,
+        bucket: &str,
+    ) -> Result<Vec<MetadataResponse>, DatabaseError> {
+        let records = sqlx::query_as!(
+            MetadataResponse,
+            r#"SELECT cid, size, content_type, bucket, object_key as key, updated_at, encryption, encryption_key_md5, private, version_id,
+                retention_mode, retain_until, legal_hold, checksum_algorithm, checksum, checksum_type, etag, parts FROM metadata WHERE bucket = ? ORDER BY object_key"#,
+            bucket
+        )
+        .fetch_all(&self.pool)
+        .inspect_ok(|records| tracing::trace!(total = records.len(), "listed objects"))
+        .instrument(tracing::debug_span!("list bucket objects", bucket))
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Number of current objects of a bucket under retention or legal hold at `now`
+    pub async fn count_locked_objects(
+        &self,
+        bucket: &str,
+        now: NaiveDateTime,
+    ) -> Result<i64, DatabaseError> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(1) as "count!: i64" FROM metadata WHERE bucket = ? AND (legal_hold OR retain_until > ?)"#,
+            bucket,
+            now
+        )
+        .fetch_one(&self.pool)
+        .instrument(tracing::debug_span!("count locked objects", bucket))
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Counts the current objects of a bucket kept out of the public IPFS network
+    pub async fn count_private_objects(&self, bucket: &str) -> Result<i64, DatabaseError> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(1) as "count!: i64" FROM metadata WHERE bucket = ? AND private"#,
+            bucket
+        )
+        .fetch_one(&self.pool)
+        .instrument(tracing::debug_span!("count private objects", bucket))
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Replaces every current object of a bucket in a single transaction, returning how many were replaced
+    pub async fn replace_bucket_objects(
+        &self,
+        bucket: &str,
+        objects: &[NewObjectMetadata<'_>],
+    ) -> Result<u64, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query!("DELETE FROM metadata WHERE bucket = ?", bucket)
+            .execute(&mut *tx)
+            .inspect_ok(|result| tracing::trace!(total = result.rows_affected(), "deleted objects"))
+            .instrument(tracing::debug_span!("delete bucket objects", bucket))
             .await?;
 
+        for object in objects {
+            Self::insert_object(&mut tx, object).await?;
+        }
+
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Record a snapshot of a bucket, returning `None` when the label is already taken
+    pub async fn create_snapshot(
+        &self,
+        bucket: &str,
+        label: &str,
+        cid: &str,
+    ) -> Result<Option<Snapshot>, DatabaseError> {
+        let result = sqlx::query!(
+            "INSERT INTO snapshots (bucket, label, cid) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+            bucket,
+            label,
+            cid
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("stored snapshot"))
+        .instrument(tracing::debug_span!("create snapshot", bucket, label, cid))
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get_snapshot(bucket, label).await
+    }
+
+    /// Snapshot of a bucket by its label
+    pub async fn get_snapshot(
+        &self,
+        bucket: &str,
+        label: &str,
+    ) -> Result<Option<Snapshot>, DatabaseError> {
+        let snapshot = sqlx::query_as!(
+            Snapshot,
+            r#"SELECT bucket, label, cid, created_at as "created_at!: NaiveDateTime" FROM snapshots WHERE bucket = ? AND label = ?"#,
+            bucket,
+            label
+        )
+        .fetch_optional(&self.pool)
+        .instrument(tracing::debug_span!("get snapshot", bucket, label))
+        .await?;
+
+        Ok(snapshot)
+    }
+
+    /// Whether a CID is the root of any snapshot
+    pub async fn is_snapshot(&self, cid: &str) -> Result<bool, DatabaseError> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(1) as "count!: i64" FROM snapshots WHERE cid = ?"#,
+            cid
+        )
+        .fetch_one(&self.pool)
+        .instrument(tracing::debug_span!("is snapshot", cid))
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Snapshots of a bucket, oldest first
+    pub async fn list_snapshots(&self, bucket: &str) -> Result<Vec<Snapshot>, DatabaseError> {
+        let snapshots = sqlx::query_as!(
+            Snapshot,
+            r#"SELECT bucket, label, cid, created_at as "created_at!: NaiveDateTime" FROM snapshots WHERE bucket = ? ORDER BY created_at, label"#,
+            bucket
+        )
+        .fetch_all(&self.pool)
+        .instrument(tracing::debug_span!("list snapshots", bucket))
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    /// Number of current objects of a bucket with keys starting with `prefix`
+    pub async fn count_objects_with_prefix(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<i64, DatabaseError> {
+        // `LIKE` would match `%` and `_` of the prefix as wildcards, and ignore case
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(1) as "count!: i64" FROM metadata WHERE bucket = $1 AND substr(object_key, 1, length($2)) = $2"#,
+            bucket,
+            prefix
+        )
+        .fetch_one(&self.pool)
+        .instrument(tracing::debug_span!("count objects with prefix", bucket, prefix))
+        .await?;
+
         Ok(count)
     }
 
+    /// Queue content to be pinned on the remote Pinning Service.
+    /// Content queued for removal is kept, refreshing the status of its existing remote pin.
+    pub async fn queue_remote_pin(&self, cid: &str) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            r#"
+INSERT INTO remote_pins (cid) VALUES (?)
+ON CONFLICT (cid) DO UPDATE SET
+    status = CASE WHEN request_id IS NULL THEN 'pending' ELSE 'queued' END,
+    attempts = 0,
+    updated_at = CURRENT_TIMESTAMP
+WHERE status = 'unpin'
+            "#,
+            cid,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("queued remote pin"))
+        .instrument(tracing::debug_span!("queue remote pin", cid))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Queue the removal of the remote pin of content no longer referenced
+    pub async fn queue_remote_unpin(&self, cid: &str) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "UPDATE remote_pins SET status = 'unpin', attempts = 0, updated_at = CURRENT_TIMESTAMP WHERE cid = ?",
+            cid,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("queued remote unpin"))
+        .instrument(tracing::debug_span!("queue remote unpin", cid))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remote pins not yet settled, with less than `max_attempts` failures
+    pub async fn list_unsettled_remote_pins(
+        &self,
+        max_attempts: i64,
+    ) -> Result<Vec<RemotePin>, DatabaseError> {
+        let pins = sqlx::query_as!(
+            RemotePin,
+            "SELECT cid, request_id, status, attempts FROM remote_pins WHERE status != 'pinned' AND attempts < ? ORDER BY updated_at",
+            max_attempts,
+        )
+        .fetch_all(&self.pool)
+        .inspect_ok(|pins| tracing::trace!(total = pins.len(), "found unsettled remote pins"))
+        .instrument(tracing::debug_span!("list unsettled remote pins"))
+        .await?;
+
+        Ok(pins)
+    }
+
+    /// Store the status of a remote pin reported by the Pinning Service.
+    /// `failed` pins count as a failed attempt, to be requested again.
+    pub async fn update_remote_pin(
+        &self,
+        cid: &str,
+        request_id: &str,
+        status: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            r#"
+UPDATE remote_pins SET
+    request_id = $2,
+    status = $3,
+    attempts = CASE WHEN $3 = 'failed' THEN attempts + 1 ELSE 0 END,
+    last_error = NULL,
+    updated_at = CURRENT_TIMESTAMP
+WHERE cid = $1 AND status != 'unpin'
+            "#,
+            cid,
+            request_id,
+            status,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("updated remote pin"))
+        .instrument(tracing::debug_span!(
+            "update remote pin",
+            cid,
+            request_id,
+            status
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record a failed request to the Pinning Service, to be retried
+    pub async fn fail_remote_pin(&self, cid: &str, error: &str) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "UPDATE remote_pins SET attempts = attempts + 1, last_error = ?, updated_at = CURRENT_TIMESTAMP WHERE cid = ?",
+            error,
+            cid,
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("failed remote pin"))
+        .instrument(tracing::debug_span!("fail remote pin", cid))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stop tracking content once its remote pin is removed, unless it was pinned again meanwhile
+    pub async fn delete_remote_pin(&self, cid: &str) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "DELETE FROM remote_pins WHERE cid = ? AND status = 'unpin'",
+            cid
+        )
+        .execute(&self.pool)
+        .inspect_ok(|_| tracing::trace!("deleted remote pin"))
+        .instrument(tracing::debug_span!("delete remote pin", cid))
+        .await?;
+
+        Ok(())
+    }
+
     /// Find the shallowest removable directory path from a deleted object's path.
     /// Returns the shallowest directory that can be safely removed (i.e., no other objects exist in it).
     pub async fn find_shallowest_removable_directory(
@@ -212,3 +1459,52 @@ SELECT count(1) FROM metadata where bucket = ? and object_key LIKE ?;
         Ok(shallow.map(UnixPath::to_owned))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Database, NewObjectMetadata};
+    use crate::cli::SqliteConfig;
+
+    #[tokio::test]
+    async fn test_count_objects_with_prefix() {
+        let path = std::env::temp_dir().join(format!("aricanduva-test-{}.db", std::process::id()));
+        let config = SqliteConfig {
+            auto_vacuum: None,
+            journal_mode: None,
+            synchronous: None,
+        };
+        let db = Database::initialize(&path, &config).await.unwrap();
+        for key in ["logs/a_1", "logs/ab1", "Logs/a_2"] {
+            db.store_object_metadata(&NewObjectMetadata {
+                bucket: "banana-bucket",
+                key,
+                cid: "bafkqaaa",
+                size: 0,
+                content_type: "text/plain",
+                encryption: None,
+                encryption_key_md5: None,
+                private: false,
+                version_id: None,
+                retention_mode: None,
+                retain_until: None,
+                legal_hold: false,
+                checksum_algorithm: None,
+                checksum: None,
+                checksum_type: None,
+                etag: None,
+                parts: None,
+                tags: &[],
+            })
+            .await
+            .unwrap();
+        }
+
+        // `_` and case are matched literally
+        let count = db
+            .count_objects_with_prefix("banana-bucket", "logs/a_")
+            .await;
+        db.pool.close().await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(count.unwrap(), 1);
+    }
+}
@@ -0,0 +1,454 @@
+// Server-side encryption of content added to IPFS
+// Content is encrypted before `ipfs add`, so the CID only ever reveals ciphertext
+
+use std::str::FromStr;
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit,
+    aead::stream::{DecryptorBE32, EncryptorBE32},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::{Bytes, BytesMut};
+use conf::Conf;
+use futures::{Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use http::{HeaderMap, response::Builder};
+use md5::{Digest, Md5};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::database::MetadataResponse;
+
+pub type Key = [u8; 32];
+
+/// The only algorithm supported by S3 for both `SSE-S3` and `SSE-C`
+pub const ALGORITHM: &str = "AES256";
+
+/// Value stored on metadata for objects encrypted with customer provided keys
+const CUSTOMER_MODE: &str = "SSE-C";
+
+const SSE_HEADER: &str = "x-amz-server-side-encryption";
+const SSE_C_ALGORITHM_HEADER: &str = "x-amz-server-side-encryption-customer-algorithm";
+const SSE_C_KEY_HEADER: &str = "x-amz-server-side-encryption-customer-key";
+const SSE_C_KEY_MD5_HEADER: &str = "x-amz-server-side-encryption-customer-key-md5";
+
+/// Identifies the format of encrypted content: `ARI` + format version
+const MAGIC: &[u8; 4] = b"ARI\x01";
+const SALT_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN;
+/// Plaintext size of each encrypted segment
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const ENCRYPTED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_LEN;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Unsupported encryption algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Invalid customer encryption key")]
+    InvalidKey,
+
+    #[error("Object is encrypted with a customer key that was not provided")]
+    MissingCustomerKey,
+
+    #[error("Customer encryption key does not match the object key")]
+    KeyMismatch,
+
+    #[error("Server side encryption key is not configured")]
+    MissingServerKey,
+
+    #[error("Could not decrypt content")]
+    Decrypt,
+
+    #[error("IPFS error: {0}")]
+    Ipfs(#[from] crate::ipfs::Error),
+}
+
+impl Error {
+    pub fn status_code(&self) -> http::StatusCode {
+        match self {
+            Error::UnsupportedAlgorithm(_) | Error::InvalidKey | Error::MissingCustomerKey => {
+                http::StatusCode::BAD_REQUEST
+            }
+            Error::KeyMismatch => http::StatusCode::FORBIDDEN,
+            Error::MissingServerKey => http::StatusCode::NOT_IMPLEMENTED,
+            Error::Decrypt | Error::Ipfs(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Base64 encoded 256-bit key
+#[derive(Clone)]
+pub struct ServerKey(Key);
+
+impl FromStr for ServerKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        decode_key(s)
+            .map(ServerKey)
+            .ok_or_else(|| "encryption key must be a base64 encoded 256-bit key".to_string())
+    }
+}
+
+impl std::fmt::Debug for ServerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("REDACTED")
+    }
+}
+
+#[derive(Debug, Clone, Conf)]
+pub struct EncryptionConfig {
+    #[conf(long, env)]
+    /// Base64 encoded 256-bit key used for server-side encryption (`x-amz-server-side-encryption: AES256`)
+    /// Generate one with `openssl rand -base64 32`
+    pub key: Option<ServerKey>,
+
+    #[conf(long, env, default(false))]
+    /// Encrypt every upload with the server key, even when not requested by the client
+    pub always: Option<bool>,
+}
+
+impl EncryptionConfig {
+    /// `SSE-S3` encryption with the server key, when configured
+    pub fn server(&self) -> Option<Encryption> {
+        self.key
+            .as_ref()
+            .map(|ServerKey(key)| Encryption::Server(*key))
+    }
+}
+
+/// Whether content starts with the header of content produced by [`Encryption::encrypt`]
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(MAGIC)
+}
+
+/// Plaintext size of content produced by [`Encryption::encrypt`], from its encrypted size
+pub fn plaintext_size(encrypted_size: u64) -> Option<u64> {
+    let body = usize::try_from(encrypted_size)
+        .ok()?
+        .checked_sub(HEADER_LEN)?;
+    let segments = body.div_ceil(ENCRYPTED_CHUNK_SIZE).max(1);
+    u64::try_from(body.checked_sub(segments * TAG_LEN)?).ok()
+}
+
+fn decode_key(value: &str) -> Option<Key> {
+    BASE64_STANDARD.decode(value).ok()?.try_into().ok()
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Customer provided key (`SSE-C`) sent on every request of an object
+///
+/// Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/ServerSideEncryptionCustomerKeys.html>
+pub struct CustomerKey {
+    key: Key,
+    key_md5: String,
+}
+
+impl CustomerKey {
+    /// Reads `x-amz-server-side-encryption-customer-*` headers, validating the key against its MD5
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, Error> {
+        let Some(algorithm) = header(headers, SSE_C_ALGORITHM_HEADER) else {
+            return Ok(None);
+        };
+        if algorithm != ALGORITHM {
+            return Err(Error::UnsupportedAlgorithm(algorithm.to_string()));
+        }
+
+        let key = header(headers, SSE_C_KEY_HEADER)
+            .and_then(decode_key)
+            .ok_or(Error::InvalidKey)?;
+        let key_md5 = BASE64_STANDARD.encode(Md5::digest(key));
+        if header(headers, SSE_C_KEY_MD5_HEADER).is_some_and(|md5| md5 != key_md5) {
+            return Err(Error::InvalidKey);
+        }
+
+        Ok(Some(CustomerKey { key, key_md5 }))
+    }
+}
+
+pub enum Encryption {
+    /// `SSE-S3` semantics, using the key from [`EncryptionConfig`]
+    Server(Key),
+    /// `SSE-C` semantics, using the key provided on the request headers
+    Customer(CustomerKey),
+}
+
+impl Encryption {
+    /// Encryption requested on a `PutObject` or `CreateMultipartUpload` request
+    pub fn requested(
+        config: &EncryptionConfig,
+        headers: &HeaderMap,
+    ) -> Result<Option<Self>, Error> {
+        if let Some(customer) = CustomerKey::from_headers(headers)? {
+            return Ok(Some(Encryption::Customer(customer)));
+        }
+
+        let server_side = match header(headers, SSE_HEADER) {
+            Some(ALGORITHM) => true,
+            Some(algorithm) => return Err(Error::UnsupportedAlgorithm(algorithm.to_string())),
+            None => config.always.unwrap_or_default(),
+        };
+
+        if !server_side {
+            return Ok(None);
+        }
+        match &config.key {
+            Some(ServerKey(key)) => Ok(Some(Encryption::Server(*key))),
+            None => Err(Error::MissingServerKey),
+        }
+    }
+
+    /// Encryption used to store an object, validating customer keys provided on the request headers
+    pub fn stored(
+        config: &EncryptionConfig,
+        metadata: &MetadataResponse,
+        headers: &HeaderMap,
+    ) -> Result<Option<Self>, Error> {
+        match metadata.encryption.as_deref() {
+            None => Ok(None),
+            Some(CUSTOMER_MODE) => {
+                let customer =
+                    CustomerKey::from_headers(headers)?.ok_or(Error::MissingCustomerKey)?;
+                if metadata.encryption_key_md5.as_deref() != Some(customer.key_md5.as_str()) {
+                    return Err(Error::KeyMismatch);
+                }
+                Ok(Some(Encryption::Customer(customer)))
+            }
+            Some(_) => match &config.key {
+                Some(ServerKey(key)) => Ok(Some(Encryption::Server(*key))),
+                None => Err(Error::MissingServerKey),
+            },
+        }
+    }
+
+    fn key(&self) -> &Key {
+        match self {
+            Encryption::Server(key) => key,
+            Encryption::Customer(customer) => &customer.key,
+        }
+    }
+
+    /// Value stored on the metadata `encryption` column
+    pub fn mode(&self) -> &'static str {
+        match self {
+            Encryption::Server(_) => ALGORITHM,
+            Encryption::Customer(_) => CUSTOMER_MODE,
+        }
+    }
+
+    /// `MD5` of the customer key, stored to validate later requests
+    pub fn key_md5(&self) -> Option<&str> {
+        match self {
+            Encryption::Server(_) => None,
+            Encryption::Customer(customer) => Some(&customer.key_md5),
+        }
+    }
+
+    /// Adds the S3 response headers describing the encryption
+    pub fn response_headers(&self, builder: Builder) -> Builder {
+        match self {
+            Encryption::Server(_) => builder.header(SSE_HEADER, ALGORITHM),
+            Encryption::Customer(customer) => builder
+                .header(SSE_C_ALGORITHM_HEADER, ALGORITHM)
+                .header(SSE_C_KEY_MD5_HEADER, &customer.key_md5),
+        }
+    }
+
+    /// Per-object key, so nonces never repeat under the same key
+    fn cipher(&self, salt: &[u8]) -> Aes256Gcm {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(self.key())
+            .expect("HMAC can take key of any size");
+        mac.update(salt);
+        Aes256Gcm::new(&mac.finalize().into_bytes())
+    }
+
+    /// Encrypts content using AES-256-GCM in `STREAM` segments of [`CHUNK_SIZE`]
+    ///
+    /// Layout: `MAGIC | salt | segment* | last segment`
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut salt = [0; SALT_LEN];
+        rand::rng().fill(&mut salt);
+
+        let segments = plaintext.len().saturating_sub(1) / CHUNK_SIZE;
+        let mut output =
+            Vec::with_capacity(HEADER_LEN + plaintext.len() + (segments + 1) * TAG_LEN);
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&salt);
+
+        let (body, last) = plaintext.split_at(segments * CHUNK_SIZE);
+        let mut encryptor = EncryptorBE32::from_aead(self.cipher(&salt), &[0; 7].into());
+        for chunk in body.chunks(CHUNK_SIZE) {
+            output.extend(
+                encryptor
+                    .encrypt_next(chunk)
+                    .expect("AES-GCM encryption does not fail for bounded segments"),
+            );
+        }
+        output.extend(
+            encryptor
+                .encrypt_last(last)
+                .expect("AES-GCM encryption does not fail for bounded segments"),
+        );
+        output
+    }
+
+    /// Whether content produced by [`Encryption::encrypt`] was encrypted with this key,
+    /// authenticating its first segment
+    pub async fn decrypts<S>(self, content: S) -> bool
+    where
+        S: Stream<Item = Result<Bytes, crate::ipfs::Error>> + Unpin,
+    {
+        let decrypted = self.decrypt(content);
+        let mut decrypted = std::pin::pin!(decrypted);
+        matches!(decrypted.next().await, Some(Ok(_)))
+    }
+
+    /// Decrypts content produced by [`Encryption::encrypt`] as it is streamed from IPFS
+    pub fn decrypt<S>(self, content: S) -> impl Stream<Item = Result<Bytes, Error>> + use<S>
+    where
+        S: Stream<Item = Result<Bytes, crate::ipfs::Error>> + Unpin,
+    {
+        struct State<S> {
+            encryption: Encryption,
+            content: S,
+            buffer: BytesMut,
+            decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+        }
+
+        let state = State {
+            encryption: self,
+            content,
+            buffer: BytesMut::new(),
+            decryptor: None,
+        };
+
+        futures::stream::try_unfold(state, |mut state| async move {
+            loop {
+                if state.decryptor.is_none() && state.buffer.len() >= HEADER_LEN {
+                    let header = state.buffer.split_to(HEADER_LEN);
+                    if &header[..MAGIC.len()] != MAGIC {
+                        return Err(Error::Decrypt);
+                    }
+                    let cipher = state.encryption.cipher(&header[MAGIC.len()..]);
+                    state.decryptor = Some(DecryptorBE32::from_aead(cipher, &[0; 7].into()));
+                }
+
+                // The last segment is only known once the content is over,
+                // so a segment is only released when more content follows it
+                if let Some(decryptor) = state.decryptor.as_mut()
+                    && state.buffer.len() > ENCRYPTED_CHUNK_SIZE
+                {
+                    let segment = state.buffer.split_to(ENCRYPTED_CHUNK_SIZE);
+                    let plaintext = decryptor
+                        .decrypt_next(segment.as_ref())
+                        .map_err(|_| Error::Decrypt)?;
+                    return Ok(Some((Bytes::from(plaintext), state)));
+                }
+
+                if let Some(chunk) = state.content.next().await {
+                    state.buffer.extend_from_slice(&chunk?);
+                    continue;
+                }
+
+                let Some(decryptor) = state.decryptor.take() else {
+                    // Either already finished or too short to be encrypted content
+                    return if state.buffer.is_empty() {
+                        Ok(None)
+                    } else {
+                        Err(Error::Decrypt)
+                    };
+                };
+                let segment = state.buffer.split();
+                let plaintext = decryptor
+                    .decrypt_last(segment.as_ref())
+                    .map_err(|_| Error::Decrypt)?;
+                return Ok(Some((Bytes::from(plaintext), state)));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use futures::TryStreamExt;
+
+    use super::{CHUNK_SIZE, Encryption, is_encrypted, plaintext_size};
+
+    async fn roundtrip(plaintext: &[u8], network_chunk: usize) -> Vec<u8> {
+        let ciphertext = Encryption::Server([7; 32]).encrypt(plaintext);
+        assert_ne!(&ciphertext[..], plaintext);
+
+        let stream = futures::stream::iter(
+            ciphertext
+                .chunks(network_chunk)
+                .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                .collect::<Vec<_>>(),
+        );
+        Encryption::Server([7; 32])
+            .decrypt(stream)
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        for size in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            let plaintext = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            assert_eq!(roundtrip(&plaintext, 1000).await, plaintext);
+            assert_eq!(roundtrip(&plaintext, 4 * CHUNK_SIZE).await, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_plaintext_size() {
+        for size in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            let ciphertext = Encryption::Server([7; 32]).encrypt(&vec![0; size]);
+            assert!(is_encrypted(&ciphertext));
+            assert_eq!(plaintext_size(ciphertext.len() as u64), Some(size as u64));
+        }
+        assert_eq!(plaintext_size(4), None);
+        assert!(!is_encrypted(b"banana"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_key() {
+        let ciphertext = Encryption::Server([7; 32]).encrypt(b"banana");
+        let stream = futures::stream::iter([Ok(Bytes::from(ciphertext))]);
+        let result = Encryption::Server([8; 32])
+            .decrypt(stream)
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decrypts_with_key() {
+        let ciphertext = Bytes::from(Encryption::Server([7; 32]).encrypt(&[1; 100_000]));
+        let stream = || futures::stream::iter([Ok(ciphertext.clone())]);
+        assert!(Encryption::Server([7; 32]).decrypts(stream()).await);
+        assert!(!Encryption::Server([8; 32]).decrypts(stream()).await);
+    }
+}
@@ -0,0 +1,467 @@
+// Consistency check between the SQLite metadata, the MFS tree and the pinset
+// `PutObject` adds content before storing metadata, and `DeleteObject` unlinks and unpins around deleting it,
+// so a crash in between leaves them diverging. Discrepancies are reported, and repaired on request.
+// Uploads in progress also link and pin content before storing metadata, so untracked entries and orphan pins
+// are only removed once they outlive `fsck.grace_period`.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use conf::Conf;
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+use typed_path::{UnixPath, UnixPathBuf};
+
+use crate::AppState;
+use crate::cli::{PinningBackend, RunConfig};
+use crate::database::{Database, DatabaseError, StoredObject};
+use crate::ipfs::{self, IpfsClient, MfsEntry};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("IPFS error: {0}")]
+    Ipfs(#[from] ipfs::Error),
+
+    #[error("Invalid path: {0}")]
+    Path(#[from] typed_path::CheckedPathError),
+}
+
+#[derive(Debug, Conf, Clone)]
+pub struct FsckConfig {
+    #[conf(long, env)]
+    /// Also check consistency periodically in background (eg: `1d`). Disabled when unset
+    pub interval: Option<humantime::Duration>,
+
+    #[conf(long, env, default(false))]
+    /// Repair discrepancies: re-link objects into MFS, re-pin their content and remove MFS entries without objects
+    /// Objects whose content can't be retrieved are only reported
+    pub repair: Option<bool>,
+
+    #[conf(long, env, default(false))]
+    /// Also unpin CIDs not referenced by any object when repairing
+    /// Only safe on IPFS Nodes dedicated to the service, as it unpins content pinned by other tools
+    pub unpin_orphans: Option<bool>,
+
+    #[conf(long, env, default_value = "10m")]
+    /// How long untracked MFS entries and orphan pins must remain before being removed when repairing,
+    /// so uploads in progress store their metadata first. Should exceed `import_timeout`
+    pub grace_period: humantime::Duration,
+}
+
+/// Discrepancies found, and repairs that failed
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub repair: bool,
+    /// MFS paths of objects missing, or linking other content
+    pub unlinked: Vec<String>,
+    /// MFS paths of objects whose content could not be re-linked, kept for operators to review
+    pub dangling: Vec<String>,
+    /// MFS entries under `folder_prefix` without objects
+    pub untracked: Vec<String>,
+    /// CIDs referenced by objects or snapshots but not pinned on the IPFS Node
+    pub unpinned: Vec<String>,
+    /// CIDs pinned on the IPFS Node but not referenced by objects or snapshots
+    pub orphan_pins: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl Report {
+    fn is_clean(&self) -> bool {
+        self.unlinked.is_empty()
+            && self.dangling.is_empty()
+            && self.untracked.is_empty()
+            && self.unpinned.is_empty()
+            && self.orphan_pins.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+/// Runs `aricanduva fsck`, printing the report as JSON
+/// Returns the exit code, failing when discrepancies are not repaired
+pub async fn command(db: &Database, ipfs: &IpfsClient, config: &RunConfig) -> i32 {
+    match check(db, ipfs, config).await {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            i32::from(
+                !report.errors.is_empty()
+                    || !report.dangling.is_empty()
+                    || !(report.repair || report.is_clean()),
+            )
+        }
+        Err(e) => {
+            eprintln!("Failed to check consistency: {e}");
+            1
+        }
+    }
+}
+
+/// Checks consistency on each `fsck_interval`
+pub async fn run(state: AppState, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        match check(&state.db, &state.ipfs_client, &state.config)
+            .instrument(tracing::info_span!("fsck"))
+            .await
+        {
+            Ok(report) if report.is_clean() => tracing::info!("No discrepancies found"),
+            Ok(report) => tracing::warn!(
+                repair = report.repair,
+                unlinked = report.unlinked.len(),
+                dangling = report.dangling.len(),
+                untracked = report.untracked.len(),
+                unpinned = report.unpinned.len(),
+                orphan_pins = report.orphan_pins.len(),
+                errors = ?report.errors,
+                "Found discrepancies"
+            ),
+            Err(e) => tracing::error!(error = %e, "Failed to check consistency"),
+        }
+    }
+}
+
+/// Compares the objects stored on `SQLite` with the MFS tree and the pinset, repairing them when configured
+pub async fn check(db: &Database, ipfs: &IpfsClient, config: &RunConfig) -> Result<Report, Error> {
+    let mut report = Report {
+        repair: config.fsck.repair.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let root = crate::s3::mfs_root(&config.folder_prefix)?;
+
+    // MFS is walked before listing objects, so objects stored meanwhile are not reported as untracked
+    let entries = ipfs.walk(&root).await?;
+    let objects = db.list_current_objects().await?;
+    let mut expected = HashMap::with_capacity(objects.len());
+    for object in &objects {
+        let path = crate::s3::normalized_path(&config.folder_prefix, &object.bucket, &object.key)?;
+        expected.insert(path, object);
+    }
+
+    for (path, object) in unlinked(&entries, &expected) {
+        relink(db, ipfs, config, path, object, &mut report).await;
+    }
+
+    let untracked = untracked(&entries, &expected);
+    report.untracked = untracked
+        .iter()
+        .map(|entry| entry.path.to_string_lossy().into_owned())
+        .collect();
+
+    // Pins are managed by IPFS Cluster otherwise
+    if matches!(config.pinning_backend, PinningBackend::Kubo) {
+        check_pins(db, ipfs, config, &mut report).await?;
+    }
+
+    let unpin_orphans = config.fsck.unpin_orphans.unwrap_or_default();
+    if report.repair && (!untracked.is_empty() || unpin_orphans && !report.orphan_pins.is_empty()) {
+        let grace_period: Duration = config.fsck.grace_period.into();
+        tracing::info!(
+            ?grace_period,
+            "Waiting for uploads in progress before removing untracked content"
+        );
+        tokio::time::sleep(grace_period).await;
+
+        for entry in untracked {
+            remove_untracked(db, ipfs, &root, entry, &mut report).await;
+        }
+        if unpin_orphans {
+            for cid in report.orphan_pins.clone() {
+                unpin_orphan(db, ipfs, &cid, &mut report).await;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Objects whose MFS path is missing, or links other content
+fn unlinked<'a>(
+    entries: &[MfsEntry],
+    expected: &HashMap<UnixPathBuf, &'a StoredObject>,
+) -> Vec<(UnixPathBuf, &'a StoredObject)> {
+    let linked: HashMap<&UnixPath, &str> = entries
+        .iter()
+        .filter(|entry| !entry.directory)
+        .map(|entry| (entry.path.as_path(), entry.cid.as_str()))
+        .collect();
+
+    let mut unlinked: Vec<_> = expected
+        .iter()
+        .filter(|(path, object)| linked.get(path.as_path()) != Some(&object.cid.as_str()))
+        .map(|(path, object)| (path.clone(), *object))
+        .collect();
+    unlinked.sort_by(|(a, _), (b, _)| a.cmp(b));
+    unlinked
+}
+
+/// MFS entries without objects, skipping entries inside untracked directories
+fn untracked<'a>(
+    entries: &'a [MfsEntry],
+    expected: &HashMap<UnixPathBuf, &StoredObject>,
+) -> Vec<&'a MfsEntry> {
+    let directories: HashSet<&UnixPath> = expected
+        .keys()
+        .flat_map(|path| path.ancestors().skip(1))
+        .collect();
+
+    let mut untracked_directories = HashSet::new();
+    let mut untracked = Vec::new();
+    for entry in entries {
+        if entry
+            .path
+            .parent()
+            .is_some_and(|parent| untracked_directories.contains(parent))
+        {
+            if entry.directory {
+                untracked_directories.insert(entry.path.as_path());
+            }
+            continue;
+        }
+
+        let tracked = if entry.directory {
+            directories.contains(entry.path.as_path())
+        } else {
+            expected.contains_key(&entry.path)
+        };
+        if !tracked {
+            if entry.directory {
+                untracked_directories.insert(entry.path.as_path());
+            }
+            untracked.push(entry);
+        }
+    }
+    untracked
+}
+
+/// Links the object content back into MFS, reporting the object as dangling when its content can't be retrieved
+///
+/// Dangling objects are kept, as their content may only be unreachable for now.
+async fn relink(
+    db: &Database,
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    path: UnixPathBuf,
+    object: &StoredObject,
+    report: &mut Report,
+) {
+    let location = path.to_string_lossy().into_owned();
+    report.unlinked.push(location.clone());
+    if !report.repair {
+        return;
+    }
+
+    // Objects replaced or deleted since listed are left alone
+    match db.get_object_metadata(&object.bucket, &object.key).await {
+        Ok(Some(metadata)) if metadata.cid == object.cid => {}
+        Ok(_) => return,
+        Err(e) => {
+            report.errors.push(format!("{location}: {e}"));
+            return;
+        }
+    }
+
+    let timeout = config.import_timeout.into();
+    let error = match tokio::time::timeout(timeout, ipfs.link(&object.cid, &path)).await {
+        Ok(Ok(())) => {
+            tracing::info!(path = location, cid = object.cid, "Re-linked object");
+            return;
+        }
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => "timed out retrieving content".to_string(),
+    };
+
+    tracing::warn!(
+        path = location,
+        cid = object.cid,
+        error,
+        "Failed to re-link object content"
+    );
+    report.dangling.push(location);
+}
+
+/// Removes an MFS entry without objects, unless it changed or an object was stored there since listed
+async fn remove_untracked(
+    db: &Database,
+    ipfs: &IpfsClient,
+    root: &UnixPath,
+    entry: &MfsEntry,
+    report: &mut Report,
+) {
+    let location = entry.path.to_string_lossy().into_owned();
+    match ipfs.stat(&location).await {
+        Ok(stat) if stat.hash == entry.cid => {}
+        Ok(_) => return,
+        Err(e) => {
+            report.errors.push(format!("{location}: {e}"));
+            return;
+        }
+    }
+
+    let relative = entry
+        .path
+        .strip_prefix(root)
+        .map(|relative| relative.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (bucket, key) = relative.split_once('/').unwrap_or((&relative, ""));
+    let tracked = if entry.directory {
+        let prefix = if key.is_empty() {
+            String::new()
+        } else {
+            format!("{key}/")
+        };
+        db.count_objects_with_prefix(bucket, &prefix)
+            .await
+            .map(|count| count > 0)
+    } else {
+        db.get_object_metadata(bucket, key)
+            .await
+            .map(|metadata| metadata.is_some())
+    };
+
+    match tracked {
+        Ok(true) => {}
+        Ok(false) => match ipfs.unlink(&entry.path).await {
+            Ok(()) => tracing::info!(path = location, "Removed untracked MFS entry"),
+            Err(e) => report.errors.push(format!("{location}: {e}")),
+        },
+        Err(e) => report.errors.push(format!("{location}: {e}")),
+    }
+}
+
+/// Compares CIDs referenced by objects, their versions and snapshots with the recursive pins of the IPFS Node
+/// Private objects are not pinned, so they are not expected on the pinset
+async fn check_pins(
+    db: &Database,
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    report: &mut Report,
+) -> Result<(), Error> {
+    let referenced: HashSet<String> = db.list_referenced_cids().await?.into_iter().collect();
+    let pinned = ipfs.pinned().await?;
+
+    let mut unpinned: Vec<_> = referenced.difference(&pinned).cloned().collect();
+    unpinned.sort();
+    for cid in unpinned {
+        if report.repair {
+            match tokio::time::timeout(config.import_timeout.into(), ipfs.pin(&cid)).await {
+                Ok(Ok(())) => tracing::info!(cid, "Re-pinned content"),
+                Ok(Err(e)) => report.errors.push(format!("{cid}: {e}")),
+                Err(_) => report.errors.push(format!("{cid}: timed out pinning")),
+            }
+        }
+        report.unpinned.push(cid);
+    }
+
+    let mut orphans: Vec<_> = pinned.difference(&referenced).cloned().collect();
+    orphans.sort();
+    report.orphan_pins = orphans;
+
+    Ok(())
+}
+
+/// Unpins content without objects or snapshots, unless stored since listed
+async fn unpin_orphan(db: &Database, ipfs: &IpfsClient, cid: &str, report: &mut Report) {
+    let referenced = match (db.cid_count(cid).await, db.is_snapshot(cid).await) {
+        (Ok(count), Ok(snapshot)) => count > 0 || snapshot,
+        (Err(e), _) | (_, Err(e)) => {
+            report.errors.push(format!("{cid}: {e}"));
+            return;
+        }
+    };
+    if referenced {
+        return;
+    }
+
+    match ipfs.unpin(cid).await {
+        Ok(()) => tracing::info!(cid, "Unpinned orphan content"),
+        Err(e) => report.errors.push(format!("{cid}: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use typed_path::UnixPathBuf;
+
+    use super::{unlinked, untracked};
+    use crate::database::StoredObject;
+    use crate::ipfs::MfsEntry;
+
+    fn entry(path: &str, cid: &str, directory: bool) -> MfsEntry {
+        MfsEntry {
+            path: UnixPathBuf::from(path),
+            cid: cid.to_string(),
+            directory,
+            size: 0,
+        }
+    }
+
+    fn object(key: &str, cid: &str) -> StoredObject {
+        StoredObject {
+            bucket: "bucket".to_string(),
+            key: key.to_string(),
+            cid: cid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_finds_discrepancies() {
+        let entries = [
+            entry("/buckets/bucket", "dir1", true),
+            entry("/buckets/bucket/a.txt", "cid-a", false),
+            entry("/buckets/bucket/b.txt", "cid-old", false),
+            entry("/buckets/bucket/leftover.txt", "cid-x", false),
+            entry("/buckets/bucket/docs", "dir2", true),
+            entry("/buckets/bucket/old", "dir3", true),
+            entry("/buckets/bucket/old/c.txt", "cid-c", false),
+            entry("/buckets/gone", "dir4", true),
+        ];
+        let objects = [
+            object("a.txt", "cid-a"),
+            object("b.txt", "cid-b"),
+            object("docs/d.txt", "cid-d"),
+        ];
+        let expected: HashMap<_, _> = objects
+            .iter()
+            .map(|object| {
+                (
+                    UnixPathBuf::from(format!("/buckets/bucket/{}", object.key)),
+                    object,
+                )
+            })
+            .collect();
+
+        let unlinked: Vec<_> = unlinked(&entries, &expected)
+            .into_iter()
+            .map(|(path, _)| path.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            unlinked,
+            ["/buckets/bucket/b.txt", "/buckets/bucket/docs/d.txt"]
+        );
+
+        let untracked: Vec<_> = untracked(&entries, &expected)
+            .into_iter()
+            .map(|entry| entry.path.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            untracked,
+            [
+                "/buckets/bucket/leftover.txt",
+                "/buckets/bucket/old",
+                "/buckets/gone"
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+// Public gateways used on redirects
+// Gateways are probed in background, and redirects use the fastest healthy one
+
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::future::join_all;
+use http::{Request, Uri};
+use http_body_util::Empty;
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::AppState;
+use crate::https::HttpsClient;
+
+const DEFAULT_GATEWAY: &str = "https://dweb.link";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Clone, Default)]
+pub struct GatewayStatus {
+    pub gateway: String,
+    /// `None` until the gateway is probed
+    pub healthy: Option<bool>,
+    pub latency_ms: Option<u128>,
+    pub checked_at: Option<i64>,
+    pub error: Option<String>,
+}
+
+struct Gateway {
+    uri: Uri,
+    status: RwLock<GatewayStatus>,
+}
+
+pub struct Gateways {
+    gateways: Vec<Gateway>,
+    client: HttpsClient<Empty<Bytes>>,
+}
+
+impl Gateways {
+    /// Gateways in order of preference, defaulting to `dweb.link` when none is configured
+    pub fn new(gateways: &[Uri]) -> Result<Self, std::io::Error> {
+        let gateways = if gateways.is_empty() {
+            vec![Uri::from_static(DEFAULT_GATEWAY)]
+        } else {
+            gateways.to_vec()
+        };
+
+        Ok(Gateways {
+            gateways: gateways
+                .into_iter()
+                .map(|uri| Gateway {
+                    status: RwLock::new(GatewayStatus {
+                        gateway: uri.to_string(),
+                        ..Default::default()
+                    }),
+                    uri,
+                })
+                .collect(),
+            client: crate::https::client()?,
+        })
+    }
+
+    /// Fastest healthy gateway, or the first one not known to be down
+    pub fn select(&self) -> &Uri {
+        let statuses = self.statuses();
+        let index = select(&statuses);
+        &self.gateways[index].uri
+    }
+
+    /// Last probe results of each gateway
+    pub fn statuses(&self) -> Vec<GatewayStatus> {
+        self.gateways
+            .iter()
+            .map(|gateway| {
+                gateway
+                    .status
+                    .read()
+                    .map(|status| status.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    async fn probe(&self, gateway: &Gateway, cid: &str) {
+        let started = Instant::now();
+        let result = match probe_request(&gateway.uri, cid) {
+            Ok(request) => tokio::time::timeout(PROBE_TIMEOUT, self.client.request(request))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|response| response.map_err(|e| e.to_string()))
+                .and_then(|response| {
+                    let status = response.status();
+                    if status.is_success() || status.is_redirection() {
+                        Ok(())
+                    } else {
+                        Err(format!("unexpected status {status}"))
+                    }
+                }),
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(e) = &result {
+            tracing::warn!(gateway = %gateway.uri, error = %e, "Gateway probe failed");
+        }
+
+        if let Ok(mut status) = gateway.status.write() {
+            status.healthy = Some(result.is_ok());
+            status.latency_ms = result.is_ok().then(|| started.elapsed().as_millis());
+            status.checked_at = Some(chrono::Utc::now().timestamp());
+            status.error = result.err();
+        }
+    }
+}
+
+fn probe_request(gateway: &Uri, cid: &str) -> Result<Request<Empty<Bytes>>, http::Error> {
+    let mut parts = gateway.clone().into_parts();
+    parts.path_and_query = Some(format!("/ipfs/{cid}").parse()?);
+    Request::get(Uri::from_parts(parts)?).body(Empty::new())
+}
+
+/// Index of the fastest healthy gateway.
+/// Falls back to the first gateway not known to be down, then to the first gateway.
+fn select(statuses: &[GatewayStatus]) -> usize {
+    let fastest = statuses
+        .iter()
+        .enumerate()
+        .filter(|(_, status)| status.healthy == Some(true))
+        .min_by_key(|(_, status)| status.latency_ms)
+        .map(|(index, _)| index);
+    fastest
+        .or_else(|| statuses.iter().position(|status| status.healthy.is_none()))
+        .unwrap_or_default()
+}
+
+/// Probes every gateway on each `gateway_probe_interval`, fetching the `gateway_probe_cid`
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(state.config.gateway_probe_interval.into());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let gateways = &state.gateways;
+        join_all(
+            gateways
+                .gateways
+                .iter()
+                .map(|gateway| gateways.probe(gateway, &state.config.gateway_probe_cid)),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GatewayStatus, select};
+
+    fn status(healthy: Option<bool>, latency_ms: Option<u128>) -> GatewayStatus {
+        GatewayStatus {
+            healthy,
+            latency_ms,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_selects_fastest_healthy_gateway() {
+        let statuses = [
+            status(Some(true), Some(300)),
+            status(Some(false), None),
+            status(Some(true), Some(120)),
+        ];
+        assert_eq!(select(&statuses), 2);
+    }
+
+    #[test]
+    fn test_selects_unprobed_gateway_when_none_healthy() {
+        assert_eq!(select(&[status(None, None), status(None, None)]), 0);
+        assert_eq!(select(&[status(Some(false), None), status(None, None)]), 1);
+        assert_eq!(
+            select(&[status(Some(false), None), status(Some(false), None)]),
+            0
+        );
+    }
+}
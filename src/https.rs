@@ -0,0 +1,23 @@
+// HTTP client of the external services aricanduva reaches: gateways, Pinning Services and IPFS Cluster
+
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::{Client, connect::HttpConnector};
+use hyper_util::rt::TokioExecutor;
+
+pub type HttpsClient<B> = Client<HttpsConnector<HttpConnector>, B>;
+
+/// HTTP/1 client of both `http` and `https` addresses, trusting the native root certificates
+/// Fails when the native root certificates can't be loaded
+pub fn client<B>() -> Result<HttpsClient<B>, std::io::Error>
+where
+    B: hyper::body::Body + Send,
+    B::Data: Send,
+{
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()?
+        .https_or_http()
+        .enable_http1()
+        .build();
+
+    Ok(Client::builder(TokioExecutor::new()).build(connector))
+}
@@ -9,7 +9,10 @@ pub struct HealthCheckResponse {
     pub timestamp: i64,
     pub db_status: Option<String>,
     pub rpc_status: Option<crate::ipfs::RpcVersion>,
+    pub rpc_nodes: Vec<crate::ipfs::NodeStatus>,
     pub mode: crate::cli::OperationMode,
+    pub gateways: Vec<crate::gateway::GatewayStatus>,
+    pub ipns: Vec<crate::ipns::IpnsStatus>,
 }
 
 #[axum::debug_handler]
@@ -36,7 +39,10 @@ pub async fn health_check(
             timestamp: chrono::Utc::now().timestamp(),
             db_status,
             rpc_status,
+            rpc_nodes: state.ipfs_client.statuses(),
             mode: state.config.mode.clone(),
+            gateways: state.gateways.statuses(),
+            ipns: state.ipns.statuses(),
         }),
     )
 }
@@ -1,29 +1,256 @@
 // IPFS integration module
-// Uses ipfs-api-backend-hyper to connect to an IPFS node
+// Uses ipfs-api-backend-hyper to connect to a pool of IPFS nodes
+// Writes go to the primary node, while reads are balanced across every node, falling back on failures.
+// Each node has a circuit breaker, skipping it for a cooldown after consecutive failures.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
-use futures::{Stream, TryFutureExt, TryStreamExt, io::Cursor};
+use conf::Conf;
+use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt, future::join_all, io::Cursor};
 use http::Uri;
 use ipfs_api_backend_hyper::{
     IpfsApi, IpfsClient as HyperIpfsClient, TryFromUri,
-    response::{AddResponse, VersionResponse},
+    response::{AddResponse, FilesStatResponse, VersionResponse},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing_futures::Instrument;
-use typed_path::UnixPath;
+use typed_path::{UnixPath, UnixPathBuf};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("IPFS client error: {0}")]
-    ClientError(#[from] ipfs_api_backend_hyper::Error),
+    Client(#[from] ipfs_api_backend_hyper::Error),
 
     #[error("IO error: {0}")]
-    IoError(#[from] std::io::Error),
+    Io(#[from] std::io::Error),
+
+    #[error("IPFS node unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Not a file: {0}")]
+    NotFile(String),
+
+    #[error("Invalid CID: {0}")]
+    InvalidCid(String),
+
+    #[error("Invalid CAR: {0}")]
+    Car(String),
+}
+
+/// Options of `ipfs add`, affecting the CIDs produced and how content is deduplicated
+/// Unset options use the IPFS Node defaults
+/// Ref <https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-add>
+#[derive(Conf, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddOptions {
+    #[conf(long, env)]
+    /// CID version. `1` also enables `raw_leaves` on Kubo
+    #[serde(default)]
+    pub cid_version: Option<u32>,
+
+    #[conf(long, env)]
+    /// Hash function (eg: `sha2-256`, `blake2b-256`, `blake3`)
+    #[serde(default)]
+    pub hash: Option<String>,
+
+    #[conf(long, env)]
+    /// Chunking algorithm: `size-[bytes]`, `rabin-[min]-[avg]-[max]` or `buzhash`
+    /// Content-defined chunkers (`rabin`, `buzhash`) deduplicate similar content better, such as backups
+    #[serde(default)]
+    pub chunker: Option<String>,
+
+    #[conf(long, env)]
+    /// Use raw blocks for leaf nodes
+    #[serde(default)]
+    pub raw_leaves: Option<bool>,
+
+    #[conf(long, env)]
+    /// Inline small blocks into CIDs
+    #[serde(default)]
+    pub inline: Option<bool>,
+
+    #[conf(long, env)]
+    /// Use the trickle DAG layout, optimized for sequential reads
+    #[serde(default)]
+    pub trickle: Option<bool>,
+}
+
+impl AddOptions {
+    /// Options set here, falling back to the ones from `defaults`
+    #[must_use]
+    pub fn or(self, defaults: &AddOptions) -> AddOptions {
+        AddOptions {
+            cid_version: self.cid_version.or(defaults.cid_version),
+            hash: self.hash.or_else(|| defaults.hash.clone()),
+            chunker: self.chunker.or_else(|| defaults.chunker.clone()),
+            raw_leaves: self.raw_leaves.or(defaults.raw_leaves),
+            inline: self.inline.or(defaults.inline),
+            trickle: self.trickle.or(defaults.trickle),
+        }
+    }
+
+    /// Rejects options the IPFS Node would fail every `add` with
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(version) = self.cid_version
+            && version > 1
+        {
+            return Err(format!("unsupported cid_version {version}"));
+        }
+        if let Some(chunker) = &self.chunker
+            && !valid_chunker(chunker)
+        {
+            return Err(format!("unsupported chunker {chunker}"));
+        }
+        Ok(())
+    }
+}
+
+/// `size-[bytes]`, `rabin`, `rabin-[avg]`, `rabin-[min]-[avg]-[max]` or `buzhash`
+fn valid_chunker(chunker: &str) -> bool {
+    let numbers = |params: &str, counts: &[usize]| {
+        let params: Vec<&str> = if params.is_empty() {
+            Vec::new()
+        } else {
+            params.split('-').collect()
+        };
+        counts.contains(&params.len())
+            && params.iter().all(|n| n.parse::<u64>().is_ok_and(|n| n > 0))
+    };
+
+    match chunker.split_once('-') {
+        None => matches!(chunker, "rabin" | "buzhash"),
+        Some(("size", params)) => numbers(params, &[1]),
+        Some(("rabin", params)) => numbers(params, &[1, 3]),
+        Some(_) => false,
+    }
+}
+
+/// Consecutive failures before a node is skipped, and for how long
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreaker {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+    /// Reads not returning their first chunk in time count as failures
+    pub read_timeout: Duration,
+}
+
+#[derive(Default)]
+struct Health {
+    failures: u32,
+    open_until: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl Health {
+    /// Closed circuits, or open circuits past the cooldown allowing a trial request
+    fn available(&self, now: Instant) -> bool {
+        self.open_until.is_none_or(|until| now >= until)
+    }
+
+    fn record<T, E: std::fmt::Display>(
+        &mut self,
+        result: &Result<T, E>,
+        breaker: CircuitBreaker,
+        now: Instant,
+    ) {
+        match result {
+            Ok(_) => *self = Health::default(),
+            Err(e) => {
+                self.failures += 1;
+                self.last_error = Some(e.to_string());
+                if self.failures >= breaker.failure_threshold {
+                    self.open_until = Some(now + breaker.cooldown);
+                }
+            }
+        }
+    }
+}
+
+struct Node {
+    address: Uri,
+    client: HyperIpfsClient,
+    breaker: CircuitBreaker,
+    health: Mutex<Health>,
+}
+
+impl Node {
+    fn available(&self) -> bool {
+        self.health
+            .lock()
+            .map_or(true, |health| health.available(Instant::now()))
+    }
+
+    fn record<T>(&self, result: &Result<T, ipfs_api_backend_hyper::Error>) {
+        self.report(result.as_ref().err());
+    }
+
+    /// Errors returned by the node API (eg: unknown MFS paths) show the node is reachable
+    fn report(&self, error: Option<&ipfs_api_backend_hyper::Error>) {
+        match error {
+            Some(ipfs_api_backend_hyper::Error::Api(_)) | None => {
+                if let Ok(mut health) = self.health.lock() {
+                    health.record(&Ok::<_, &str>(()), self.breaker, Instant::now());
+                }
+            }
+            Some(e) => self.fail(e),
+        }
+    }
+
+    fn fail(&self, error: &dyn std::fmt::Display) {
+        tracing::warn!(node = %self.address, error = %error, "IPFS node request failed");
+        if let Ok(mut health) = self.health.lock() {
+            health.record(&Err::<(), _>(error), self.breaker, Instant::now());
+        }
+    }
+
+    fn status(&self, primary: bool) -> NodeStatus {
+        let mut status = NodeStatus {
+            address: self.address.to_string(),
+            primary,
+            available: true,
+            failures: 0,
+            last_error: None,
+        };
+        if let Ok(health) = self.health.lock() {
+            status.available = health.available(Instant::now());
+            status.failures = health.failures;
+            status.last_error.clone_from(&health.last_error);
+        }
+        status
+    }
+}
+
+#[derive(Serialize)]
+pub struct NodeStatus {
+    address: String,
+    primary: bool,
+    /// `false` while the circuit breaker is open
+    available: bool,
+    failures: u32,
+    last_error: Option<String>,
+}
+
+/// Type of directories listed by `files ls --long`
+const MFS_DIRECTORY: u64 = 1;
+
+/// File or directory of the MFS tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MfsEntry {
+    pub path: UnixPathBuf,
+    pub cid: String,
+    pub directory: bool,
+    /// Size of files in bytes
+    pub size: u64,
 }
 
 #[derive(Clone)]
 pub struct IpfsClient {
-    client: HyperIpfsClient,
+    /// Primary node first, followed by the replicas
+    nodes: Vec<Arc<Node>>,
+    next_read: Arc<AtomicUsize>,
 }
 
 #[derive(Serialize)]
@@ -43,35 +270,154 @@ impl From<VersionResponse> for RpcVersion {
 
 impl IpfsClient {
     /// Create a new IPFS client with custom configuration
-    pub fn new_with_config(rpc_address: Uri, credentials: Option<(String, String)>) -> Self {
-        let client = HyperIpfsClient::build_with_base_uri(rpc_address);
-        let client = match credentials {
-            Some((username, password)) => client.with_credentials(username, password),
-            _ => client,
-        };
-        IpfsClient { client }
+    /// The first address is the primary node receiving writes, the others only serve reads
+    pub fn new_with_config(
+        rpc_addresses: &[Uri],
+        credentials: Option<&(String, String)>,
+        breaker: CircuitBreaker,
+    ) -> Self {
+        let nodes = rpc_addresses
+            .iter()
+            .map(|address| {
+                let client = HyperIpfsClient::build_with_base_uri(address.clone());
+                let client = match credentials {
+                    Some((username, password)) => {
+                        client.with_credentials(username.clone(), password.clone())
+                    }
+                    _ => client,
+                };
+                Arc::new(Node {
+                    address: address.clone(),
+                    client,
+                    breaker,
+                    health: Mutex::default(),
+                })
+            })
+            .collect();
+        IpfsClient {
+            nodes,
+            next_read: Arc::default(),
+        }
+    }
+
+    /// Node receiving writes, failing fast while its circuit breaker is open
+    fn primary(&self) -> Result<&Node, Error> {
+        let primary = self
+            .nodes
+            .first()
+            .ok_or_else(|| Error::Unavailable("no IPFS node configured".to_string()))?;
+        if primary.available() {
+            Ok(primary)
+        } else {
+            Err(Error::Unavailable(primary.address.to_string()))
+        }
+    }
+
+    /// Available nodes to read from, rotating the first one on each call
+    fn read_order(&self) -> Vec<Arc<Node>> {
+        let start = self.next_read.fetch_add(1, Ordering::Relaxed);
+        rotate(&self.nodes, start)
+            .into_iter()
+            .filter(|node| node.available())
+            .cloned()
+            .collect()
+    }
+
+    /// Health of each node, primary first
+    pub fn statuses(&self) -> Vec<NodeStatus> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| node.status(index == 0))
+            .collect()
     }
 
     /// Method for adding content to IPFS
     /// Returns the CID (Content Identifier) of the added content
-    #[tracing::instrument(err, skip_all, fields(%path))]
+    /// Content not pinned on the node is kept by the MFS link until pinned elsewhere (eg: IPFS Cluster)
+    #[tracing::instrument(err, skip_all, fields(%path, pin, ?options))]
     pub async fn add_content(
         &self,
         path: &UnixPath,
         // content: impl AsyncRead + Send + Sync + Unpin + 'static,
         content: Vec<u8>,
+        pin: bool,
+        options: &AddOptions,
     ) -> Result<AddResponse, Error> {
         let content = Cursor::new(content);
 
-        let add_response = self
+        let primary = self.primary()?;
+        let add_response = primary
             .client
-            .add_async(content)
+            .add_async_with_options(
+                content,
+                ipfs_api_backend_hyper::request::Add {
+                    pin: Some(pin),
+                    cid_version: options.cid_version,
+                    hash: options.hash.as_deref(),
+                    chunker: options.chunker.as_deref(),
+                    raw_leaves: options.raw_leaves,
+                    inline: options.inline,
+                    trickle: options.trickle,
+                    ..Default::default()
+                },
+            )
             .inspect_ok(|_| tracing::debug!("added"))
             .instrument(tracing::debug_span!("ipfs add"))
-            .await?;
+            .await;
+        primary.record(&add_response);
+        let add_response = add_response?;
+
+        self.link(&add_response.hash, path).await?;
 
-        let cid = &add_response.hash;
-        self.client
+        Ok(add_response)
+    }
+
+    /// Imports a file already on IPFS without moving its bytes through the service:
+    /// stats it, pins it when requested and links it into MFS
+    /// CIDs the node can't resolve are `InvalidCid`, and directories `NotFile`
+    /// Returns the stat of the imported file, with its normalized CID and size
+    #[tracing::instrument(err, skip(self))]
+    pub async fn import_content(
+        &self,
+        cid: &str,
+        path: &UnixPath,
+        pin: bool,
+    ) -> Result<FilesStatResponse, Error> {
+        let primary = self.primary()?;
+        let stat = primary
+            .client
+            .files_stat(&format!("/ipfs/{cid}"))
+            .inspect_ok(|_| tracing::debug!("stat content"))
+            .instrument(tracing::debug_span!("ipfs files stat"))
+            .await;
+        primary.record(&stat);
+        let stat = match stat {
+            Ok(stat) => stat,
+            // The node answered, but can't resolve the CID
+            Err(ipfs_api_backend_hyper::Error::Api(e)) => {
+                return Err(Error::InvalidCid(format!("{cid}: {e}")));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if stat.typ != "file" {
+            return Err(Error::NotFile(cid.to_string()));
+        }
+
+        if pin {
+            self.pin(&stat.hash).await?;
+        }
+
+        self.link(&stat.hash, path).await?;
+        Ok(stat)
+    }
+
+    /// Links existing content into IPFS MFS, replacing any previous entry
+    /// Path must be fully normalized including `bucket_prefix/bucket/key*`
+    pub async fn link(&self, cid: &str, path: &UnixPath) -> Result<(), Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
             .files_cp_with_options(ipfs_api_backend_hyper::request::FilesCp {
                 path: &format!("/ipfs/{cid}"),
                 dest: &path.to_string_lossy(),
@@ -80,52 +426,469 @@ impl IpfsClient {
             })
             .inspect_ok(|()| tracing::debug!("mfs cp"))
             .instrument(tracing::debug_span!("ipfs mfs link", cid))
-            .await?;
-
-        Ok(add_response)
+            .await;
+        primary.record(&result);
+        Ok(result?)
     }
 
     /// Method for getting content from IPFS
     /// Returns the content as a byte vector
+    /// Nodes are tried in turn until one starts streaming the content
     pub fn get_content(&self, cid: &str) -> impl Stream<Item = Result<Bytes, Error>> + use<> {
-        self.client
-            .cat(cid)
-            .map_err(Error::from)
-            .inspect_ok(|_| tracing::debug!("retrieved content"))
-            .instrument(tracing::debug_span!("ipfs cat", cid))
+        let nodes = self.read_order();
+        let span = tracing::debug_span!("ipfs cat", cid);
+        let cid = cid.to_string();
+
+        futures::stream::once(Box::pin(async move {
+            let mut last_error = Error::Unavailable("every IPFS node is unavailable".to_string());
+            for node in nodes {
+                let mut stream = node.client.cat(&cid);
+                // Nodes missing the content search the network instead of failing
+                let timeout = node.breaker.read_timeout;
+                let Ok(first) = tokio::time::timeout(timeout, stream.next()).await else {
+                    let e = Error::Unavailable(format!(
+                        "no content within {}",
+                        humantime::format_duration(timeout)
+                    ));
+                    node.fail(&e);
+                    last_error = e;
+                    continue;
+                };
+                match first {
+                    Some(Ok(first)) => {
+                        node.report(None);
+                        return Ok(futures::stream::iter([Ok(first)])
+                            .chain(stream)
+                            .map_err(Error::from)
+                            .boxed());
+                    }
+                    Some(Err(e)) => {
+                        node.report(Some(&e));
+                        last_error = e.into();
+                    }
+                    None => {
+                        node.report(None);
+                        return Ok(futures::stream::empty().boxed());
+                    }
+                }
+            }
+            Err(last_error)
+        }))
+        .try_flatten()
+        .inspect_ok(|_| tracing::debug!("retrieved content"))
+        .instrument(span)
     }
 
-    /// Ping the IPFS node to check connectivity
+    /// Ping every IPFS node to check connectivity, updating their circuit breakers
+    /// Returns the version of the first node reachable, primary first
     pub async fn ping(&self) -> Result<RpcVersion, Error> {
-        let version: VersionResponse = self
-            .client
-            .version()
-            .inspect_ok(|_| tracing::debug!("pinged ipfs node"))
-            .inspect_err(|e| tracing::error!(error = %e, "failed to ping ipfs node"))
-            .instrument(tracing::debug_span!("ipfs version"))
-            .await?;
-        Ok(version.into())
+        let versions = join_all(self.nodes.iter().map(|node| async {
+            let version = node
+                .client
+                .version()
+                .inspect_ok(|_| tracing::debug!("pinged ipfs node"))
+                .inspect_err(|e| tracing::error!(error = %e, "failed to ping ipfs node"))
+                .instrument(tracing::debug_span!("ipfs version", node = %node.address))
+                .await;
+            node.record(&version);
+            version
+        }))
+        .await;
+
+        let mut last_error = Error::Unavailable("no IPFS node configured".to_string());
+        for version in versions {
+            match version {
+                Ok(version) => return Ok(version.into()),
+                Err(e) => last_error = e.into(),
+            }
+        }
+        Err(last_error)
     }
 
     /// Delete content from IPFS MFS
     /// Path must be fully normalized including `bucket_prefix/bucket/key*`
     pub async fn unlink(&self, path: &UnixPath) -> Result<(), Error> {
         let path = path.to_string();
-        self.client
+        let primary = self.primary()?;
+        let result = primary
+            .client
             .files_rm(&path, true)
             .inspect_ok(|()| tracing::debug!("unlinked file"))
             .instrument(tracing::debug_span!("ipfs files rm", path))
-            .await?;
-        Ok(())
+            .await;
+        primary.record(&result);
+        Ok(result?)
     }
 
     /// Unpin content from IPFS
-    pub async fn unpin(&self, metadata: &crate::database::MetadataResponse) -> Result<(), Error> {
-        self.client
-            .pin_rm(&metadata.cid, true)
+    pub async fn unpin(&self, cid: &str) -> Result<(), Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .pin_rm(cid, true)
             .inspect_ok(|_| tracing::debug!("unpinned content"))
-            .instrument(tracing::debug_span!("ipfs pin rm", cid = %metadata.cid))
-            .await?;
+            .instrument(tracing::debug_span!("ipfs pin rm", cid))
+            .await;
+        primary.record(&result);
+        result?;
+        Ok(())
+    }
+
+    /// Pin content on IPFS, fetching it from the network when missing
+    pub async fn pin(&self, cid: &str) -> Result<(), Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .pin_add(cid, true)
+            .inspect_ok(|_| tracing::debug!("pinned content"))
+            .instrument(tracing::debug_span!("ipfs pin add", cid))
+            .await;
+        primary.record(&result);
+        result?;
         Ok(())
     }
+
+    /// CID of a MFS directory, creating it when missing
+    pub async fn directory(&self, path: &UnixPath) -> Result<String, Error> {
+        let path = path.to_string_lossy();
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .files_mkdir(&path, true)
+            .instrument(tracing::debug_span!("ipfs files mkdir", %path))
+            .await;
+        primary.record(&result);
+        result?;
+
+        Ok(self.stat(&path).await?.hash)
+    }
+
+    /// Stat of a MFS entry, or of content on IPFS with `/ipfs/{cid}`
+    pub async fn stat(&self, path: &str) -> Result<FilesStatResponse, Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .files_stat(path)
+            .instrument(tracing::debug_span!("ipfs files stat", path))
+            .await;
+        primary.record(&result);
+        Ok(result?)
+    }
+
+    /// IPNS name of a key on the keystore of the primary node, if it exists
+    pub async fn key_name(&self, key: &str) -> Result<Option<String>, Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .key_list()
+            .instrument(tracing::debug_span!("ipfs key list"))
+            .await;
+        primary.record(&result);
+        Ok(result?
+            .keys
+            .into_iter()
+            .find(|pair| pair.name == key)
+            .map(|pair| pair.id))
+    }
+
+    /// Publishes `cid` over IPNS with `key`, returning its IPNS name
+    pub async fn publish(&self, cid: &str, key: &str) -> Result<String, Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .name_publish(&format!("/ipfs/{cid}"), false, None, None, Some(key))
+            .inspect_ok(|published| tracing::debug!(name = published.name, "published to ipns"))
+            .instrument(tracing::debug_span!("ipfs name publish", cid, key))
+            .await;
+        primary.record(&result);
+        Ok(result?.name)
+    }
+
+    /// Exports the DAG of `cid` from the primary node as a CAR stream
+    pub fn export_car(&self, cid: &str) -> impl Stream<Item = Result<Bytes, Error>> + use<> {
+        let span = tracing::debug_span!("ipfs dag export", cid);
+        let stream = match self.primary() {
+            Ok(primary) => primary.client.dag_export(cid).map_err(Error::from).boxed(),
+            Err(e) => futures::stream::once(async { Err(e) }).boxed(),
+        };
+        stream.instrument(span)
+    }
+
+    /// Imports the blocks of a CAR stream into the primary node, pinning its root
+    /// Returns the root CID
+    pub async fn import_car<S>(&self, car: S) -> Result<String, Error>
+    where
+        S: Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+    {
+        let primary = self.primary()?;
+
+        // The client needs a `Sync` reader, so chunks are forwarded through a channel
+        let (mut sender, receiver) = futures::channel::mpsc::channel(16);
+        let forward = async move {
+            let mut car = std::pin::pin!(car);
+            while let Some(chunk) = car.next().await {
+                if futures::SinkExt::send(&mut sender, chunk).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let import = primary
+            .client
+            .dag_import(receiver.into_async_read())
+            .instrument(tracing::debug_span!("ipfs dag import"));
+        let (result, ()) = futures::join!(import, forward);
+        primary.record(&result);
+
+        let root = result?
+            .root
+            .ok_or_else(|| Error::Car("no root imported".to_string()))?;
+        if let Some(e) = root.pin_error_msg.filter(|e| !e.is_empty()) {
+            return Err(Error::Car(format!("failed to pin root: {e}")));
+        }
+        root.cid
+            .get("/")
+            .cloned()
+            .ok_or_else(|| Error::Car("root without a CID".to_string()))
+    }
+
+    /// CIDs recursively pinned on the primary node
+    pub async fn pinned(&self) -> Result<HashSet<String>, Error> {
+        let primary = self.primary()?;
+        let result = primary
+            .client
+            .pin_ls(None, Some("recursive"))
+            .inspect_ok(|pins| tracing::debug!(total = pins.keys.len(), "listed pins"))
+            .instrument(tracing::debug_span!("ipfs pin ls"))
+            .await;
+        primary.record(&result);
+        Ok(result?.keys.into_keys().collect())
+    }
+
+    /// Every entry of the MFS tree under `root`, parents before their children
+    /// Empty when `root` doesn't exist
+    pub async fn walk(&self, root: &UnixPath) -> Result<Vec<MfsEntry>, Error> {
+        let primary = self.primary()?;
+        let mut entries = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(directory) = pending.pop() {
+            let path = directory.to_string_lossy();
+            let result = primary
+                .client
+                .files_ls_with_options(ipfs_api_backend_hyper::request::FilesLs {
+                    path: Some(&path),
+                    long: Some(true),
+                    unsorted: Some(true),
+                })
+                .instrument(tracing::debug_span!("ipfs files ls", %path))
+                .await;
+            primary.record(&result);
+            let listed = match result {
+                Err(ipfs_api_backend_hyper::Error::Api(_)) if directory == root => break,
+                result => result?,
+            };
+
+            for entry in listed.entries {
+                let path = directory.join(&entry.name);
+                let is_directory = entry.typ == MFS_DIRECTORY;
+                if is_directory {
+                    pending.push(path.clone());
+                }
+                entries.push(MfsEntry {
+                    path,
+                    cid: entry.hash,
+                    directory: is_directory,
+                    size: entry.size,
+                });
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Items starting at `start`, wrapping around
+fn rotate<T>(items: &[T], start: usize) -> Vec<&T> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let start = start % items.len();
+    items[start..].iter().chain(&items[..start]).collect()
+}
+
+/// Version and multicodec name of a CID, `None` for unknown formats or codecs
+///
+/// Kubo returns `CIDv0` or base32 encoded `CIDv1`, the only multibase supported here.
+/// Ref <https://github.com/multiformats/cid>
+pub fn cid_format(cid: &str) -> Option<(u64, &'static str)> {
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        return Some((0, "dag-pb"));
+    }
+
+    let bytes = data_encoding::BASE32_NOPAD
+        .decode(cid.strip_prefix('b')?.to_ascii_uppercase().as_bytes())
+        .ok()?;
+    let (version, rest) = varint(&bytes)?;
+    let (codec, _) = varint(rest)?;
+    let codec = match codec {
+        0x55 => "raw",
+        0x70 => "dag-pb",
+        0x71 => "dag-cbor",
+        0x0129 => "dag-json",
+        _ => return None,
+    };
+    (version == 1).then_some((version, codec))
+}
+
+/// Base32 encoded `CIDv1` of a CID, as required by subdomain gateways.
+/// `CIDv0` are converted, keeping the `dag-pb` codec and their multihash.
+pub fn cid_v1(cid: &str) -> Option<String> {
+    match cid_format(cid)? {
+        (0, _) => {
+            let mut bytes = vec![0x01, 0x70];
+            bytes.extend(base58_decode(cid)?);
+            Some(format!(
+                "b{}",
+                data_encoding::BASE32_NOPAD
+                    .encode(&bytes)
+                    .to_ascii_lowercase()
+            ))
+        }
+        _ => Some(cid.to_string()),
+    }
+}
+
+/// Decodes base58btc, the encoding of `CIDv0`
+fn base58_decode(value: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in value.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)?;
+        for byte in bytes.iter_mut().rev() {
+            carry += usize::from(*byte) * 58;
+            *byte = carry.to_le_bytes()[0];
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry.to_le_bytes()[0]);
+            carry >>= 8;
+        }
+    }
+    let zeros = value.bytes().take_while(|&c| c == b'1').count();
+    Some([vec![0; zeros], bytes].concat())
+}
+
+/// Decodes an unsigned varint, returning the value and the remaining bytes
+fn varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{AddOptions, CircuitBreaker, Health, cid_format, cid_v1, rotate, valid_chunker};
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(30),
+        };
+        let now = Instant::now();
+        let mut health = Health::default();
+
+        health.record(&Err::<(), _>("refused"), breaker, now);
+        assert!(health.available(now));
+        health.record(&Err::<(), _>("refused"), breaker, now);
+        assert!(!health.available(now));
+        assert_eq!(health.last_error.as_deref(), Some("refused"));
+
+        // Trial request allowed after the cooldown
+        let later = now + Duration::from_secs(30);
+        assert!(health.available(later));
+        health.record(&Ok::<_, &str>(()), breaker, later);
+        assert_eq!(health.failures, 0);
+        assert!(health.available(later));
+    }
+
+    #[test]
+    fn test_add_options_fallback() {
+        let defaults = AddOptions {
+            cid_version: Some(1),
+            chunker: Some("size-262144".to_string()),
+            ..Default::default()
+        };
+        let bucket = AddOptions {
+            chunker: Some("buzhash".to_string()),
+            trickle: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            bucket.or(&defaults),
+            AddOptions {
+                cid_version: Some(1),
+                chunker: Some("buzhash".to_string()),
+                trickle: Some(true),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_valid_chunker() {
+        assert!(valid_chunker("size-1048576"));
+        assert!(valid_chunker("rabin"));
+        assert!(valid_chunker("rabin-262144"));
+        assert!(valid_chunker("rabin-131072-262144-524288"));
+        assert!(valid_chunker("buzhash"));
+        assert!(!valid_chunker("size"));
+        assert!(!valid_chunker("size-0"));
+        assert!(!valid_chunker("rabin-1-2"));
+        assert!(!valid_chunker("fastcdc"));
+    }
+
+    #[test]
+    fn test_rotate() {
+        assert_eq!(rotate(&[1, 2, 3], 0), [&1, &2, &3]);
+        assert_eq!(rotate(&[1, 2, 3], 4), [&2, &3, &1]);
+        assert!(rotate::<u8>(&[], 1).is_empty());
+    }
+
+    #[test]
+    fn test_cid_format() {
+        assert_eq!(
+            cid_format("QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG"),
+            Some((0, "dag-pb"))
+        );
+        assert_eq!(
+            cid_format("bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi"),
+            Some((1, "dag-pb"))
+        );
+        assert_eq!(cid_format("bafkqaaa"), Some((1, "raw")));
+        assert_eq!(
+            cid_format("zdj7WWeQ43G6JJvLWQWZpyHuAMq6uYWRjkBXFad11vE2LHhQ7"),
+            None
+        );
+        assert_eq!(cid_format("not a cid"), None);
+    }
+
+    #[test]
+    fn test_cid_v1() {
+        assert_eq!(
+            cid_v1("QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn").as_deref(),
+            Some("bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354")
+        );
+        assert_eq!(cid_v1("bafkqaaa").as_deref(), Some("bafkqaaa"));
+        assert_eq!(cid_v1("not a cid"), None);
+    }
 }
@@ -0,0 +1,263 @@
+// IPNS publishing of bucket roots
+// Each bucket is a MFS directory whose CID changes on every write. Buckets configured with a key of the
+// IPFS Node keystore get their root published under the IPNS name of the key, once writes settle down.
+
+use std::time::{Duration, Instant};
+
+use dashmap::{DashMap, DashSet};
+use futures::future::join_all;
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::AppState;
+use crate::database::DatabaseError;
+use crate::ipfs;
+
+/// How often pending buckets are checked
+const TICK: Duration = Duration::from_secs(1);
+/// Continuous writes delay publishing by at most this many `ipns_debounce`
+const MAX_DELAY_FACTOR: u32 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("IPFS error: {0}")]
+    Ipfs(#[from] ipfs::Error),
+
+    #[error("Invalid path: {0}")]
+    Path(#[from] typed_path::CheckedPathError),
+
+    #[error("Bucket has private objects")]
+    Private,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct IpnsStatus {
+    pub bucket: String,
+    pub key: String,
+    pub name: Option<String>,
+    /// Root CID last published
+    pub root: Option<String>,
+    pub published_at: Option<i64>,
+    /// Error of the last attempt, retried after the next `ipns_debounce`
+    pub error: Option<String>,
+}
+
+/// Writes not published yet
+#[derive(Clone, Copy)]
+struct Pending {
+    first: Instant,
+    last: Instant,
+}
+
+impl Pending {
+    /// Due once writes stopped for `debounce`, or after `MAX_DELAY_FACTOR` times `debounce`
+    fn is_due(self, now: Instant, debounce: Duration) -> bool {
+        now.duration_since(self.last) >= debounce
+            || now.duration_since(self.first) >= debounce * MAX_DELAY_FACTOR
+    }
+}
+
+#[derive(Default)]
+pub struct Publisher {
+    pending: DashMap<String, Pending>,
+    publishing: DashSet<String>,
+    statuses: DashMap<String, IpnsStatus>,
+}
+
+impl Publisher {
+    /// Marks the root of a bucket as changed, to be published if it has an IPNS key
+    pub fn notify(&self, bucket: &str) {
+        let now = Instant::now();
+        self.pending
+            .entry(bucket.to_string())
+            .and_modify(|pending| pending.last = now)
+            .or_insert(Pending {
+                first: now,
+                last: now,
+            });
+    }
+
+    /// Last publication of a bucket root
+    pub fn status(&self, bucket: &str) -> Option<IpnsStatus> {
+        self.statuses.get(bucket).map(|status| status.clone())
+    }
+
+    /// IPNS name of a bucket whose current root is published, `None` while writes are not published yet
+    pub fn published_name(&self, bucket: &str) -> Option<String> {
+        if self.pending.contains_key(bucket) || self.publishing.contains(bucket) {
+            return None;
+        }
+        self.status(bucket)
+            .filter(|status| status.error.is_none())
+            .and_then(|status| status.name)
+    }
+
+    /// Last publication of each bucket root
+    pub fn statuses(&self) -> Vec<IpnsStatus> {
+        let mut statuses: Vec<_> = self
+            .statuses
+            .iter()
+            .map(|status| status.value().clone())
+            .collect();
+        statuses.sort_by(|a, b| a.bucket.cmp(&b.bucket));
+        statuses
+    }
+
+    /// Removes and returns the buckets due to be published
+    fn due(&self, debounce: Duration) -> Vec<String> {
+        let now = Instant::now();
+        let due: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|pending| pending.value().is_due(now, debounce))
+            .map(|pending| pending.key().clone())
+            .collect();
+        for bucket in &due {
+            self.publishing.insert(bucket.clone());
+            self.pending.remove(bucket);
+        }
+        due
+    }
+}
+
+/// Publishes the root of a bucket if it changed since the last publication
+async fn publish(state: &AppState, bucket: &str) -> Result<(), Error> {
+    let publisher = &state.ipns;
+    let ipns = state.db.get_bucket_ipns(bucket).await?;
+    let Some(key) = ipns.ipns_key else {
+        publisher.statuses.remove(bucket);
+        return Ok(());
+    };
+
+    // The root links every current object, publishing it would expose the private ones
+    // Writes are held until the root is read, so no private object lands after the check
+    let writes = state.bucket_locks.restore(bucket).await;
+    if crate::s3::is_private_bucket(&state.db, &state.config, bucket).await? {
+        return Err(Error::Private);
+    }
+
+    let path = crate::s3::bucket_path(&state.config.folder_prefix, bucket)?;
+    let root = state.ipfs_client.directory(&path).await?;
+    drop(writes);
+
+    let previous = publisher.status(bucket);
+    if let Some(previous) = &previous
+        && previous.key == key
+        && previous.root.as_ref() == Some(&root)
+        && previous.error.is_none()
+    {
+        tracing::debug!(bucket, root, "Bucket root already published");
+        return Ok(());
+    }
+
+    let name = state.ipfs_client.publish(&root, &key).await?;
+    tracing::info!(bucket, root, name, "Published bucket root");
+    publisher.statuses.insert(
+        bucket.to_string(),
+        IpnsStatus {
+            bucket: bucket.to_string(),
+            key,
+            name: Some(name),
+            root: Some(root),
+            published_at: Some(chrono::Utc::now().timestamp()),
+            error: None,
+        },
+    );
+    Ok(())
+}
+
+async fn publish_or_retry(state: &AppState, bucket: &str) {
+    if let Err(e) = publish(state, bucket).await {
+        tracing::error!(bucket, error = %e, "Failed to publish bucket root");
+        if let Ok(ipns) = state.db.get_bucket_ipns(bucket).await
+            && let Some(key) = ipns.ipns_key
+        {
+            let mut status = state.ipns.status(bucket).unwrap_or_default();
+            status.bucket = bucket.to_string();
+            status.key = key;
+            status.error = Some(e.to_string());
+            state.ipns.statuses.insert(bucket.to_string(), status);
+        }
+        state.ipns.notify(bucket);
+    }
+    state.ipns.publishing.remove(bucket);
+}
+
+/// Publishes every bucket with an IPNS key on start, then the buckets written to once writes settle
+/// down for `ipns_debounce`
+pub async fn run(state: AppState) {
+    match state.db.list_ipns_buckets().await {
+        Ok(buckets) => {
+            for bucket in buckets {
+                state.ipns.notify(&bucket);
+            }
+        }
+        Err(e) => tracing::error!(error = %e, "Failed to list buckets published over IPNS"),
+    }
+
+    let debounce: Duration = state.config.ipns_debounce.into();
+    let mut interval = tokio::time::interval(TICK);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let due = state.ipns.due(debounce);
+        join_all(due.iter().map(|bucket| publish_or_retry(&state, bucket))).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{IpnsStatus, Pending, Publisher};
+
+    #[test]
+    fn test_pending_is_due_after_debounce() {
+        let debounce = Duration::from_secs(30);
+        let now = Instant::now();
+        let pending = Pending {
+            first: now,
+            last: now,
+        };
+        assert!(!pending.is_due(now + Duration::from_secs(10), debounce));
+        assert!(pending.is_due(now + debounce, debounce));
+
+        // Continuous writes are published after the maximum delay
+        let pending = Pending {
+            first: now,
+            last: now + Duration::from_secs(290),
+        };
+        assert!(!pending.is_due(now + Duration::from_secs(299), debounce));
+        assert!(pending.is_due(now + Duration::from_secs(300), debounce));
+    }
+
+    #[test]
+    fn test_published_name_once_writes_are_published() {
+        let publisher = Publisher::default();
+        publisher.statuses.insert(
+            "bucket".to_string(),
+            IpnsStatus {
+                bucket: "bucket".to_string(),
+                name: Some("k51qzi5uqu5d".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            publisher.published_name("bucket").as_deref(),
+            Some("k51qzi5uqu5d")
+        );
+
+        publisher.notify("bucket");
+        assert_eq!(publisher.published_name("bucket"), None);
+
+        // Still stale while publishing
+        assert_eq!(publisher.due(Duration::ZERO), vec!["bucket".to_string()]);
+        assert_eq!(publisher.published_name("bucket"), None);
+        publisher.publishing.remove("bucket");
+        assert!(publisher.published_name("bucket").is_some());
+    }
+}
@@ -1,19 +1,29 @@
-use axum::{Router, routing::get};
-use bytes::Bytes;
+use axum::{Router, ServiceExt, extract::Request, middleware, routing::get};
 use conf::Conf;
-use dashmap::DashMap;
 use rand::distr::SampleString;
 use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::signal;
 use tower_http::{compression::CompressionLayer, trace::TraceLayer};
+use tower_layer::Layer;
 use tracing::Level;
 
+mod admin;
+mod car;
 mod cli;
+mod cluster;
 mod database;
+mod encryption;
+mod fsck;
+mod gateway;
+mod https;
 mod info;
 mod ipfs;
+mod ipns;
 mod limited_slots;
+mod pinning;
+mod rebuild;
 mod s3;
+mod snapshot;
 
 use crate::cli::{CliOperations, RunConfig};
 use crate::info::health_check;
@@ -22,8 +32,12 @@ use crate::ipfs::IpfsClient;
 struct App {
     db: database::Database,
     ipfs_client: IpfsClient,
+    cluster: Option<cluster::ClusterClient>,
+    gateways: gateway::Gateways,
+    ipns: ipns::Publisher,
     config: RunConfig,
-    multipart_slots: limited_slots::LimitedSlotsMap<String, DashMap<i8, Bytes>>,
+    multipart_slots: limited_slots::LimitedSlotsMap<String, s3::MultipartUpload>,
+    bucket_locks: s3::BucketLocks,
 }
 
 type AppState = Arc<App>;
@@ -81,6 +95,58 @@ async fn main() {
             println!("AUTH_SECRET_KEY={}", secret_key.to_uppercase());
             std::process::exit(0);
         }
+        Some(CliOperations::Presign(presign)) => {
+            let request = s3::authorization::PresignRequest {
+                endpoint: &presign.endpoint,
+                method: &presign.method,
+                bucket: &presign.bucket,
+                key: &presign.key,
+                region: &presign.region,
+                expires: presign.expires.into(),
+                now: chrono::Utc::now(),
+            };
+            match s3::authorization::presign(&presign.auth, &request) {
+                Ok(url) => {
+                    println!("{url}");
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("Failed to generate presigned URL: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(CliOperations::Fsck(config)) => {
+            let db = database(&config).await;
+            let ipfs_client = ipfs_client(&config);
+            std::process::exit(fsck::command(&db, &ipfs_client, &config).await);
+        }
+        Some(CliOperations::Rebuild(command)) => {
+            let db = database(&command.config).await;
+            let ipfs_client = ipfs_client(&command.config);
+            std::process::exit(rebuild::command(&db, &ipfs_client, &command).await);
+        }
+        Some(CliOperations::Snapshot(snapshot)) => {
+            let db = database(&snapshot.config).await;
+            let ipfs_client = ipfs_client(&snapshot.config);
+            let cluster = cluster_client(&snapshot.config);
+            std::process::exit(
+                snapshot::command(&db, &ipfs_client, cluster.as_ref(), &snapshot).await,
+            );
+        }
+        Some(CliOperations::Export(command)) => {
+            let db = database(&command.config).await;
+            let ipfs_client = ipfs_client(&command.config);
+            std::process::exit(car::export_command(&db, &ipfs_client, &command).await);
+        }
+        Some(CliOperations::Import(command)) => {
+            let db = database(&command.config).await;
+            let ipfs_client = ipfs_client(&command.config);
+            let cluster = cluster_client(&command.config);
+            std::process::exit(
+                car::import_command(&db, &ipfs_client, cluster.as_ref(), &command).await,
+            );
+        }
         Some(CliOperations::Run(config)) => config,
         _ => cli.config,
     };
@@ -88,6 +154,79 @@ async fn main() {
     run(config).await;
 }
 
+async fn database(config: &RunConfig) -> database::Database {
+    match database::Database::initialize(&config.database_path, &config.sqlite).await {
+        Ok(db) => {
+            tracing::info!("Database initialized successfully");
+            db
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to initialize database");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// IPFS client of the `rpc_address` node and its `rpc_replica`
+fn ipfs_client(config: &RunConfig) -> IpfsClient {
+    let rpc_credentials = config.rpc_credentials.clone().map(Into::into);
+    IpfsClient::new_with_config(
+        &[
+            std::slice::from_ref(&config.rpc_address),
+            config.rpc_replica.as_slice(),
+        ]
+        .concat(),
+        rpc_credentials.as_ref(),
+        ipfs::CircuitBreaker {
+            failure_threshold: config.rpc_failure_threshold,
+            cooldown: config.rpc_cooldown.into(),
+            read_timeout: config.rpc_read_timeout.into(),
+        },
+    )
+}
+
+/// IPFS Cluster client when running with `pinning_backend: cluster`
+fn cluster_client(config: &RunConfig) -> Option<cluster::ClusterClient> {
+    match (config.pinning_backend, &config.cluster) {
+        (cli::PinningBackend::Kubo, _) => None,
+        (cli::PinningBackend::Cluster, Some(cluster)) => match cluster::ClusterClient::new(cluster)
+        {
+            Ok(cluster) => Some(cluster),
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to load certificates to reach IPFS Cluster");
+                std::process::exit(1);
+            }
+        },
+        (cli::PinningBackend::Cluster, None) => {
+            tracing::error!(
+                "Pinning backend `cluster` requires `cluster-address` to be configured"
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Remote Pinning Service client, when `remote_pinning` is configured
+fn pinning_client(config: &RunConfig) -> Option<pinning::PinningClient> {
+    let remote_pinning = config.remote_pinning.as_ref()?;
+    check_interval("remote-pinning-interval", remote_pinning.interval);
+    match pinning::PinningClient::new(remote_pinning) {
+        Ok(client) => Some(client),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to load certificates for the Pinning Service");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Exits when a background task is configured to run every zero seconds, which `tokio::time::interval` rejects
+fn check_interval(name: &str, interval: humantime::Duration) {
+    if interval.is_zero() {
+        tracing::error!("`{name}` must be greater than zero");
+        std::process::exit(1);
+    }
+}
+
 async fn run(config: RunConfig) {
     tracing::debug!(config = ?config, "Loaded configuration");
 
@@ -97,35 +236,62 @@ async fn run(config: RunConfig) {
         );
     }
 
+    if let Err(e) = config.add.validate() {
+        tracing::error!(error = %e, "Invalid add options");
+        std::process::exit(1);
+    }
+    check_interval("lifecycle-interval", config.lifecycle_interval);
+    if !matches!(config.mode, cli::OperationMode::Proxy) {
+        check_interval("gateway-probe-interval", config.gateway_probe_interval);
+    }
+    if let Some(interval) = config.fsck.interval {
+        check_interval("fsck-interval", interval);
+    }
+
     // Initialize database before starting the server
-    let db = match database::Database::initialize(&config.database_path, &config.sqlite).await {
-        Ok(db) => {
-            tracing::info!("Database initialized successfully");
-            db
-        }
+    let db = database(&config).await;
+    let ipfs_client = ipfs_client(&config);
+
+    let cluster = cluster_client(&config);
+    let pinning = pinning_client(&config);
+
+    let gateways = match gateway::Gateways::new(&config.gateway) {
+        Ok(gateways) => gateways,
         Err(e) => {
-            tracing::error!(error = %e, "Failed to initialize database");
+            tracing::error!(error = %e, "Failed to load certificates to probe gateways");
             std::process::exit(1);
         }
     };
 
-    let ipfs_client = IpfsClient::new_with_config(
-        config.rpc_address.clone(),
-        config.rpc_credentials.clone().map(Into::into),
-    );
-
     let app_state = Arc::new(App {
         db,
         ipfs_client,
+        cluster,
+        gateways,
+        ipns: ipns::Publisher::default(),
         config: config.clone(),
         multipart_slots: limited_slots::LimitedSlotsMap::with_capacity(
             config.concurrent_multipart_upload,
         ),
+        bucket_locks: s3::BucketLocks::default(),
     });
 
+    tokio::spawn(s3::lifecycle::run(app_state.clone()));
+    if !matches!(config.mode, cli::OperationMode::Proxy) {
+        tokio::spawn(gateway::run(app_state.clone()));
+    }
+    if let (Some(client), Some(remote_pinning)) = (pinning, config.remote_pinning.clone()) {
+        tokio::spawn(pinning::run(app_state.clone(), client, remote_pinning));
+    }
+    tokio::spawn(ipns::run(app_state.clone()));
+    if let Some(interval) = config.fsck.interval {
+        tokio::spawn(fsck::run(app_state.clone(), interval.into()));
+    }
+
     let app = Router::new()
         .route("/healthz", get(health_check))
-        .merge(s3::routes(&config))
+        .merge(admin::routes(&config))
+        .merge(s3::routes(&app_state))
         .with_state(app_state.clone())
         .layer(config.ip_extraction.clone().into_extension())
         .layer(CompressionLayer::new())
@@ -135,12 +301,19 @@ async fn run(config: RunConfig) {
                 .on_response(tower_http::trace::DefaultOnResponse::new().level(Level::INFO)),
         );
 
+    // Must wrap the router, as rewriting the URI needs to happen before routing
+    let app = middleware::map_request_with_state(
+        config.base_domain.as_deref().map(Arc::<str>::from),
+        s3::virtual_host::rewrite,
+    )
+    .layer(app);
+
     let listener = config.listen_socket().await;
     tracing::info!(?config.mode, "Service started");
 
     axum::serve(
         listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
+        ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
     )
     .with_graceful_shutdown(async move {
         shutdown_signal().await;
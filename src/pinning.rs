@@ -0,0 +1,496 @@
+// Remote pinning through the IPFS Pinning Services API
+// Content added to the Kubo node is also pinned on a remote service for redundancy
+// Ref <https://ipfs.github.io/pinning-services-api-spec/>
+
+use bytes::Bytes;
+use conf::Conf;
+use http::{Method, Request, StatusCode, Uri, header};
+use http_body_util::{BodyExt, Full};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+
+use crate::AppState;
+use crate::cli::{PinningBackend, RunConfig};
+use crate::cluster::ClusterClient;
+use crate::database::{Database, RemotePin};
+use crate::https::HttpsClient;
+use crate::ipfs::IpfsClient;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid request: {0}")]
+    Request(#[from] http::Error),
+
+    #[error("Pinning service unreachable: {0}")]
+    Client(#[from] hyper_util::client::legacy::Error),
+
+    #[error("Failed to read response: {0}")]
+    Body(#[from] hyper::Error),
+
+    #[error("Invalid response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Pinning service returned {0}: {1}")]
+    Status(StatusCode, String),
+}
+
+#[derive(Conf, Clone)]
+pub struct RemotePinningConfig {
+    #[conf(long, env)]
+    /// Pinning Services API endpoint, without the `/pins` path (eg: `https://api.pinata.cloud/psa`)
+    pub endpoint: Uri,
+
+    #[conf(long, env)]
+    /// Access token sent as `Authorization: Bearer` to the Pinning Service
+    pub access_token: String,
+
+    #[conf(long, env, default_value = "1m")]
+    /// How often queued pins are requested and their status refreshed
+    pub interval: humantime::Duration,
+
+    #[conf(long, env, default(10))]
+    /// Failed requests before giving up on a pin
+    pub max_attempts: i64,
+}
+
+impl std::fmt::Debug for RemotePinningConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemotePinningConfig")
+            .field("endpoint", &self.endpoint)
+            .field("access_token", &"REDACTED")
+            .field("interval", &self.interval)
+            .field("max_attempts", &self.max_attempts)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Queued,
+    Pinning,
+    Pinned,
+    Failed,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Pinning => "pinning",
+            Status::Pinned => "pinned",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Pin {
+    pub cid: String,
+}
+
+/// Pin request tracked by the service
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PinStatus {
+    pub requestid: String,
+    pub status: Status,
+    pub pin: Pin,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PinResults {
+    pub count: u64,
+    pub results: Vec<PinStatus>,
+}
+
+#[derive(Clone)]
+pub struct PinningClient {
+    endpoint: Uri,
+    access_token: String,
+    client: HttpsClient<Full<Bytes>>,
+}
+
+impl PinningClient {
+    pub fn new(config: &RemotePinningConfig) -> Result<Self, std::io::Error> {
+        Ok(PinningClient {
+            endpoint: config.endpoint.clone(),
+            access_token: config.access_token.clone(),
+            client: crate::https::client()?,
+        })
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path_and_query: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<(StatusCode, Bytes), Error> {
+        let uri = format!(
+            "{}{path_and_query}",
+            self.endpoint.to_string().trim_end_matches('/')
+        );
+        let mut request = Request::builder().method(method).uri(uri).header(
+            header::AUTHORIZATION,
+            format!("Bearer {}", self.access_token),
+        );
+        if body.is_some() {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+        }
+        let request = request.body(Full::from(body.unwrap_or_default()))?;
+
+        let response = self.client.request(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        if status.is_success() || status == StatusCode::NOT_FOUND {
+            Ok((status, body))
+        } else {
+            Err(Error::Status(
+                status,
+                String::from_utf8_lossy(&body).into_owned(),
+            ))
+        }
+    }
+
+    async fn pin_status(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<PinStatus, Error> {
+        match self.request(method, path, body).await? {
+            (StatusCode::NOT_FOUND, body) => Err(Error::Status(
+                StatusCode::NOT_FOUND,
+                String::from_utf8_lossy(&body).into_owned(),
+            )),
+            (_, body) => Ok(serde_json::from_slice(&body)?),
+        }
+    }
+
+    /// Request the service to pin content
+    pub async fn add(&self, cid: &str) -> Result<PinStatus, Error> {
+        let body = serde_json::to_vec(&Pin {
+            cid: cid.to_string(),
+        })?;
+        self.pin_status(Method::POST, "/pins", Some(body)).await
+    }
+
+    /// Status of a pin request, `None` when unknown to the service
+    pub async fn status(&self, request_id: &str) -> Result<Option<PinStatus>, Error> {
+        match self
+            .request(Method::GET, &format!("/pins/{request_id}"), None)
+            .await?
+        {
+            (StatusCode::NOT_FOUND, _) => Ok(None),
+            (_, body) => Ok(Some(serde_json::from_slice(&body)?)),
+        }
+    }
+
+    /// Pin requests of a CID, in any status
+    pub async fn find(&self, cid: &str) -> Result<Vec<PinStatus>, Error> {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("cid", cid)
+            .append_pair("status", "queued,pinning,pinned,failed")
+            .finish();
+        let (_, body) = self
+            .request(Method::GET, &format!("/pins?{query}"), None)
+            .await?;
+        Ok(serde_json::from_slice::<PinResults>(&body)?.results)
+    }
+
+    /// Remove a pin request. Already removed requests are ignored
+    pub async fn remove(&self, request_id: &str) -> Result<(), Error> {
+        self.request(Method::DELETE, &format!("/pins/{request_id}"), None)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Queue added content to be pinned on the remote Pinning Service, when configured
+pub async fn queue_pin(db: &Database, config: &RunConfig, cid: &str) {
+    if config.remote_pinning.is_some()
+        && let Err(e) = db.queue_remote_pin(cid).await
+    {
+        tracing::error!(error = %e, cid, "Failed to queue remote pin");
+    }
+}
+
+/// Pins content on IPFS Cluster, when configured, and queues it for remote pinning
+/// Content added through the Kubo node is already pinned there, unless the cluster is the pinning backend
+pub async fn pin_content(
+    db: &Database,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    name: &str,
+    cid: &str,
+) -> Result<(), String> {
+    if let Some(cluster) = cluster {
+        let replication = db
+            .get_bucket_replication(bucket)
+            .await
+            .map_err(|e| e.to_string())?;
+        cluster
+            .pin(cid, name, replication)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    queue_pin(db, config, cid).await;
+    Ok(())
+}
+
+/// Pins content already on the IPFS Node with the configured pinning backend, and queues it for remote pinning
+/// Used by snapshots, restores and imports, which may run from the CLI without the server state
+pub async fn pin_existing(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    name: &str,
+    cid: &str,
+) -> Result<(), String> {
+    if matches!(config.pinning_backend, PinningBackend::Kubo) {
+        ipfs.pin(cid).await.map_err(|e| e.to_string())?;
+    }
+    pin_content(db, cluster, config, bucket, name, cid).await
+}
+
+/// Queue the removal of the remote pin of unpinned content, when configured
+pub async fn queue_unpin(state: &AppState, cid: &str) {
+    if state.config.remote_pinning.is_some()
+        && let Err(e) = state.db.queue_remote_unpin(cid).await
+    {
+        tracing::error!(error = %e, cid, "Failed to queue remote unpin");
+    }
+}
+
+/// Requests pending pins, refreshes pins in progress and removes pins of unreferenced content
+/// on each `remote_pinning.interval`
+pub async fn run(state: AppState, client: PinningClient, config: RemotePinningConfig) {
+    let mut interval = tokio::time::interval(config.interval.into());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let pins = match state
+            .db
+            .list_unsettled_remote_pins(config.max_attempts)
+            .await
+        {
+            Ok(pins) => pins,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to retrieve remote pins");
+                continue;
+            }
+        };
+
+        for pin in pins {
+            let span = tracing::debug_span!(
+                "remote pin",
+                cid = pin.cid,
+                status = pin.status,
+                attempts = pin.attempts
+            );
+            if let Err(e) = sync(&state, &client, &pin).instrument(span).await {
+                tracing::warn!(error = %e, cid = pin.cid, "Failed to sync remote pin");
+                if let Err(e) = state.db.fail_remote_pin(&pin.cid, &e.to_string()).await {
+                    tracing::error!(error = %e, "Failed to store remote pin failure");
+                }
+            }
+        }
+    }
+}
+
+async fn sync(state: &AppState, client: &PinningClient, pin: &RemotePin) -> Result<(), Error> {
+    let status = match (pin.status.as_str(), pin.request_id.as_deref()) {
+        ("unpin", Some(request_id)) => {
+            client.remove(request_id).await?;
+            return delete(state, &pin.cid).await;
+        }
+        ("unpin", None) => {
+            for status in client.find(&pin.cid).await? {
+                client.remove(&status.requestid).await?;
+            }
+            return delete(state, &pin.cid).await;
+        }
+        ("queued" | "pinning", Some(request_id)) => match client.status(request_id).await? {
+            Some(status) => status,
+            // Removed from the service, eg: by an unpin racing a new upload of the content
+            None => client.add(&pin.cid).await?,
+        },
+        (_, request_id) => {
+            // Failed requests are kept by the service, and replaced by a new request
+            if let Some(request_id) = request_id {
+                client.remove(request_id).await?;
+            }
+            client.add(&pin.cid).await?
+        }
+    };
+
+    tracing::debug!(status = status.status.as_str(), "Remote pin status");
+    if let Err(e) = state
+        .db
+        .update_remote_pin(&pin.cid, &status.requestid, status.status.as_str())
+        .await
+    {
+        tracing::error!(error = %e, "Failed to store remote pin status");
+    }
+    Ok(())
+}
+
+async fn delete(state: &AppState, cid: &str) -> Result<(), Error> {
+    tracing::debug!("Removed remote pin");
+    if let Err(e) = state.db.delete_remote_pin(cid).await {
+        tracing::error!(error = %e, "Failed to delete remote pin");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::{Path, Query, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use serde::Deserialize;
+
+    use super::{Pin, PinResults, PinStatus, PinningClient, RemotePinningConfig, Status};
+
+    type Pins = Arc<Mutex<Vec<PinStatus>>>;
+
+    fn authorized(headers: &HeaderMap) -> Result<(), StatusCode> {
+        match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+            Some("Bearer secret") => Ok(()),
+            _ => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+
+    async fn add(
+        State(pins): State<Pins>,
+        headers: HeaderMap,
+        Json(pin): Json<Pin>,
+    ) -> Result<(StatusCode, Json<PinStatus>), StatusCode> {
+        authorized(&headers)?;
+        let mut pins = pins.lock().unwrap();
+        let status = PinStatus {
+            requestid: format!("request-{}", pins.len()),
+            status: Status::Queued,
+            pin,
+        };
+        pins.push(PinStatus {
+            requestid: status.requestid.clone(),
+            status: Status::Pinned,
+            pin: Pin {
+                cid: status.pin.cid.clone(),
+            },
+        });
+        Ok((StatusCode::ACCEPTED, Json(status)))
+    }
+
+    fn copy(status: &PinStatus) -> PinStatus {
+        PinStatus {
+            requestid: status.requestid.clone(),
+            status: status.status,
+            pin: Pin {
+                cid: status.pin.cid.clone(),
+            },
+        }
+    }
+
+    async fn status(
+        State(pins): State<Pins>,
+        Path(request_id): Path<String>,
+    ) -> Result<Json<PinStatus>, StatusCode> {
+        let pins = pins.lock().unwrap();
+        pins.iter()
+            .find(|status| status.requestid == request_id)
+            .map(|status| Json(copy(status)))
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+
+    #[derive(Deserialize)]
+    struct FindParams {
+        cid: String,
+    }
+
+    async fn find(State(pins): State<Pins>, Query(params): Query<FindParams>) -> Json<PinResults> {
+        let pins = pins.lock().unwrap();
+        let results = pins
+            .iter()
+            .filter(|status| status.pin.cid == params.cid)
+            .map(copy)
+            .collect::<Vec<_>>();
+        Json(PinResults {
+            count: results.len() as u64,
+            results,
+        })
+    }
+
+    async fn remove(State(pins): State<Pins>, Path(request_id): Path<String>) -> StatusCode {
+        pins.lock()
+            .unwrap()
+            .retain(|status| status.requestid != request_id);
+        StatusCode::ACCEPTED
+    }
+
+    async fn mock_service() -> (String, Pins) {
+        let pins = Pins::default();
+        let app = Router::new()
+            .route("/psa/pins", post(add).get(find))
+            .route("/psa/pins/{request_id}", get(status).delete(remove))
+            .with_state(pins.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{address}/psa/"), pins)
+    }
+
+    fn client(endpoint: &str, access_token: &str) -> PinningClient {
+        PinningClient::new(&RemotePinningConfig {
+            endpoint: endpoint.parse().unwrap(),
+            access_token: access_token.to_string(),
+            interval: "1m".parse().unwrap(),
+            max_attempts: 10,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pins_and_removes_content() {
+        let (endpoint, pins) = mock_service().await;
+        let client = client(&endpoint, "secret");
+        let cid = "bafkqaaa";
+
+        let added = client.add(cid).await.unwrap();
+        assert_eq!(added.status, Status::Queued);
+        assert_eq!(added.pin.cid, cid);
+
+        let status = client.status(&added.requestid).await.unwrap().unwrap();
+        assert_eq!(status.status, Status::Pinned);
+
+        let found = client.find(cid).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].requestid, added.requestid);
+
+        client.remove(&added.requestid).await.unwrap();
+        assert!(pins.lock().unwrap().is_empty());
+        assert!(client.status(&added.requestid).await.unwrap().is_none());
+        // Already removed pins are ignored
+        client.remove(&added.requestid).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejected_token() {
+        let (endpoint, _) = mock_service().await;
+        let client = client(&endpoint, "wrong");
+
+        assert!(matches!(
+            client.add("bafkqaaa").await,
+            Err(super::Error::Status(StatusCode::UNAUTHORIZED, _))
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+// Recovery of the SQLite metadata from the MFS tree, eg: after losing `metadata.db`
+// Current objects are linked on MFS as `/{folder_prefix}/{bucket}/{key}`, so their mapping can be recreated.
+// Versions, tags, checksums, ETags, ACLs and bucket settings only live on SQLite and are not recovered:
+// restored objects are private unless requested otherwise, and content encrypted with customer keys is skipped.
+
+use std::collections::HashSet;
+
+use futures::StreamExt;
+use serde::Serialize;
+use typed_path::UnixPath;
+
+use crate::cli::{RebuildCommand, RunConfig};
+use crate::database::{Database, DatabaseError, NewObjectMetadata};
+use crate::encryption;
+use crate::ipfs::{self, IpfsClient, MfsEntry};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("IPFS error: {0}")]
+    Ipfs(#[from] ipfs::Error),
+
+    #[error("Invalid path: {0}")]
+    Path(#[from] typed_path::CheckedPathError),
+
+    #[error("Invalid size: {0}")]
+    Size(#[from] std::num::TryFromIntError),
+
+    #[error("Timed out reading {0}")]
+    Timeout(String),
+}
+
+/// Objects found on MFS, as `bucket/key`
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub restored: Vec<String>,
+    /// Objects with metadata already stored, left untouched
+    pub existing: usize,
+    /// Encrypted content skipped, as the server key is not configured or didn't encrypt it (eg: `SSE-C`)
+    pub encrypted: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Whether restored objects are private, as object ACLs only live on `SQLite`
+/// Objects in `private_buckets` are always private
+#[derive(Clone, Copy)]
+pub(crate) enum Visibility<'a> {
+    Private,
+    Public,
+    /// Only objects with the same key and CID as a public one, as `(key, cid)`, are public
+    Known(&'a HashSet<(String, String)>),
+}
+
+impl Visibility<'_> {
+    pub(crate) fn is_private(self, config: &RunConfig, bucket: &str, key: &str, cid: &str) -> bool {
+        let private = match self {
+            Visibility::Private => true,
+            Visibility::Public => false,
+            Visibility::Known(public) => !public.contains(&(key.to_string(), cid.to_string())),
+        };
+        private || config.private_buckets.iter().any(|b| b == bucket)
+    }
+}
+
+enum Outcome {
+    Restored,
+    Existing,
+    Encrypted,
+}
+
+/// Metadata of a file found on MFS, recreated as an object
+pub(crate) struct Restored {
+    pub key: String,
+    pub cid: String,
+    size: i64,
+    content_type: String,
+    encryption: Option<&'static str>,
+    pub private: bool,
+}
+
+impl Restored {
+    pub(crate) fn metadata<'a>(&'a self, bucket: &'a str) -> NewObjectMetadata<'a> {
+        NewObjectMetadata {
+            bucket,
+            key: &self.key,
+            cid: &self.cid,
+            size: self.size,
+            content_type: &self.content_type,
+            encryption: self.encryption,
+            encryption_key_md5: None,
+            private: self.private,
+            version_id: None,
+            retention_mode: None,
+            retain_until: None,
+            legal_hold: false,
+            checksum_algorithm: None,
+            checksum: None,
+            checksum_type: None,
+            etag: None,
+            parts: None,
+            tags: &[],
+        }
+    }
+}
+
+/// Runs `aricanduva rebuild`, printing the report as JSON
+/// Returns the exit code, failing when objects could not be restored
+pub async fn command(db: &Database, ipfs: &IpfsClient, command: &RebuildCommand) -> i32 {
+    let visibility = if command.public.unwrap_or_default() {
+        Visibility::Public
+    } else {
+        Visibility::Private
+    };
+    match rebuild(db, ipfs, &command.config, visibility).await {
+        Ok(report) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).unwrap_or_default()
+            );
+            i32::from(!report.errors.is_empty())
+        }
+        Err(e) => {
+            eprintln!("Failed to rebuild metadata: {e}");
+            1
+        }
+    }
+}
+
+/// Stores metadata for every file on MFS under `folder_prefix` without an object
+pub async fn rebuild(
+    db: &Database,
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    visibility: Visibility<'_>,
+) -> Result<Report, Error> {
+    let root = crate::s3::mfs_root(&config.folder_prefix)?;
+    let entries = ipfs.walk(&root).await?;
+    Ok(restore_entries(db, ipfs, config, &root, &entries, visibility).await)
+}
+
+/// Stores metadata for the files of `entries` without an object, linked under the MFS `root`
+async fn restore_entries(
+    db: &Database,
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    root: &UnixPath,
+    entries: &[MfsEntry],
+    visibility: Visibility<'_>,
+) -> Report {
+    let mut report = Report::default();
+    for entry in entries.iter().filter(|entry| !entry.directory) {
+        let Some((bucket, key)) = object_location(root, &entry.path) else {
+            tracing::warn!(path = %entry.path.to_string_lossy(), "Skipping file outside of buckets");
+            continue;
+        };
+
+        let name = format!("{bucket}/{key}");
+        let private = visibility.is_private(config, &bucket, &key, &entry.cid);
+        match restore(db, ipfs, config, &bucket, &key, entry, private).await {
+            Ok(Outcome::Restored) => {
+                tracing::info!(bucket, key, cid = entry.cid, "Restored object");
+                report.restored.push(name);
+            }
+            Ok(Outcome::Existing) => report.existing += 1,
+            Ok(Outcome::Encrypted) => report.encrypted.push(name),
+            Err(e) => report.errors.push(format!("{name}: {e}")),
+        }
+    }
+
+    report
+}
+
+/// Bucket and key of a file linked under the MFS `root`
+/// Bucket names can't start with a dot, so hidden directories (eg: snapshots being restored) are skipped
+fn object_location(root: &UnixPath, path: &UnixPath) -> Option<(String, String)> {
+    let relative = path.strip_prefix(root).ok()?.to_string_lossy().into_owned();
+    let (bucket, key) = relative.split_once('/')?;
+    (!bucket.is_empty() && !bucket.starts_with('.') && !key.is_empty())
+        .then(|| (bucket.to_string(), key.to_string()))
+}
+
+async fn restore(
+    db: &Database,
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    bucket: &str,
+    key: &str,
+    entry: &MfsEntry,
+    private: bool,
+) -> Result<Outcome, Error> {
+    if db.get_object_metadata(bucket, key).await?.is_some() {
+        return Ok(Outcome::Existing);
+    }
+
+    match inspect(ipfs, config, key, entry, private).await? {
+        Some(object) => {
+            db.store_object_metadata(&object.metadata(bucket)).await?;
+            Ok(Outcome::Restored)
+        }
+        None => Ok(Outcome::Encrypted),
+    }
+}
+
+/// Reads the start of a file on MFS to recreate its object, within `import_timeout`
+/// Returns `None` for encrypted content the server key doesn't decrypt, as it can't be read
+pub(crate) async fn inspect(
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    key: &str,
+    entry: &MfsEntry,
+    private: bool,
+) -> Result<Option<Restored>, Error> {
+    tokio::time::timeout(
+        config.import_timeout.into(),
+        inspect_content(ipfs, config, key, entry, private),
+    )
+    .await
+    .map_err(|_| Error::Timeout(entry.path.to_string_lossy().into_owned()))?
+}
+
+async fn inspect_content(
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    key: &str,
+    entry: &MfsEntry,
+    private: bool,
+) -> Result<Option<Restored>, Error> {
+    // Encrypted content is detected by its header, and only restored when the server key decrypts it,
+    // as content encrypted with customer keys can't be read without them
+    let start = ipfs
+        .get_content(&entry.cid)
+        .next()
+        .await
+        .transpose()?
+        .unwrap_or_default();
+    let (encryption, size) = if encryption::is_encrypted(&start) {
+        let (Some(server), Some(size)) = (
+            config.encryption.server(),
+            encryption::plaintext_size(entry.size),
+        ) else {
+            return Ok(None);
+        };
+        if !server.decrypts(ipfs.get_content(&entry.cid)).await {
+            return Ok(None);
+        }
+        (Some(encryption::ALGORITHM), size)
+    } else {
+        (None, entry.size)
+    };
+
+    Ok(Some(Restored {
+        key: key.to_string(),
+        cid: entry.cid.clone(),
+        size: i64::try_from(size)?,
+        content_type: crate::s3::guessed_content_type(key),
+        encryption,
+        private,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use typed_path::UnixPath;
+
+    use super::object_location;
+
+    #[test]
+    fn test_object_location() {
+        let root = UnixPath::new("/buckets");
+        assert_eq!(
+            object_location(root, UnixPath::new("/buckets/bucket/docs/a.txt")),
+            Some(("bucket".to_string(), "docs/a.txt".to_string()))
+        );
+        assert_eq!(
+            object_location(root, UnixPath::new("/buckets/stray.txt")),
+            None
+        );
+        assert_eq!(object_location(root, UnixPath::new("/other/a.txt")), None);
+        assert_eq!(
+            object_location(root, UnixPath::new("/buckets/.restore-bucket/a.txt")),
+            None
+        );
+    }
+}
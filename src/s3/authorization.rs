@@ -1,11 +1,22 @@
-use std::{borrow::Cow, collections::HashMap, fmt::Debug, num::ParseIntError, str::Utf8Error};
+use std::{
+    borrow::Cow, collections::HashMap, fmt::Debug, num::ParseIntError, str::Utf8Error,
+    time::Duration,
+};
 
-use axum::{body::Body, extract::Request, http::StatusCode, response::IntoResponse};
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Request},
+    http::StatusCode,
+    response::IntoResponse,
+};
 use bytes::Bytes;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
 use conf::Conf;
-use futures::{AsyncBufReadExt, AsyncReadExt, FutureExt, Stream, TryStreamExt};
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, FutureExt, Stream, TryStreamExt};
 use hmac::{Hmac, Mac};
-use http::{HeaderMap, HeaderValue, Uri, header};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Uri, header};
+use http_body::Frame;
+use http_body_util::StreamBody;
 use percent_encoding::{AsciiSet, percent_encode};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -18,12 +29,18 @@ use tower_layer::Layer;
 use tower_service::Service;
 use url::Url;
 
+mod sigv2;
+
 #[derive(Clone, Serialize, Deserialize, Conf)]
 pub struct AuthConfig {
     #[conf(long, env)]
     access_key: String,
     #[conf(long, env)]
     secret_key: String,
+    #[conf(long, env, default(false))]
+    #[serde(default)]
+    /// Also accept legacy AWS Signature Version 2 (`Authorization: AWS` header and `Signature=` query)
+    sigv2: Option<bool>,
 }
 
 impl Debug for AuthConfig {
@@ -31,10 +48,15 @@ impl Debug for AuthConfig {
         f.debug_struct("AuthConfig")
             .field("access_key", &self.access_key)
             .field("secret_key", &"REDACTED")
+            .field("sigv2", &self.sigv2)
             .finish()
     }
 }
 
+/// Extension of requests authenticated by a signed header or a presigned URL
+#[derive(Clone, Copy, Debug)]
+pub struct Authenticated;
+
 #[derive(Clone)]
 pub struct AuthorizationLayer {
     config: Arc<AuthConfig>,
@@ -71,6 +93,20 @@ pub struct AuthorizationService<S> {
     config: AuthorizationLayer,
 }
 
+impl<S> AuthorizationService<S> {
+    /// Checks `SigV4` signatures, falling back to `SigV2` when enabled on [`AuthConfig`]
+    fn is_authorized(&self, request: &Request) -> bool {
+        let config = self.config.as_ref();
+        match from_authorization_header(request).or_else(|| from_query_params(request)) {
+            Some(sign) => sign.is_valid(config),
+            None if config.sigv2.unwrap_or_default() => sigv2::from_authorization_header(request)
+                .or_else(|| sigv2::from_query_params(request))
+                .is_some_and(|sign| sign.is_valid(config)),
+            None => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct AuthenticationRequest<'a> {
     credential: Cow<'a, str>,
@@ -91,13 +127,7 @@ impl AuthenticationRequest<'_> {
             return false;
         }
 
-        let date_key = Self::sign(
-            format!("AWS4{}", config.secret_key).as_bytes(),
-            self.date.as_bytes(),
-        );
-        let date_region_key = Self::sign(&date_key, self.region.as_bytes());
-        let date_region_service_key = Self::sign(&date_region_key, self.service.as_bytes());
-        let sign_key = Self::sign(&date_region_service_key, "aws4_request".as_bytes());
+        let sign_key = Self::signing_key(config, &self.date, &self.region, &self.service);
 
         // Compute HMAC of string_to_sign with the final signing key
         let hmac_result = Self::sign(&sign_key, self.string_to_sign.as_bytes());
@@ -105,6 +135,17 @@ impl AuthenticationRequest<'_> {
         hex::encode(&hmac_result).as_str() == self.signature
     }
 
+    /// Derives the `SigV4` signing key scoped to the date, region and service
+    fn signing_key(config: &AuthConfig, date: &str, region: &str, service: &str) -> Vec<u8> {
+        let date_key = Self::sign(
+            format!("AWS4{}", config.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let date_region_key = Self::sign(&date_key, region.as_bytes());
+        let date_region_service_key = Self::sign(&date_region_key, service.as_bytes());
+        Self::sign(&date_region_service_key, "aws4_request".as_bytes())
+    }
+
     fn sign(key: &[u8], data: &[u8]) -> Vec<u8> {
         let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
         mac.update(data);
@@ -120,6 +161,12 @@ const PERCENT_ENCODE_SET: AsciiSet = percent_encoding::NON_ALPHANUMERIC
     .remove(b'_')
     .remove(b'~');
 
+/// Format used by `x-amz-date` and `X-Amz-Date`
+const AMZ_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Longest expiration accepted by AWS for presigned URLs (7 days)
+pub const MAX_PRESIGN_EXPIRATION: Duration = Duration::from_hours(7 * 24);
+
 /// <https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html>
 const EMTPY_BODY_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
 
@@ -150,6 +197,14 @@ fn canonicalize_query_string(uri: &Uri) -> Option<String> {
     Some(parts.join("&"))
 }
 
+/// URI as sent by the client, before any rewrite such as [`super::virtual_host::rewrite`]
+fn signed_uri(request: &Request) -> &Uri {
+    request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or(request.uri(), |original| &original.0)
+}
+
 fn canonicalize_headers(headers: &HeaderMap, signed_headers: &[&str]) -> String {
     let mut pairs = signed_headers
         .iter()
@@ -215,8 +270,8 @@ fn from_authorization_header(request: &Request) -> Option<AuthenticationRequest<
 
     // Create canonical request and string to sign using S3 logic
     let method = request.method().as_str();
-    let canonical_uri = canonicalize_uri(request.uri());
-    let canonical_query_string = canonicalize_query_string(request.uri()).unwrap_or_default();
+    let canonical_uri = canonicalize_uri(signed_uri(request));
+    let canonical_query_string = canonicalize_query_string(signed_uri(request)).unwrap_or_default();
     let canonical_headers = canonicalize_headers(request.headers(), &signed_headers);
     let signed_headers_list = signed_headers_part;
 
@@ -284,6 +339,27 @@ fn from_query_params(request: &Request) -> Option<AuthenticationRequest<'_>> {
     let signed_headers = query.remove("x-amz-signedheaders").unwrap_or_default();
     let date_time = query.remove("x-amz-date")?;
 
+    if let Some(expires) = query.remove("x-amz-expires") {
+        // Checked before the signature, so out of range values must not overflow
+        let max = i64::try_from(MAX_PRESIGN_EXPIRATION.as_secs()).ok()?;
+        let Some(expires) = expires
+            .parse::<i64>()
+            .ok()
+            .filter(|seconds| (1..=max).contains(seconds))
+            .and_then(TimeDelta::try_seconds)
+        else {
+            tracing::warn!(%expires, "Invalid presigned URL expiration");
+            return None;
+        };
+        let signed_at = NaiveDateTime::parse_from_str(&date_time, AMZ_DATE_FORMAT)
+            .ok()?
+            .and_utc();
+        if Utc::now() > signed_at.checked_add_signed(expires)? {
+            tracing::warn!(%signed_at, "Presigned URL expired");
+            return None;
+        }
+    }
+
     // Parse credential format: AccessKeyId/YYYYMMDD/aws-region/aws-service/aws4_request
     let credential_parts: Vec<_> = access_key_id.split('/').collect();
     if credential_parts.len() < 5 {
@@ -295,8 +371,8 @@ fn from_query_params(request: &Request) -> Option<AuthenticationRequest<'_>> {
     };
 
     let method = request.method().as_str();
-    let canonical_uri = canonicalize_uri(request.uri());
-    let canonical_query_string = canonicalize_query_string(request.uri()).unwrap_or_default();
+    let canonical_uri = canonicalize_uri(signed_uri(request));
+    let canonical_query_string = canonicalize_query_string(signed_uri(request)).unwrap_or_default();
     let signed_headers_list = signed_headers.split(';').collect::<Vec<&str>>();
     let canonical_headers = canonicalize_headers(request.headers(), &signed_headers_list);
 
@@ -330,6 +406,109 @@ fn from_query_params(request: &Request) -> Option<AuthenticationRequest<'_>> {
     })
 }
 
+/// Parameters used to generate a presigned URL
+#[derive(Debug)]
+pub struct PresignRequest<'a> {
+    /// Public address clients use to reach the service
+    pub endpoint: &'a Uri,
+    pub method: &'a Method,
+    pub bucket: &'a str,
+    pub key: &'a str,
+    pub region: &'a str,
+    pub expires: Duration,
+    pub now: DateTime<Utc>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PresignError {
+    #[error("Expiration must be at most {} seconds", MAX_PRESIGN_EXPIRATION.as_secs())]
+    ExpirationTooLong,
+    #[error("Endpoint must include scheme and host")]
+    InvalidEndpoint,
+    #[error("Could not build URL: {0}")]
+    InvalidUri(#[from] http::uri::InvalidUri),
+}
+
+/// Generates a presigned URL using `SigV4` query parameter authentication
+///
+/// Uses the same canonicalization as [`from_query_params`] so the URL is accepted by [`AuthorizationService`]
+///
+/// Ref <https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-query-string-auth.html>
+pub fn presign(config: &AuthConfig, request: &PresignRequest<'_>) -> Result<Uri, PresignError> {
+    if request.expires > MAX_PRESIGN_EXPIRATION {
+        return Err(PresignError::ExpirationTooLong);
+    }
+    let (Some(scheme), Some(authority)) = (request.endpoint.scheme(), request.endpoint.authority())
+    else {
+        return Err(PresignError::InvalidEndpoint);
+    };
+
+    let date_time = request.now.format(AMZ_DATE_FORMAT).to_string();
+    let date = request.now.format("%Y%m%d").to_string();
+    let service = "s3";
+    let credential = format!(
+        "{}/{date}/{}/{service}/aws4_request",
+        config.access_key, request.region
+    );
+
+    let path = std::iter::once(request.bucket)
+        .chain(request.key.split('/'))
+        .map(|segment| percent_encode(segment.as_bytes(), &PERCENT_ENCODE_SET).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    let path = format!("{}/{path}", request.endpoint.path().trim_end_matches('/'));
+
+    let query = [
+        ("X-Amz-Algorithm", "AWS4-HMAC-SHA256"),
+        ("X-Amz-Credential", &credential),
+        ("X-Amz-Date", &date_time),
+        ("X-Amz-Expires", &request.expires.as_secs().to_string()),
+        ("X-Amz-SignedHeaders", "host"),
+    ]
+    .iter()
+    .map(|(k, v)| format!("{k}={}", percent_encode(v.as_bytes(), &PERCENT_ENCODE_SET)))
+    .collect::<Vec<_>>()
+    .join("&");
+
+    let unsigned: Uri = format!("{path}?{query}").parse()?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::HOST,
+        HeaderValue::from_str(authority.as_str()).map_err(|_| PresignError::InvalidEndpoint)?,
+    );
+
+    let method = request.method.as_str();
+    let canonical_uri = canonicalize_uri(&unsigned);
+    let canonical_query_string = canonicalize_query_string(&unsigned).unwrap_or_default();
+    let canonical_headers = canonicalize_headers(&headers, &["host"]);
+    let canonical_request = format!(
+        r"{method}
+{canonical_uri}
+{canonical_query_string}
+{canonical_headers}
+
+host
+UNSIGNED-PAYLOAD",
+    );
+
+    let string_to_sign = format!(
+        r"AWS4-HMAC-SHA256
+{date_time}
+{date}/{}/{service}/aws4_request
+{}",
+        request.region,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let sign_key = AuthenticationRequest::signing_key(config, &date, request.region, service);
+    let signature = hex::encode(AuthenticationRequest::sign(
+        &sign_key,
+        string_to_sign.as_bytes(),
+    ));
+
+    Ok(format!("{scheme}://{authority}{unsigned}&X-Amz-Signature={signature}").parse()?)
+}
+
 impl<T> Service<Request> for AuthorizationService<T>
 where
     T: Service<Request>,
@@ -348,23 +527,23 @@ where
     fn call(&mut self, request: Request) -> Self::Future {
         let _headers = request.headers();
 
-        if let Some(sign) =
-            from_authorization_header(&request).or_else(|| from_query_params(&request))
-            && sign.is_valid(self.config.as_ref()) {
-                let content_encoding = request.headers().get("x-amz-content-sha256").cloned();
-                let (parts, body) = request.into_parts();
-                let body = if content_encoding
-                    == Some(HeaderValue::from_static(
-                        "STREAMING-AWS4-HMAC-SHA256-PAYLOAD",
-                    )) {
-                    Body::from_stream(streaming_chunk_body(body))
-                } else {
-                    body
-                };
-                let request = Request::from_parts(parts, body);
-                let future = self.inner.call(request);
-                return async { Ok(future.await?.into_response()) }.boxed();
-            }
+        if self.is_authorized(&request) {
+            let content_encoding = request.headers().get("x-amz-content-sha256").cloned();
+            let (mut parts, body) = request.into_parts();
+            parts.extensions.insert(Authenticated);
+            let body = if content_encoding
+                .as_ref()
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| STREAMING_PAYLOADS.contains(&value))
+            {
+                Body::new(StreamBody::new(streaming_chunk_body(body)))
+            } else {
+                body
+            };
+            let request = Request::from_parts(parts, body);
+            let future = self.inner.call(request);
+            return async { Ok(future.await?.into_response()) }.boxed();
+        }
 
         async {
             tracing::error!("Authorization failed");
@@ -380,36 +559,47 @@ enum StreamingErrors {
     Parsing(#[from] Utf8Error),
     #[error("Could not parse chunk size")]
     ParseInt(#[from] ParseIntError),
+    #[error("Could not parse trailer")]
+    Trailer,
     #[error("Could not read body")]
     IoRead(#[from] std::io::Error),
 }
 
-/// Provides a body following the chunk signature specs
+/// Values of `x-amz-content-sha256` for payloads sent with `Content-Encoding: aws-chunked`
+const STREAMING_PAYLOADS: [&str; 3] = [
+    "STREAMING-AWS4-HMAC-SHA256-PAYLOAD",
+    "STREAMING-AWS4-HMAC-SHA256-PAYLOAD-TRAILER",
+    "STREAMING-UNSIGNED-PAYLOAD-TRAILER",
+];
+
+/// Provides a body following the chunk signature specs, with trailers (eg: checksums) as the last frame
 /// <https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming.html>
-fn streaming_chunk_body(body: Body) -> impl Stream<Item = Result<Bytes, StreamingErrors>> {
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming-trailers.html>
+fn streaming_chunk_body(body: Body) -> impl Stream<Item = Result<Frame<Bytes>, StreamingErrors>> {
     let buffer = body
         .into_data_stream()
         .map_err(std::io::Error::other)
         .inspect_err(|error| tracing::error!(%error, "Failed to read body"))
         .into_async_read();
-    futures::stream::try_unfold(buffer, |mut buffer| async move {
-        let mut size_varint = Vec::new();
-        buffer.read_until(b';', &mut size_varint).await?;
-        let chunk_size = str::from_utf8(&size_varint[..&size_varint.len() - 1])?;
+    futures::stream::try_unfold(Some(buffer), |buffer| async move {
+        let Some(mut buffer) = buffer else {
+            return Ok(None);
+        };
+
+        // "<hex size>;chunk-signature=<hex>\r\n", without the signature on unsigned payloads
+        let mut size_line = Vec::new();
+        buffer.read_until(b'\n', &mut size_line).await?;
+        let size_line = str::from_utf8(&size_line)?;
+        let chunk_size = size_line.trim_end().split(';').next().unwrap_or_default();
         let chunk_size = usize::from_str_radix(chunk_size, 16)?;
 
+        // TODO actual signature check of the chunk
+
         if chunk_size == 0 {
-            return Ok(None);
+            let trailers = streaming_trailers(&mut buffer).await?;
+            return Ok((!trailers.is_empty()).then(|| (Frame::trailers(trailers), None)));
         }
 
-        // TODO actual signature check of the chunk
-        // skip signature
-        // ";chunk-signature=<hex>\r\n"
-        // The signature is 64 bytes long (hex-encoded SHA256 hash) and
-        // starts with a 16 byte header: len("chunk-signature=") + 64 + 2 == 82.
-        let mut signature_buffer = [0; 82];
-        buffer.read_exact(&mut signature_buffer).await?;
-
         let mut chunk_buffer = vec![0; chunk_size];
         buffer.read_exact(&mut chunk_buffer).await?;
 
@@ -417,6 +607,198 @@ fn streaming_chunk_body(body: Body) -> impl Stream<Item = Result<Bytes, Streamin
         let mut newline = [0; 2];
         buffer.read_exact(&mut newline).await?;
 
-        Ok(Some((Bytes::from(chunk_buffer), buffer)))
+        Ok(Some((Frame::data(Bytes::from(chunk_buffer)), Some(buffer))))
     })
 }
+
+/// Reads "<name>:<value>\r\n" trailers after the last chunk, until an empty line
+async fn streaming_trailers(
+    buffer: &mut (impl AsyncBufRead + Unpin),
+) -> Result<HeaderMap, StreamingErrors> {
+    let mut trailers = HeaderMap::new();
+    loop {
+        let mut line = String::new();
+        if buffer.read_line(&mut line).await? == 0 {
+            return Ok(trailers);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(trailers);
+        }
+
+        let (name, value) = line.split_once(':').ok_or(StreamingErrors::Trailer)?;
+        // TODO actual signature check of the trailers
+        if name == "x-amz-trailer-signature" {
+            continue;
+        }
+        trailers.append(
+            HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| StreamingErrors::Trailer)?,
+            HeaderValue::from_str(value.trim()).map_err(|_| StreamingErrors::Trailer)?,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    mod presign {
+        use std::time::Duration;
+
+        use axum::{body::Body, extract::Request};
+        use http::{Method, Uri, header};
+
+        use crate::s3::authorization::{
+            AuthConfig, PresignError, PresignRequest, from_query_params, presign,
+        };
+
+        fn config() -> AuthConfig {
+            AuthConfig {
+                access_key: "banana".to_string(),
+                secret_key: "bananabanana".to_string(),
+                sigv2: None,
+            }
+        }
+
+        fn as_request(method: Method, url: &Uri) -> Request {
+            Request::builder()
+                .method(method)
+                .uri(url.path_and_query().unwrap().as_str())
+                .header(header::HOST, url.authority().unwrap().as_str())
+                .body(Body::empty())
+                .unwrap()
+        }
+
+        #[test]
+        fn test_presigned_url_is_accepted() {
+            let endpoint = Uri::from_static("http://localhost:3000");
+            let url = presign(
+                &config(),
+                &PresignRequest {
+                    endpoint: &endpoint,
+                    method: &Method::GET,
+                    bucket: "banana-bucket",
+                    key: "path/to/some file.txt",
+                    region: "us-east-1",
+                    expires: Duration::from_secs(3600),
+                    now: chrono::Utc::now(),
+                },
+            )
+            .unwrap();
+
+            assert_eq!(url.path(), "/banana-bucket/path/to/some%20file.txt");
+            let request = as_request(Method::GET, &url);
+            let sign = from_query_params(&request).unwrap();
+            assert!(sign.is_valid(&config()));
+
+            let request = as_request(Method::PUT, &url);
+            let sign = from_query_params(&request).unwrap();
+            assert!(!sign.is_valid(&config()));
+        }
+
+        #[test]
+        fn test_expired_url_is_rejected() {
+            let endpoint = Uri::from_static("http://localhost:3000");
+            let url = presign(
+                &config(),
+                &PresignRequest {
+                    endpoint: &endpoint,
+                    method: &Method::GET,
+                    bucket: "banana-bucket",
+                    key: "README.md",
+                    region: "us-east-1",
+                    expires: Duration::from_secs(60),
+                    now: chrono::Utc::now() - chrono::TimeDelta::hours(1),
+                },
+            )
+            .unwrap();
+
+            assert!(from_query_params(&as_request(Method::GET, &url)).is_none());
+        }
+
+        #[test]
+        fn test_rejects_long_expiration() {
+            let endpoint = Uri::from_static("http://localhost:3000");
+            let result = presign(
+                &config(),
+                &PresignRequest {
+                    endpoint: &endpoint,
+                    method: &Method::GET,
+                    bucket: "banana-bucket",
+                    key: "README.md",
+                    region: "us-east-1",
+                    expires: Duration::from_hours(8 * 24),
+                    now: chrono::Utc::now(),
+                },
+            );
+
+            assert!(matches!(result, Err(PresignError::ExpirationTooLong)));
+        }
+
+        #[test]
+        fn test_rejects_out_of_range_expires() {
+            let endpoint = Uri::from_static("http://localhost:3000");
+            let url = presign(
+                &config(),
+                &PresignRequest {
+                    endpoint: &endpoint,
+                    method: &Method::GET,
+                    bucket: "banana-bucket",
+                    key: "README.md",
+                    region: "us-east-1",
+                    expires: Duration::from_secs(60),
+                    now: chrono::Utc::now(),
+                },
+            )
+            .unwrap();
+            assert!(url.to_string().contains("X-Amz-Expires=60&"));
+
+            for expires in ["9223372036854775807", "-60", "0", "604801"] {
+                let url: Uri = url
+                    .to_string()
+                    .replace("X-Amz-Expires=60", &format!("X-Amz-Expires={expires}"))
+                    .parse()
+                    .unwrap();
+                assert!(from_query_params(&as_request(Method::GET, &url)).is_none());
+            }
+        }
+    }
+
+    mod streaming {
+        use axum::body::Body;
+        use http_body_util::{BodyExt, StreamBody};
+
+        use crate::s3::authorization::streaming_chunk_body;
+
+        async fn decode(payload: String) -> (String, Option<http::HeaderMap>) {
+            let body = Body::new(StreamBody::new(streaming_chunk_body(Body::from(payload))));
+            let collected = body.collect().await.unwrap();
+            let trailers = collected.trailers().cloned();
+            (
+                String::from_utf8(collected.to_bytes().to_vec()).unwrap(),
+                trailers,
+            )
+        }
+
+        #[tokio::test]
+        async fn test_signed_chunks() {
+            let signature = "0".repeat(64);
+            let payload = format!(
+                "5;chunk-signature={signature}\r\nhello\r\n6;chunk-signature={signature}\r\n world\r\n0;chunk-signature={signature}\r\n\r\n"
+            );
+            let (body, trailers) = decode(payload).await;
+            assert_eq!(body, "hello world");
+            assert!(trailers.is_none());
+        }
+
+        #[tokio::test]
+        async fn test_unsigned_chunks_with_trailers() {
+            let (body, trailers) = decode(
+                "5\r\nhello\r\n6\r\n world\r\n0\r\nx-amz-checksum-crc32:DUoRhQ==\r\nx-amz-trailer-signature:abc\r\n\r\n".to_string(),
+            )
+            .await;
+            assert_eq!(body, "hello world");
+            let trailers = trailers.unwrap();
+            assert_eq!(trailers["x-amz-checksum-crc32"], "DUoRhQ==");
+            assert!(!trailers.contains_key("x-amz-trailer-signature"));
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! Additional checksums of uploads, sent by SDKs on `x-amz-checksum-*` headers or `aws-chunked` trailers
+//!
+//! Checksums are verified against the received content and stored, to be returned on `HeadObject`
+//! and `GetObject` when requested with `x-amz-checksum-mode: ENABLED`.
+//!
+//! Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/checking-object-integrity.html>
+
+use std::str::FromStr;
+
+use axum::RequestExt;
+use axum::extract::{FromRequest, Request};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode, response::Builder};
+use http_body_util::{BodyExt, LengthLimitError};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::database::MetadataResponse;
+
+const ALGORITHM_HEADER: &str = "x-amz-checksum-algorithm";
+const SDK_ALGORITHM_HEADER: &str = "x-amz-sdk-checksum-algorithm";
+const TYPE_HEADER: &str = "x-amz-checksum-type";
+const MODE_HEADER: &str = "x-amz-checksum-mode";
+const TRAILER_HEADER: &str = "x-amz-trailer";
+const CONTENT_MD5_HEADER: &str = "content-md5";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Unsupported checksum algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Unsupported checksum type: {0}")]
+    UnsupportedType(String),
+
+    #[error("{0} checksums can't be {1}")]
+    InvalidType(&'static str, &'static str),
+
+    #[error("Only one checksum can be sent")]
+    MultipleChecksums,
+
+    #[error("Checksum declared on x-amz-trailer was not sent")]
+    MissingTrailer,
+
+    #[error("Checksum does not match the content")]
+    Mismatch,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    Crc32c,
+    Sha1,
+    Sha256,
+    Crc64Nvme,
+}
+
+impl ChecksumAlgorithm {
+    const ALL: [ChecksumAlgorithm; 5] = [
+        ChecksumAlgorithm::Crc32,
+        ChecksumAlgorithm::Crc32c,
+        ChecksumAlgorithm::Sha1,
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Crc64Nvme,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc32 => "CRC32",
+            ChecksumAlgorithm::Crc32c => "CRC32C",
+            ChecksumAlgorithm::Sha1 => "SHA1",
+            ChecksumAlgorithm::Sha256 => "SHA256",
+            ChecksumAlgorithm::Crc64Nvme => "CRC64NVME",
+        }
+    }
+
+    /// Header carrying the checksum value
+    fn header(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc32 => "x-amz-checksum-crc32",
+            ChecksumAlgorithm::Crc32c => "x-amz-checksum-crc32c",
+            ChecksumAlgorithm::Sha1 => "x-amz-checksum-sha1",
+            ChecksumAlgorithm::Sha256 => "x-amz-checksum-sha256",
+            ChecksumAlgorithm::Crc64Nvme => "x-amz-checksum-crc64nvme",
+        }
+    }
+
+    /// Only CRC checksums can be combined into a full object checksum of multipart uploads
+    fn is_crc(self) -> bool {
+        !matches!(self, ChecksumAlgorithm::Sha1 | ChecksumAlgorithm::Sha256)
+    }
+
+    /// Base64 encoded digest of the content, as sent on `x-amz-checksum-*` headers
+    pub fn encoded(self, content: &[u8]) -> String {
+        BASE64_STANDARD.encode(self.digest([content]))
+    }
+
+    fn digest<'a>(self, chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+        match self {
+            ChecksumAlgorithm::Crc32 => {
+                let mut hasher = crc32fast::Hasher::new();
+                chunks.into_iter().for_each(|chunk| hasher.update(chunk));
+                hasher.finalize().to_be_bytes().to_vec()
+            }
+            ChecksumAlgorithm::Crc32c => chunks
+                .into_iter()
+                .fold(0, crc32c::crc32c_append)
+                .to_be_bytes()
+                .to_vec(),
+            ChecksumAlgorithm::Sha1 => {
+                let mut hasher = Sha1::new();
+                chunks.into_iter().for_each(|chunk| hasher.update(chunk));
+                hasher.finalize().to_vec()
+            }
+            ChecksumAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                chunks.into_iter().for_each(|chunk| hasher.update(chunk));
+                hasher.finalize().to_vec()
+            }
+            ChecksumAlgorithm::Crc64Nvme => {
+                let mut digest = crc64fast_nvme::Digest::new();
+                chunks.into_iter().for_each(|chunk| digest.write(chunk));
+                digest.sum64().to_be_bytes().to_vec()
+            }
+        }
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChecksumAlgorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::UnsupportedAlgorithm(s.to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ChecksumType {
+    /// Checksum of the whole content
+    FullObject,
+    /// Checksum of the checksums of each part of a multipart upload
+    Composite,
+}
+
+impl ChecksumType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChecksumType::FullObject => "FULL_OBJECT",
+            ChecksumType::Composite => "COMPOSITE",
+        }
+    }
+}
+
+impl FromStr for ChecksumType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "FULL_OBJECT" => Ok(ChecksumType::FullObject),
+            "COMPOSITE" => Ok(ChecksumType::Composite),
+            _ => Err(Error::UnsupportedType(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub kind: ChecksumType,
+    /// Base64 encoded, with a `-N` suffix for composite checksums of `N` parts
+    pub value: String,
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+impl Checksum {
+    fn full_object<'a>(
+        algorithm: ChecksumAlgorithm,
+        chunks: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Self {
+        Checksum {
+            algorithm,
+            kind: ChecksumType::FullObject,
+            value: BASE64_STANDARD.encode(algorithm.digest(chunks)),
+        }
+    }
+
+    /// Algorithm requested by the client, along with the expected checksum when sent
+    fn requested(headers: &HeaderMap) -> Result<Option<(ChecksumAlgorithm, Option<&str>)>, Error> {
+        let sent = ChecksumAlgorithm::ALL
+            .into_iter()
+            .filter_map(|algorithm| Some((algorithm, header(headers, algorithm.header())?)))
+            .collect::<Vec<_>>();
+        match sent.as_slice() {
+            [] => {}
+            [(algorithm, expected)] => return Ok(Some((*algorithm, Some(expected)))),
+            _ => return Err(Error::MultipleChecksums),
+        }
+
+        // Trailers are merged into headers once the body is read
+        if header(headers, TRAILER_HEADER).is_some_and(|name| name.starts_with("x-amz-checksum-")) {
+            return Err(Error::MissingTrailer);
+        }
+
+        header(headers, SDK_ALGORITHM_HEADER)
+            .or_else(|| header(headers, ALGORITHM_HEADER))
+            .map(|algorithm| Ok((algorithm.parse()?, None)))
+            .transpose()
+    }
+
+    /// Computes the checksum requested for an upload, verifying it against the one sent by the client
+    pub fn verified(headers: &HeaderMap, body: &[u8]) -> Result<Option<Self>, Error> {
+        let Some((algorithm, expected)) = Self::requested(headers)? else {
+            return Ok(None);
+        };
+
+        let checksum = Self::full_object(algorithm, [body]);
+        if expected.is_some_and(|expected| expected != checksum.value) {
+            return Err(Error::Mismatch);
+        }
+        Ok(Some(checksum))
+    }
+
+    /// Computes the checksum of a completed multipart upload, as requested on `CreateMultipartUpload`
+    ///
+    /// Checksums are composite by default, except for `CRC64NVME` which only supports full object checksums
+    pub fn multipart(headers: &HeaderMap, parts: &[Bytes]) -> Result<Option<Self>, Error> {
+        let Some((algorithm, _)) = Self::requested(headers)? else {
+            return Ok(None);
+        };
+
+        let kind = match header(headers, TYPE_HEADER) {
+            Some(kind) => kind.parse()?,
+            None if algorithm == ChecksumAlgorithm::Crc64Nvme => ChecksumType::FullObject,
+            None => ChecksumType::Composite,
+        };
+
+        match kind {
+            ChecksumType::FullObject if algorithm.is_crc() => Ok(Some(Self::full_object(
+                algorithm,
+                parts.iter().map(|part| &part[..]),
+            ))),
+            ChecksumType::Composite if algorithm != ChecksumAlgorithm::Crc64Nvme => {
+                let digests = parts
+                    .iter()
+                    .flat_map(|part| algorithm.digest([&part[..]]))
+                    .collect::<Vec<_>>();
+                Ok(Some(Checksum {
+                    algorithm,
+                    kind,
+                    value: format!(
+                        "{}-{}",
+                        BASE64_STANDARD.encode(algorithm.digest([&digests[..]])),
+                        parts.len()
+                    ),
+                }))
+            }
+            _ => Err(Error::InvalidType(algorithm.as_str(), kind.as_str())),
+        }
+    }
+
+    /// Checksum stored with the object, if any
+    pub fn stored(metadata: &MetadataResponse) -> Option<Self> {
+        Some(Checksum {
+            algorithm: metadata.checksum_algorithm.as_deref()?.parse().ok()?,
+            kind: metadata
+                .checksum_type
+                .as_deref()
+                .map_or(Ok(ChecksumType::FullObject), str::parse)
+                .ok()?,
+            value: metadata.checksum.clone()?,
+        })
+    }
+
+    /// Adds `x-amz-checksum-*` headers of the checksum
+    pub fn response_headers(&self, builder: Builder) -> Builder {
+        builder
+            .header(self.algorithm.header(), &self.value)
+            .header(TYPE_HEADER, self.kind.as_str())
+    }
+}
+
+/// S3 `ETag` of content uploaded at once: the hex encoded MD5, verified against `Content-MD5` when sent
+pub fn etag(headers: &HeaderMap, body: &[u8]) -> Result<String, Error> {
+    let md5 = Md5::digest(body);
+    if header(headers, CONTENT_MD5_HEADER)
+        .is_some_and(|expected| expected != BASE64_STANDARD.encode(md5))
+    {
+        return Err(Error::Mismatch);
+    }
+    Ok(hex::encode(md5))
+}
+
+/// S3 `ETag` of multipart uploads: the MD5 of the MD5 of each part, suffixed by the number of parts
+pub fn multipart_etag(parts: &[Bytes]) -> String {
+    let mut hasher = Md5::new();
+    for part in parts {
+        hasher.update(Md5::digest(part));
+    }
+    format!("{}-{}", hex::encode(hasher.finalize()), parts.len())
+}
+
+/// Adds the checksum of a stored object, only if requested with `x-amz-checksum-mode: ENABLED`
+pub fn response_headers(
+    builder: Builder,
+    metadata: &MetadataResponse,
+    headers: &HeaderMap,
+) -> Builder {
+    match Checksum::stored(metadata) {
+        Some(checksum) if header(headers, MODE_HEADER) == Some("ENABLED") => {
+            checksum.response_headers(builder)
+        }
+        _ => builder,
+    }
+}
+
+/// Request body along with the trailers of `aws-chunked` uploads, where SDKs send checksums
+pub struct TrailedBody {
+    pub body: Bytes,
+    pub trailers: HeaderMap,
+}
+
+impl<S: Send + Sync> FromRequest<S> for TrailedBody {
+    type Rejection = StatusCode;
+
+    async fn from_request(request: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        // Respects `DefaultBodyLimit`, as the `Bytes` extractor
+        let collected = match request.into_limited_body().collect().await {
+            Ok(collected) => collected,
+            Err(e) => {
+                let e = e.into_inner();
+                if e.is::<LengthLimitError>() {
+                    return Err(StatusCode::PAYLOAD_TOO_LARGE);
+                }
+                tracing::warn!(error = %e, "Failed to read body");
+                return Err(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        let trailers = collected.trailers().cloned().unwrap_or_default();
+        Ok(TrailedBody {
+            body: collected.to_bytes(),
+            trailers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue};
+
+    use super::{Checksum, ChecksumAlgorithm, ChecksumType, Error, etag, multipart_etag};
+
+    fn headers(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        values
+            .iter()
+            .map(|(name, value)| (*name, HeaderValue::from_static(value)))
+            .fold(HeaderMap::new(), |mut headers, (name, value)| {
+                headers.insert(name, value);
+                headers
+            })
+    }
+
+    #[test]
+    fn test_digests() {
+        let expected = [
+            (ChecksumAlgorithm::Crc32, "y/Q5Jg=="),
+            (ChecksumAlgorithm::Crc32c, "4waSgw=="),
+            (ChecksumAlgorithm::Sha1, "98O8HYCOBHMq32eZZczDTKeuNEE="),
+            (
+                ChecksumAlgorithm::Sha256,
+                "FeKw08M4keuw8e9gnsQZQgwg4yDOlMZfvIwzEkSOsiU=",
+            ),
+            (ChecksumAlgorithm::Crc64Nvme, "rosUhgp5mIg="),
+        ];
+        for (algorithm, value) in expected {
+            assert_eq!(
+                Checksum::full_object(algorithm, [&b"1234"[..], &b"56789"[..]]).value,
+                value,
+                "{}",
+                algorithm.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn test_verifies_sent_checksum() {
+        let checksum = Checksum::verified(
+            &headers(&[("x-amz-checksum-crc32", "y/Q5Jg==")]),
+            b"123456789",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(checksum.algorithm, ChecksumAlgorithm::Crc32);
+
+        assert!(matches!(
+            Checksum::verified(
+                &headers(&[("x-amz-checksum-crc32", "AAAAAA==")]),
+                b"123456789"
+            ),
+            Err(Error::Mismatch)
+        ));
+        assert!(matches!(
+            Checksum::verified(
+                &headers(&[
+                    ("x-amz-checksum-crc32", "y/Q5Jg=="),
+                    ("x-amz-checksum-sha1", "98O8HYCOBHMq32eZZczDTKeuNEE=")
+                ]),
+                b"123456789"
+            ),
+            Err(Error::MultipleChecksums)
+        ));
+        assert!(matches!(
+            Checksum::verified(
+                &headers(&[("x-amz-trailer", "x-amz-checksum-crc32")]),
+                b"123456789"
+            ),
+            Err(Error::MissingTrailer)
+        ));
+    }
+
+    #[test]
+    fn test_computes_requested_algorithm() {
+        let checksum = Checksum::verified(
+            &headers(&[("x-amz-sdk-checksum-algorithm", "crc32c")]),
+            b"123456789",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(checksum.value, "4waSgw==");
+        assert!(
+            Checksum::verified(&HeaderMap::new(), b"123456789")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_multipart() {
+        let parts = [Bytes::from_static(b"1234"), Bytes::from_static(b"56789")];
+
+        let composite =
+            Checksum::multipart(&headers(&[("x-amz-checksum-algorithm", "CRC32")]), &parts)
+                .unwrap()
+                .unwrap();
+        assert_eq!(composite.kind, ChecksumType::Composite);
+        assert_eq!(composite.value, "rBMwBw==-2");
+
+        let full_object = Checksum::multipart(
+            &headers(&[
+                ("x-amz-checksum-algorithm", "CRC32"),
+                ("x-amz-checksum-type", "FULL_OBJECT"),
+            ]),
+            &parts,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(full_object.value, "y/Q5Jg==");
+
+        assert!(matches!(
+            Checksum::multipart(
+                &headers(&[
+                    ("x-amz-checksum-algorithm", "SHA256"),
+                    ("x-amz-checksum-type", "FULL_OBJECT")
+                ]),
+                &parts
+            ),
+            Err(Error::InvalidType("SHA256", "FULL_OBJECT"))
+        ));
+    }
+
+    #[test]
+    fn test_etags() {
+        assert_eq!(
+            etag(&HeaderMap::new(), b"123456789").unwrap(),
+            "25f9e794323b453885f5181f1b624d0b"
+        );
+        assert_eq!(
+            etag(
+                &headers(&[("content-md5", "JfnnlDI7RTiF9RgfG2JNCw==")]),
+                b"123456789"
+            )
+            .unwrap(),
+            "25f9e794323b453885f5181f1b624d0b"
+        );
+        assert!(matches!(
+            etag(
+                &headers(&[("content-md5", "AAAAAAAAAAAAAAAAAAAAAA==")]),
+                b"123456789"
+            ),
+            Err(Error::Mismatch)
+        ));
+
+        let parts = [Bytes::from_static(b"1234"), Bytes::from_static(b"56789")];
+        assert_eq!(multipart_etag(&parts), "d8399932ce6023ac3365f50fbbcde132-2");
+    }
+}
@@ -0,0 +1,36 @@
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+
+use serde::Deserialize;
+
+use crate::AppState;
+
+#[derive(Deserialize)]
+pub struct DeleteBucketParams {
+    lifecycle: Option<String>,
+}
+
+#[axum::debug_handler]
+/// Only implements `DeleteBucketLifecycle` as Buckets are not real
+pub async fn delete_bucket(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Query(query): Query<DeleteBucketParams>,
+) -> Result<Response<Body>, StatusCode> {
+    if query.lifecycle.is_some() {
+        if let Err(e) = state.db.delete_lifecycle_rules(&bucket).await {
+            tracing::error!(error = %e, "Failed to delete lifecycle rules");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        tracing::info!(bucket, "Deleted bucket lifecycle rules");
+        return Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Body::empty())
+            .unwrap_or_default());
+    }
+
+    Err(StatusCode::NOT_IMPLEMENTED)
+}
@@ -1,83 +1,174 @@
 use axum::body::Body;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::Response;
 
 use serde::Deserialize;
 use tracing::Instrument;
 
 use crate::AppState;
+use crate::database;
 
 #[derive(Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteObjectParams {
-    upload_id: Option<String>,
+    pub(super) upload_id: Option<String>,
+    pub(super) version_id: Option<String>,
+    pub(super) tagging: Option<String>,
 }
 
-#[axum::debug_handler]
-/// Provides `DeleteObject` and `CancelMultiPartUpload` depending on query parameters
-pub async fn delete_object(
-    State(state): State<AppState>,
-    Path((bucket, key)): Path<(String, String)>,
-    Query(query): Query<DeleteObjectParams>,
+/// Implements `DeleteObjectTagging`
+async fn delete_object_tagging(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
 ) -> Result<Response<Body>, StatusCode> {
-    if let Some(upload_id) = query.upload_id {
-        let upload = state.multipart_slots.remove(&upload_id);
-        tracing::debug!(
-            bucket,
-            key,
-            upload_id,
-            present = upload.is_some(),
-            "Aborting multipart upload"
-        );
-        drop(upload); // Just to be explicit and drop allocation
-
-        return Ok(Response::builder()
+    let metadata = super::object_metadata(state, bucket, key, None).await?;
+    super::check_tagged_version(&metadata, version_id)?;
+
+    if let Err(e) = state.db.put_object_tags(bucket, key, &[]).await {
+        tracing::error!(error = %e, "Failed to delete object tags");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
             .status(StatusCode::NO_CONTENT)
             .body(Body::empty())
-            .unwrap_or_default());
-    }
+            .unwrap_or_default(),
+    )
+}
 
-    // Retrieve object metadata from SQLite
-    let metadata = match state.db.get_object_metadata(&bucket, &key).await {
-        Ok(Some(metadata)) => metadata,
+/// Permanently deletes a version of an object or a delete marker,
+/// updating MFS when another version becomes the current one
+async fn delete_version(
+    state: AppState,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+    headers: &HeaderMap,
+) -> Result<Response<Body>, StatusCode> {
+    super::object_lock::check_version_removable(&state, bucket, key, version_id, headers).await?;
+
+    let deletion = match state
+        .db
+        .delete_object_version(bucket, key, version_id)
+        .await
+    {
+        Ok(Some(deletion)) => deletion,
         Ok(None) => {
-            tracing::warn!(bucket, key, "Object not found");
+            tracing::warn!(bucket, key, version_id, "Version not found");
             return Err(StatusCode::NOT_FOUND);
         }
         Err(e) => {
-            tracing::error!(error = %e, "Failed to retrieve object metadata");
+            tracing::error!(error = %e, "Failed to delete object version");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
 
-    let path = match super::normalized_path(
-        &state.config.folder_prefix,
-        &metadata.bucket,
-        &metadata.key,
-    ) {
-        Ok(path) => path,
-        Err(e) => {
-            tracing::error!(error = %e, "Failed to normalize storage path");
+    let previous_cid = deletion.previous.as_ref().map(|m| &m.cid);
+    let current_cid = deletion.current.as_ref().map(|m| &m.cid);
+    if previous_cid != current_cid {
+        let path = match super::normalized_path(&state.config.folder_prefix, bucket, key) {
+            Ok(path) => path,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to normalize storage path");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        let result = match current_cid {
+            Some(cid) => state.ipfs_client.link(cid, &path).await,
+            None => state.ipfs_client.unlink(&path).await,
+        };
+        if let Err(e) = result {
+            tracing::error!(error = %e, "Failed to update current version on IPFS");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
+        state.ipns.notify(bucket);
+    }
+
+    let delete_marker = deletion.deleted.delete_marker;
+    if !delete_marker {
+        super::unpin_if_orphan(state, &deletion.deleted.into()).await?;
+    }
+
+    let response = Response::builder().header("x-amz-version-id", version_id);
+    let response = if delete_marker {
+        response.header("x-amz-delete-marker", "true")
+    } else {
+        response
     };
 
-    if let Err(e) = state.ipfs_client.unlink(&path).await {
-        tracing::error!(error = %e, "Failed to delete content from IPFS");
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(response
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap_or_default())
+}
+
+/// Adds another delete marker to an object already deleted on a versioned bucket
+async fn stack_delete_marker(
+    state: AppState,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+    headers: &HeaderMap,
+) -> Result<Response<Body>, StatusCode> {
+    match state.db.is_delete_marker_latest(bucket, key).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::warn!(bucket, key, "Object not found");
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve latest object version");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
     }
 
-    if let Err(e) = state.db.delete_object(&metadata).await {
-        tracing::error!(error = %e, "Failed to delete object metadata");
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    super::object_lock::check_replaceable(&state, None, bucket, key, Some(version_id), headers)
+        .await?;
+
+    let replaced = match state.db.create_delete_marker(bucket, key, version_id).await {
+        Ok(replaced) => replaced,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to store delete marker");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if let Some(replaced) = &replaced {
+        super::unpin_if_orphan(state.clone(), replaced).await?;
     }
 
-    super::unpin_if_orphan(state.clone(), &metadata).await?;
+    Ok(Response::builder()
+        .header("x-amz-delete-marker", "true")
+        .header("x-amz-version-id", version_id)
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap_or_default())
+}
 
-    let ipfs_path = format!("/ipfs/{}", metadata.cid);
-    let cid = metadata.cid;
+/// Implements `CancelMultiPartUpload`, dropping the staged parts
+fn abort_upload(state: &AppState, bucket: &str, key: &str, upload_id: &str) -> Response<Body> {
+    let upload = state.multipart_slots.remove(&upload_id.to_owned());
+    tracing::debug!(
+        bucket,
+        key,
+        upload_id,
+        present = upload.is_some(),
+        "Aborting multipart upload"
+    );
+    drop(upload); // Just to be explicit and drop allocation
+
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap_or_default()
+}
 
+/// Removes the folders left empty by a deleted object in the background, when enabled
+fn trim_empty_folders(state: &AppState, metadata: database::MetadataResponse) {
     if state
         .config
         .experimental
@@ -97,20 +188,123 @@ pub async fn delete_object(
                         &metadata.bucket,
                         &to_remove.to_string_lossy(),
                     )
+                    && state.ipfs_client.unlink(&path).await.is_ok()
                 {
-                    let _ = state.ipfs_client.unlink(&path).await;
+                    state.ipns.notify(&metadata.bucket);
                 }
             }
             .in_current_span()
             .instrument(event)
         });
     }
+}
+
+#[axum::debug_handler]
+/// Provides `DeleteObject`, `DeleteObjectTagging` and `CancelMultiPartUpload` depending on query parameters
+///
+/// Versioned buckets keep the content, replacing the object with a delete marker,
+/// unless a specific version is deleted.
+pub async fn delete_object(
+    State(state): State<AppState>,
+    Path((bucket, key)): Path<(String, String)>,
+    Query(query): Query<DeleteObjectParams>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, StatusCode> {
+    if let Some(upload_id) = query.upload_id {
+        return Ok(abort_upload(&state, &bucket, &key, &upload_id));
+    }
+
+    if query.tagging.is_some() {
+        return delete_object_tagging(&state, &bucket, &key, query.version_id.as_deref()).await;
+    }
+
+    if let Some(version_id) = query.version_id {
+        return delete_version(state, &bucket, &key, &version_id, &headers).await;
+    }
+
+    // Retrieve object metadata from SQLite
+    let version_id = super::new_version_id(&state, &bucket).await?;
+    let metadata = match state.db.get_object_metadata(&bucket, &key).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => {
+            if let Some(version_id) = &version_id {
+                return stack_delete_marker(state, &bucket, &key, version_id, &headers).await;
+            }
+            tracing::warn!(bucket, key, "Object not found");
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve object metadata");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    super::object_lock::check_replaceable(
+        &state,
+        Some(&metadata),
+        &bucket,
+        &key,
+        version_id.as_deref(),
+        &headers,
+    )
+    .await?;
+
+    let path = match super::normalized_path(
+        &state.config.folder_prefix,
+        &metadata.bucket,
+        &metadata.key,
+    ) {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to normalize storage path");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let Err(e) = state.ipfs_client.unlink(&path).await {
+        tracing::error!(error = %e, "Failed to delete content from IPFS");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    state.ipns.notify(&bucket);
+
+    let replaced = match &version_id {
+        Some(version_id) => {
+            state
+                .db
+                .create_delete_marker(&metadata.bucket, &metadata.key, version_id)
+                .await
+        }
+        None => state.db.delete_object(&metadata).await.map(|()| None),
+    };
+    let replaced = match replaced {
+        Ok(replaced) => replaced,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to delete object metadata");
+            // The object is still current, link its content back
+            if let Err(e) = state.ipfs_client.link(&metadata.cid, &path).await {
+                tracing::error!(error = %e, "Failed to link back deleted content");
+            }
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    super::unpin_if_orphan(state.clone(), &metadata).await?;
+    if let Some(replaced) = &replaced {
+        super::unpin_if_orphan(state.clone(), replaced).await?;
+    }
+
+    let response = super::ipfs_headers(Response::builder(), &metadata.cid, metadata.private);
+    let response = match &version_id {
+        Some(version_id) => response
+            .header("x-amz-delete-marker", "true")
+            .header("x-amz-version-id", version_id),
+        None => response,
+    };
+
+    trim_empty_folders(&state, metadata);
 
     // Return success response with S3-like headers
-    let response = Response::builder()
+    let response = response
         .status(StatusCode::NO_CONTENT)
-        .header("x-ipfs-path", ipfs_path)
-        .header("x-ipfs-roots", cid)
         .body(Body::empty())
         .unwrap_or_default();
 
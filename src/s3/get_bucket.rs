@@ -1,22 +1,263 @@
 use axum::body::Body;
-use axum::extract::{Path, Query};
+use axum::extract::{Path, Query, State};
 use axum::http::{StatusCode, header};
 use axum::response::Response;
 
 use serde::Deserialize;
 
+use super::put_bucket::lifecycle_payloads::LifecycleConfiguration;
+use super::put_bucket::versioning_payloads::VersioningConfiguration;
+use crate::AppState;
+
+mod list_versions_payloads {
+    use serde::Serialize;
+
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct VersionEntry {
+        pub key: String,
+        pub version_id: String,
+        pub is_latest: bool,
+        pub last_modified: String,
+        #[serde(rename = "ETag")]
+        pub etag: String,
+        pub size: i64,
+        pub storage_class: &'static str,
+    }
+
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct DeleteMarkerEntry {
+        pub key: String,
+        pub version_id: String,
+        pub is_latest: bool,
+        pub last_modified: String,
+    }
+
+    /// Versions and delete markers are interleaved, ordered by key and then newest first
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    pub enum Entry {
+        Version(VersionEntry),
+        DeleteMarker(DeleteMarkerEntry),
+    }
+
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct ListVersionsResult {
+        pub name: String,
+        pub prefix: String,
+        pub is_truncated: bool,
+        #[serde(rename = "$value")]
+        pub entries: Vec<Entry>,
+    }
+
+    impl ListVersionsResult {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("ListVersionsResult", self)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{DeleteMarkerEntry, Entry, ListVersionsResult, VersionEntry};
+
+        #[test]
+        fn test_encode_response() {
+            let payload = ListVersionsResult {
+                name: "bucket".to_string(),
+                prefix: String::new(),
+                is_truncated: false,
+                entries: vec![
+                    Entry::DeleteMarker(DeleteMarkerEntry {
+                        key: "sample.txt".to_string(),
+                        version_id: "v2".to_string(),
+                        is_latest: true,
+                        last_modified: "2009-10-12T17:50:30.000Z".to_string(),
+                    }),
+                    Entry::Version(VersionEntry {
+                        key: "sample.txt".to_string(),
+                        version_id: "v1".to_string(),
+                        is_latest: false,
+                        last_modified: "2009-10-10T17:50:30.000Z".to_string(),
+                        etag: "W/bafkqaaa".to_string(),
+                        size: 0,
+                        storage_class: "STANDARD",
+                    }),
+                ],
+            };
+
+            let expected = r#"<ListVersionsResult>
+    <Name>bucket</Name>
+    <Prefix/>
+    <IsTruncated>false</IsTruncated>
+    <DeleteMarker>
+        <Key>sample.txt</Key>
+        <VersionId>v2</VersionId>
+        <IsLatest>true</IsLatest>
+        <LastModified>2009-10-12T17:50:30.000Z</LastModified>
+    </DeleteMarker>
+    <Version>
+        <Key>sample.txt</Key>
+        <VersionId>v1</VersionId>
+        <IsLatest>false</IsLatest>
+        <LastModified>2009-10-10T17:50:30.000Z</LastModified>
+        <ETag>W/bafkqaaa</ETag>
+        <Size>0</Size>
+        <StorageClass>STANDARD</StorageClass>
+    </Version>
+</ListVersionsResult>"#;
+
+            assert_eq!(
+                payload.to_string().unwrap(),
+                expected.replace([' ', '\n'], "")
+            );
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct GetBucketParams {
     location: Option<String>,
+    versioning: Option<String>,
+    versions: Option<String>,
+    prefix: Option<String>,
+    lifecycle: Option<String>,
+}
+
+/// Implements `GetBucketVersioning`
+async fn get_bucket_versioning(
+    state: &AppState,
+    bucket: &str,
+) -> Result<Response<Body>, StatusCode> {
+    let versioning = match state.db.get_bucket_versioning(bucket).await {
+        Ok(versioning) => versioning,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve bucket versioning");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let configuration = VersioningConfiguration {
+        status: versioning.and_then(|status| status.parse().ok()),
+    };
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/xml")
+        .body(Body::from(configuration.to_string().unwrap_or_default()))
+        .unwrap_or_default())
+}
+
+/// Implements `GetBucketLifecycleConfiguration`
+async fn get_bucket_lifecycle(
+    state: &AppState,
+    bucket: &str,
+) -> Result<Response<Body>, StatusCode> {
+    let rules = match state.db.get_lifecycle_rules(bucket).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve lifecycle rules");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if rules.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let configuration = LifecycleConfiguration {
+        rule: rules.into_iter().map(Into::into).collect(),
+    };
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/xml")
+        .body(Body::from(configuration.to_string().unwrap_or_default()))
+        .unwrap_or_default())
+}
+
+/// Implements `ListObjectVersions`, without pagination
+async fn list_object_versions(
+    state: &AppState,
+    bucket: &str,
+    prefix: String,
+) -> Result<Response<Body>, StatusCode> {
+    let versions = match state.db.list_object_versions(bucket, &prefix).await {
+        Ok(versions) => versions,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to list object versions");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut entries = Vec::with_capacity(versions.len());
+    let mut previous_key = None;
+    for version in versions {
+        let is_latest = previous_key.as_ref() != Some(&version.key);
+        previous_key = Some(version.key.clone());
+        let last_modified = version
+            .created_at
+            .and_utc()
+            .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+            .to_string();
+
+        entries.push(if version.delete_marker {
+            list_versions_payloads::Entry::DeleteMarker(list_versions_payloads::DeleteMarkerEntry {
+                key: version.key,
+                version_id: version.version_id,
+                is_latest,
+                last_modified,
+            })
+        } else {
+            list_versions_payloads::Entry::Version(list_versions_payloads::VersionEntry {
+                etag: super::etag_value(
+                    state.config.etag_mode,
+                    &version.cid,
+                    version.etag.as_deref(),
+                    version.private,
+                ),
+                key: version.key,
+                version_id: version.version_id,
+                is_latest,
+                last_modified,
+                size: version.size,
+                storage_class: "STANDARD",
+            })
+        });
+    }
+
+    let response = list_versions_payloads::ListVersionsResult {
+        name: bucket.to_string(),
+        prefix,
+        is_truncated: false,
+        entries,
+    };
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/xml")
+        .body(Body::from(response.to_string().unwrap_or_default()))
+        .unwrap_or_default())
 }
 
 #[axum::debug_handler]
-/// Implements `GetBucket` and `GetBucketLocation` depending on query parameters
+/// Implements `GetBucket`, `GetBucketLocation`, `GetBucketVersioning`, `GetBucketLifecycleConfiguration`
+/// and `ListObjectVersions` depending on query parameters
 /// Always return OK as buckets can be created on upload
 pub async fn get_bucket(
+    State(state): State<AppState>,
     Path(bucket): Path<String>,
     Query(params): Query<GetBucketParams>,
 ) -> Result<Response<Body>, StatusCode> {
+    if params.versioning.is_some() {
+        return get_bucket_versioning(&state, &bucket).await;
+    }
+
+    if params.lifecycle.is_some() {
+        return get_bucket_lifecycle(&state, &bucket).await;
+    }
+
+    if params.versions.is_some() {
+        return list_object_versions(&state, &bucket, params.prefix.unwrap_or_default()).await;
+    }
+
     if params.location.is_some() {
         return Ok(Response::builder()
             .status(StatusCode::OK)
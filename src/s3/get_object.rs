@@ -1,33 +1,278 @@
 use std::str::FromStr;
 
 use axum::body::Body;
-use axum::extract::{Path, State};
-use axum::http::{StatusCode, header};
+use axum::extract::{Extension, Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
 use axum::response::Response;
 
 use axum_client_ip::ClientIp;
 
 use http::Uri;
 use http::uri::PathAndQuery;
+use itertools::Itertools;
+use percent_encoding::{AsciiSet, percent_encode};
+use serde::Deserialize;
 
-use crate::cli::OperationMode;
+use super::authorization::Authenticated;
+use crate::cli::{GatewayStyle, OperationMode};
+use crate::encryption::Encryption;
 use crate::{AppState, database};
 
-/// Return a 307 Temporary Redirect of the content to the `config.public_gateway` address
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetObjectParams {
+    version_id: Option<String>,
+    tagging: Option<String>,
+    retention: Option<String>,
+    #[serde(rename = "legal-hold")]
+    legal_hold: Option<String>,
+    attributes: Option<String>,
+    #[serde(flatten)]
+    overrides: ResponseOverrides,
+}
+
+/// `response-*` query parameters overriding headers of the returned object, usually sent on presigned URLs
+///
+/// Ref <https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html#API_GetObject_RequestSyntax>
+#[derive(Deserialize, Default)]
+struct ResponseOverrides {
+    #[serde(rename = "response-cache-control")]
+    cache_control: Option<String>,
+    #[serde(rename = "response-content-disposition")]
+    content_disposition: Option<String>,
+    #[serde(rename = "response-content-encoding")]
+    content_encoding: Option<String>,
+    #[serde(rename = "response-content-language")]
+    content_language: Option<String>,
+    #[serde(rename = "response-content-type")]
+    content_type: Option<String>,
+    #[serde(rename = "response-expires")]
+    expires: Option<String>,
+}
+
+impl ResponseOverrides {
+    /// S3 only accepts overrides on signed requests, as they could serve any object as HTML from this origin
+    ///
+    /// Ref <https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html#API_GetObject_RequestSyntax>
+    fn check(&self, authenticated: bool) -> Result<(), StatusCode> {
+        let requested = [
+            &self.cache_control,
+            &self.content_disposition,
+            &self.content_encoding,
+            &self.content_language,
+            &self.content_type,
+            &self.expires,
+        ]
+        .iter()
+        .any(|value| value.is_some());
+        if requested && !authenticated {
+            tracing::warn!("Refusing response header overrides on an anonymous request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(())
+    }
+
+    /// Replaces the headers of the response with the requested values, ignoring invalid header values
+    fn apply(&self, headers: &mut HeaderMap) {
+        let overrides = [
+            (header::CACHE_CONTROL, &self.cache_control),
+            (header::CONTENT_DISPOSITION, &self.content_disposition),
+            (header::CONTENT_ENCODING, &self.content_encoding),
+            (header::CONTENT_LANGUAGE, &self.content_language),
+            (header::CONTENT_TYPE, &self.content_type),
+            (header::EXPIRES, &self.expires),
+        ];
+        for (name, value) in overrides {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    /// Translates `response-content-disposition` to the `filename` and `download` parameters of IPFS gateways,
+    /// using the default `filename` when none is requested. Other overrides are not supported by gateways.
+    ///
+    /// Ref <https://specs.ipfs.tech/http-gateways/path-gateway/#filename-request-query-parameter>
+    fn gateway_query(&self, filename: Option<&str>) -> Option<String> {
+        let disposition = self.content_disposition.as_deref().unwrap_or_default();
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        if let Some(filename) = disposition_filename(disposition).or(filename.map(String::from)) {
+            query.append_pair("filename", &filename);
+        }
+        if disposition
+            .split(';')
+            .next()
+            .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("attachment"))
+        {
+            query.append_pair("download", "true");
+        }
+        Some(query.finish()).filter(|query| !query.is_empty())
+    }
+}
+
+/// Filename of a `Content-Disposition` value, preferring the RFC 5987 encoded `filename*` parameter
+fn disposition_filename(disposition: &str) -> Option<String> {
+    let params = disposition
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+        .collect::<Vec<_>>();
+
+    let encoded = params
+        .iter()
+        .find(|(name, _)| name == "filename*")
+        .and_then(|(_, value)| value.split_once("''"))
+        .and_then(|(_, value)| {
+            percent_encoding::percent_decode_str(value)
+                .decode_utf8()
+                .ok()
+        })
+        .map(String::from);
+    encoded.or_else(|| {
+        params
+            .iter()
+            .find(|(name, _)| name == "filename")
+            .map(|(_, value)| value.trim_matches('"').to_string())
+    })
+}
+
+/// Implements `GetObjectTagging`
+async fn get_object_tagging(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, None).await?;
+    super::check_tagged_version(&metadata, version_id)?;
+
+    let tags = match state.db.get_object_tags(bucket, key).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve object tags");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let tagging = super::put_object::tagging_payloads::Tagging::from(tags);
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(Body::from(tagging.to_string().unwrap_or_default()))
+            .unwrap_or_default(),
+    )
+}
+
+/// Characters allowed on path segments of gateway URLs
+const PATH_SEGMENT_ENCODE_SET: AsciiSet = percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Content path on a gateway: `/{namespace}/{root}{path}`, such as `/ipfs/{cid}` or `/ipns/{name}/key`
+struct GatewayPath<'a> {
+    namespace: &'static str,
+    root: &'a str,
+    path: &'a str,
+}
+
+/// Address of the content on the gateway, in path or subdomain style.
+///
+/// Subdomain gateways require a DNS label as root: CIDs are converted to base32 `CIDv1` and
+/// `DNSLink` names are inlined, replacing `-` with `--` and `.` with `-`.
+///
+/// Ref <https://specs.ipfs.tech/http-gateways/subdomain-gateway/>
+fn gateway_location(
+    gateway: &Uri,
+    style: GatewayStyle,
+    content: &GatewayPath<'_>,
+    query: Option<&str>,
+) -> Option<Uri> {
+    let GatewayPath {
+        namespace,
+        root,
+        path,
+    } = content;
+    let query = query.map_or_else(String::new, |query| format!("?{query}"));
+    let label = match (style, *namespace) {
+        (GatewayStyle::Path, _) => None,
+        (GatewayStyle::Subdomain, "ipfs") => crate::ipfs::cid_v1(root),
+        (GatewayStyle::Subdomain, _) => Some(root.replace('-', "--").replace('.', "-")),
+    };
+
+    let mut parts = gateway.clone().into_parts();
+    match label {
+        Some(label) => {
+            let authority = parts.authority?;
+            parts.authority = format!("{label}.{namespace}.{authority}").parse().ok();
+            let path = if path.is_empty() { "/" } else { path };
+            parts.path_and_query = PathAndQuery::from_str(&format!("{path}{query}")).ok();
+        }
+        None => {
+            parts.path_and_query =
+                PathAndQuery::from_str(&format!("/{namespace}/{root}{path}{query}")).ok();
+        }
+    }
+    Uri::from_parts(parts).ok()
+}
+
+/// Return a 307 Temporary Redirect of the content to the fastest healthy `config.gateway` address
 /// instead of returning the content directly
+///
+/// With `gateway_ipns_redirect`, the current version of objects is addressed by its path under the IPNS name
+/// of its bucket once the latest bucket root is published, and by CID otherwise, suggesting the key basename
+/// as `filename`.
 fn redirect(
     state: &AppState,
     metadata: &database::MetadataResponse,
+    current: bool,
+    overrides: &ResponseOverrides,
 ) -> Result<Response, http::Error> {
     let ipfs_path = format!("/ipfs/{}", &metadata.cid);
 
-    let gateway = {
-        let mut parts = state.config.gateway.clone().into_parts();
-        parts.path_and_query = PathAndQuery::from_str(&ipfs_path).ok();
-        Uri::from_parts(parts)
-    }
-    .unwrap_or_default()
-    .to_string();
+    let published = (current && state.config.gateway_ipns_redirect.unwrap_or_default())
+        .then(|| state.ipns.published_name(&metadata.bucket))
+        .flatten();
+
+    let path;
+    let (content, query) = if let Some(name) = published.as_deref() {
+        path = metadata
+            .key
+            .split('/')
+            .map(|segment| {
+                format!(
+                    "/{}",
+                    percent_encode(segment.as_bytes(), &PATH_SEGMENT_ENCODE_SET)
+                )
+            })
+            .join("");
+        let content = GatewayPath {
+            namespace: "ipns",
+            root: name,
+            path: &path,
+        };
+        (content, overrides.gateway_query(None))
+    } else {
+        let content = GatewayPath {
+            namespace: "ipfs",
+            root: &metadata.cid,
+            path: "",
+        };
+        (
+            content,
+            overrides.gateway_query(metadata.key.rsplit('/').next()),
+        )
+    };
+    let location = gateway_location(
+        state.gateways.select(),
+        state.config.gateway_style,
+        &content,
+        query.as_deref(),
+    );
+    let gateway = location.unwrap_or_default().to_string();
 
     tracing::debug!(
         bucket = metadata.bucket,
@@ -36,7 +281,7 @@ fn redirect(
         "Redirecting to gateway"
     );
 
-    Response::builder()
+    super::version_header(Response::builder(), metadata.version_id.as_deref())
         .status(StatusCode::TEMPORARY_REDIRECT)
         .header(header::LOCATION, &gateway)
         .header("x-ipfs-path", &ipfs_path)
@@ -45,14 +290,31 @@ fn redirect(
         .body(Body::empty())
 }
 
-fn proxy(state: &AppState, metadata: &database::MetadataResponse) -> Result<Response, http::Error> {
-    let ipfs_path = format!("/ipfs/{}", &metadata.cid);
+fn proxy(
+    state: &AppState,
+    metadata: &database::MetadataResponse,
+    encryption: Option<Encryption>,
+    headers: &HeaderMap,
+    overrides: &ResponseOverrides,
+) -> Result<Response, http::Error> {
     let stream = state.ipfs_client.get_content(&metadata.cid);
-    Response::builder()
+    let mut response = super::ipfs_headers(Response::builder(), &metadata.cid, metadata.private);
+    response = super::version_header(response, metadata.version_id.as_deref());
+    response = super::object_lock::response_headers(response, metadata);
+    response = super::checksum::response_headers(response, metadata, headers);
+    let body = match encryption {
+        Some(encryption) => {
+            response = encryption.response_headers(response);
+            Body::from_stream(encryption.decrypt(stream))
+        }
+        None => Body::from_stream(stream),
+    };
+    response
         .status(StatusCode::OK)
-        .header("x-ipfs-path", &ipfs_path)
-        .header("x-ipfs-roots", &metadata.cid)
-        .header(header::CACHE_CONTROL, "public, max-age=29030400, immutable")
+        .header(
+            header::CACHE_CONTROL,
+            super::cache_control(metadata.private),
+        )
         .header(
             header::LAST_MODIFIED,
             metadata
@@ -63,36 +325,78 @@ fn proxy(state: &AppState, metadata: &database::MetadataResponse) -> Result<Resp
         )
         .header("priority", "i")
         .header("x-robots-tag", "noindex, nofollow")
-        .header(header::ETAG, super::etag_value(&metadata.cid))
+        .header(
+            header::ETAG,
+            super::etag_value(
+                state.config.etag_mode,
+                &metadata.cid,
+                metadata.etag.as_deref(),
+                metadata.private,
+            ),
+        )
         .header(header::CONTENT_TYPE, &metadata.content_type)
-        .body(axum::body::Body::from_stream(stream))
+        .body(body)
+        .map(|mut response| {
+            overrides.apply(response.headers_mut());
+            response
+        })
 }
 
-/// Provides `GetObject` endpoint
-/// 
+/// Provides `GetObject`, `GetObjectTagging`, `GetObjectRetention`, `GetObjectLegalHold` and `GetObjectAttributes` endpoints
+///
 /// It also provides a 'non-standard' response mode with a `307 Redirect` depending on the [`crate::cli::RunConfig`] parameters
+/// Encrypted objects are always proxied, as gateways can't decrypt them, as well as private objects
 #[axum::debug_handler]
 pub async fn get_object(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<GetObjectParams>,
     ClientIp(client_ip): ClientIp,
+    authenticated: Option<Extension<Authenticated>>,
+    headers: HeaderMap,
 ) -> Result<Response, StatusCode> {
+    if params.tagging.is_some() {
+        return get_object_tagging(&state, &bucket, &key, params.version_id.as_deref()).await;
+    }
+
+    let version = params.version_id.as_deref();
+    if params.retention.is_some() {
+        return super::object_lock::get_object_retention(&state, &bucket, &key, version).await;
+    }
+
+    if params.legal_hold.is_some() {
+        return super::object_lock::get_object_legal_hold(&state, &bucket, &key, version).await;
+    }
+
+    if params.attributes.is_some() {
+        return super::object_attributes::get_object_attributes(
+            &state, &bucket, &key, version, &headers,
+        )
+        .await;
+    }
+
+    params.overrides.check(authenticated.is_some())?;
+
     // Retrieve object metadata from SQLite
-    let metadata = match state.db.get_object_metadata(&bucket, &key).await {
-        Ok(Some(metadata)) => metadata,
-        Ok(None) => {
-            tracing::warn!(bucket, key, "Object not found");
-            return Err(StatusCode::NOT_FOUND);
-        }
+    let metadata =
+        super::object_metadata(&state, &bucket, &key, params.version_id.as_deref()).await?;
+
+    let encryption = match Encryption::stored(&state.config.encryption, &metadata, &headers) {
+        Ok(encryption) => encryption,
         Err(e) => {
-            tracing::error!(error = %e, "Failed to retrieve object metadata");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            tracing::warn!(error = %e, bucket, key, "Could not decrypt object");
+            return Err(e.status_code());
         }
     };
 
     let response = match state.config.mode {
-        OperationMode::Redirect => redirect(&state, &metadata),
-        OperationMode::Proxy => proxy(&state, &metadata),
+        _ if encryption.is_some() || metadata.private => {
+            proxy(&state, &metadata, encryption, &headers, &params.overrides)
+        }
+        OperationMode::Redirect => {
+            redirect(&state, &metadata, version.is_none(), &params.overrides)
+        }
+        OperationMode::Proxy => proxy(&state, &metadata, encryption, &headers, &params.overrides),
         OperationMode::Auto => {
             if iprfc::RFC6890.contains(&client_ip)
                 || state
@@ -102,12 +406,123 @@ pub async fn get_object(
                     .iter()
                     .any(|cidr| cidr.contains(&client_ip))
             {
-                proxy(&state, &metadata)
+                proxy(&state, &metadata, encryption, &headers, &params.overrides)
             } else {
-                redirect(&state, &metadata)
+                redirect(&state, &metadata, version.is_none(), &params.overrides)
             }
         }
     };
 
     Ok(response.unwrap_or_default())
 }
+
+#[cfg(test)]
+mod test {
+    use axum::http::StatusCode;
+
+    use super::{GatewayPath, ResponseOverrides, disposition_filename, gateway_location};
+    use crate::cli::GatewayStyle;
+
+    #[test]
+    fn test_disposition_filename() {
+        assert_eq!(
+            disposition_filename("attachment; filename=\"report 2024.pdf\"").as_deref(),
+            Some("report 2024.pdf")
+        );
+        assert_eq!(
+            disposition_filename("inline;filename=photo.png").as_deref(),
+            Some("photo.png")
+        );
+        assert_eq!(
+            disposition_filename(
+                "attachment; filename=\"fallback.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9.txt"
+            )
+            .as_deref(),
+            Some("résumé.txt")
+        );
+        assert_eq!(disposition_filename("attachment"), None);
+    }
+
+    #[test]
+    fn test_gateway_query() {
+        let overrides = |disposition: &str| ResponseOverrides {
+            content_disposition: Some(disposition.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            overrides("attachment; filename=\"report 2024.pdf\"")
+                .gateway_query(Some("report.pdf"))
+                .as_deref(),
+            Some("filename=report+2024.pdf&download=true")
+        );
+        assert_eq!(
+            overrides("inline; filename=photo.png")
+                .gateway_query(None)
+                .as_deref(),
+            Some("filename=photo.png")
+        );
+        assert_eq!(
+            overrides("attachment")
+                .gateway_query(Some("report.pdf"))
+                .as_deref(),
+            Some("filename=report.pdf&download=true")
+        );
+        assert_eq!(
+            ResponseOverrides::default()
+                .gateway_query(Some("report.pdf"))
+                .as_deref(),
+            Some("filename=report.pdf")
+        );
+        assert_eq!(overrides("inline").gateway_query(None), None);
+        assert_eq!(ResponseOverrides::default().gateway_query(None), None);
+    }
+
+    #[test]
+    fn test_overrides_require_signed_requests() {
+        let overrides = ResponseOverrides {
+            content_type: Some("text/html".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(overrides.check(false), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(overrides.check(true), Ok(()));
+        assert_eq!(ResponseOverrides::default().check(false), Ok(()));
+    }
+
+    #[test]
+    fn test_gateway_location() {
+        let gateway = "https://dweb.link".parse().unwrap();
+        let cid = GatewayPath {
+            namespace: "ipfs",
+            root: "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn",
+            path: "",
+        };
+        let mfs = GatewayPath {
+            namespace: "ipns",
+            root: "files.example.com",
+            path: "/bucket/report%202024.pdf",
+        };
+
+        let location = |style, content, query| {
+            gateway_location(&gateway, style, content, query)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            location(GatewayStyle::Path, &cid, Some("filename=a.txt")),
+            "https://dweb.link/ipfs/QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn?filename=a.txt"
+        );
+        assert_eq!(
+            location(GatewayStyle::Subdomain, &cid, Some("filename=a.txt")),
+            "https://bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354.ipfs.dweb.link/?filename=a.txt"
+        );
+        assert_eq!(
+            location(GatewayStyle::Path, &mfs, None),
+            "https://dweb.link/ipns/files.example.com/bucket/report%202024.pdf"
+        );
+        assert_eq!(
+            location(GatewayStyle::Subdomain, &mfs, None),
+            "https://files-example-com.ipns.dweb.link/bucket/report%202024.pdf"
+        );
+    }
+}
@@ -1,43 +1,112 @@
 use axum::body::Body;
-use axum::extract::{Path, State};
-use axum::http::{StatusCode, header};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::Response;
 
+use http::response::Builder;
+use serde::Deserialize;
+
 use crate::AppState;
+use crate::database::MetadataResponse;
+use crate::encryption::Encryption;
+
+use super::object_attributes::{ObjectPart, STORAGE_CLASS};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeadObjectParams {
+    version_id: Option<String>,
+    part_number: Option<i64>,
+}
+
+/// Size of the requested part and the number of parts of the object.
+/// Objects not stored from a multipart upload only have a single part.
+fn part_size(metadata: &MetadataResponse, part_number: i64) -> Option<(i64, usize)> {
+    match ObjectPart::stored(metadata) {
+        Some(parts) => parts
+            .iter()
+            .find(|part| part.number == part_number)
+            .map(|part| (part.size, parts.len())),
+        None => (part_number == 1).then_some((metadata.size, 1)),
+    }
+}
+
+/// Adds the version and codec of the CID, hidden for private objects
+fn cid_headers(builder: Builder, metadata: &MetadataResponse) -> Builder {
+    match crate::ipfs::cid_format(&metadata.cid) {
+        Some((version, codec)) if !metadata.private => builder
+            .header("x-ipfs-cid-version", version)
+            .header("x-ipfs-cid-codec", codec),
+        _ => builder,
+    }
+}
 
 #[axum::debug_handler]
-/// Implements `HeadObject` operation
+/// Implements `HeadObject` operation, with the size of a single part when requested with `partNumber`
 pub async fn head_object_metadata(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<HeadObjectParams>,
+    headers: HeaderMap,
 ) -> Response<Body> {
     // Verify object exists in our system
-    let metadata = match state.db.get_object_metadata(&bucket, &key).await {
-        Ok(Some(metadata)) => {
-            metadata
-        }
-        Ok(None) => {
-            tracing::warn!(bucket, key, "Object not found");
+    let metadata =
+        match super::object_metadata(&state, &bucket, &key, params.version_id.as_deref()).await {
+            Ok(metadata) => metadata,
+            Err(status) => {
+                return Response::builder()
+                    .status(status)
+                    .body(Body::empty())
+                    .unwrap_or_default();
+            }
+        };
+
+    let mut response = super::version_header(Response::builder(), metadata.version_id.as_deref());
+    let mut size = metadata.size;
+    if let Some(part_number) = params.part_number {
+        let Some((part_size, parts_count)) = part_size(&metadata, part_number) else {
+            tracing::warn!(bucket, key, part_number, "Part not found");
             return Response::builder()
-                .status(StatusCode::NOT_FOUND)
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
                 .body(Body::empty())
                 .unwrap_or_default();
-        }
+        };
+        size = part_size;
+        response = response.header("x-amz-mp-parts-count", parts_count);
+    }
+    response = cid_headers(response, &metadata);
+    response = super::object_lock::response_headers(response, &metadata);
+    response = super::checksum::response_headers(response, &metadata, &headers);
+    match Encryption::stored(&state.config.encryption, &metadata, &headers) {
+        Ok(Some(encryption)) => response = encryption.response_headers(response),
+        Ok(None) => {}
         Err(e) => {
-            tracing::error!(error = %e, "Failed to verify object existence");
+            tracing::warn!(error = %e, bucket, key, "Could not validate object encryption");
             return Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .status(e.status_code())
                 .body(Body::empty())
                 .unwrap_or_default();
         }
-    };
+    }
 
-    Response::builder()
+    super::ipfs_headers(response, &metadata.cid, metadata.private)
         .status(StatusCode::OK)
-        .header(header::CONTENT_LENGTH, metadata.size)
+        .header(header::CONTENT_LENGTH, size)
         .header(header::CONTENT_TYPE, metadata.content_type)
-        .header(header::CACHE_CONTROL, "public, max-age=29030400, immutable")
-        .header(header::ETAG, super::etag_value(&metadata.cid))
+        .header(
+            header::CACHE_CONTROL,
+            super::cache_control(metadata.private),
+        )
+        .header("x-amz-storage-class", STORAGE_CLASS)
+        .header(
+            header::ETAG,
+            super::etag_value(
+                state.config.etag_mode,
+                &metadata.cid,
+                metadata.etag.as_deref(),
+                metadata.private,
+            ),
+        )
         .header(
             header::LAST_MODIFIED,
             metadata
@@ -46,8 +115,6 @@ pub async fn head_object_metadata(
                 .format("%a, %d %b %Y %H:%M:%S GMT")
                 .to_string(),
         )
-        .header("x-ipfs-path", format!("/ipfs/{}", metadata.cid))
-        .header("x-ipfs-roots", &metadata.cid)
         .body(Body::empty())
         .unwrap_or_default()
 }
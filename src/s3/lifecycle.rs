@@ -0,0 +1,134 @@
+//! Background enforcement of bucket lifecycle rules
+//!
+//! Expired objects are deleted through the same path as `DeleteObject`, so content is unpinned
+//! once orphan and empty folders are trimmed from MFS.
+//!
+//! Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-lifecycle-mgmt.html>
+
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use chrono::{NaiveDateTime, TimeDelta};
+use futures::TryFutureExt;
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+
+use super::delete_object::{DeleteObjectParams, delete_object};
+use crate::AppState;
+use crate::database::LifecycleRule;
+
+/// Applies the lifecycle rules of every bucket on each `lifecycle_interval`
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(state.config.lifecycle_interval.into());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        apply_rules(&state)
+            .instrument(tracing::info_span!("lifecycle"))
+            .await;
+    }
+}
+
+async fn apply_rules(state: &AppState) {
+    let rules = match state.db.list_enabled_lifecycle_rules().await {
+        Ok(rules) => rules,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve lifecycle rules");
+            return;
+        }
+    };
+
+    for rule in rules {
+        let _write = state.bucket_locks.write(&rule.bucket).await;
+        apply_rule(state, &rule)
+            .instrument(tracing::debug_span!(
+                "lifecycle rule",
+                bucket = rule.bucket,
+                rule = rule.rule_id
+            ))
+            .await;
+    }
+}
+
+/// Objects modified before `days` ago expire. `None` when the date is out of range
+fn expires_before(now: NaiveDateTime, days: i64) -> Option<NaiveDateTime> {
+    TimeDelta::try_days(days).and_then(|days| now.checked_sub_signed(days))
+}
+
+async fn apply_rule(state: &AppState, rule: &LifecycleRule) {
+    let now = chrono::Utc::now().naive_utc();
+
+    if let Some(days) = rule.expiration_days {
+        let Some(before) = expires_before(now, days) else {
+            tracing::error!(days, "Invalid expiration days, skipping rule");
+            return;
+        };
+        match state
+            .db
+            .find_objects_modified_before(&rule.bucket, &rule.prefix, before)
+            .await
+        {
+            Ok(keys) => {
+                for key in keys {
+                    expire(state, &rule.bucket, key, None).await;
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "Failed to find expired objects"),
+        }
+    }
+
+    if let Some(days) = rule.noncurrent_days {
+        let Some(before) = expires_before(now, days) else {
+            tracing::error!(days, "Invalid noncurrent days, skipping rule");
+            return;
+        };
+        match state
+            .db
+            .find_noncurrent_versions_before(&rule.bucket, &rule.prefix, before)
+            .await
+        {
+            Ok(versions) => {
+                for (key, version_id) in versions {
+                    expire(state, &rule.bucket, key, Some(version_id)).await;
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "Failed to find noncurrent versions"),
+        }
+    }
+}
+
+/// Deletes an expired object, or permanently deletes a noncurrent version
+async fn expire(state: &AppState, bucket: &str, key: String, version_id: Option<String>) {
+    let span = tracing::info_span!("expire", key, version_id);
+    let params = DeleteObjectParams {
+        upload_id: None,
+        version_id,
+        tagging: None,
+    };
+    let _ = delete_object(
+        State(state.clone()),
+        Path((bucket.to_string(), key)),
+        Query(params),
+        HeaderMap::new(),
+    )
+    .inspect_ok(|_| tracing::info!("Expired object"))
+    .inspect_err(|status| tracing::error!(%status, "Failed to expire object"))
+    .instrument(span)
+    .await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::expires_before;
+
+    #[test]
+    fn test_expires_before_out_of_range() {
+        let now = chrono::Utc::now().naive_utc();
+        assert_eq!(
+            expires_before(now, 1),
+            Some(now - chrono::TimeDelta::days(1))
+        );
+        assert_eq!(expires_before(now, i64::MAX), None);
+        assert_eq!(expires_before(now, 36500 * 10_000), None);
+    }
+}
@@ -1,24 +1,130 @@
-use axum::http::{StatusCode, header};
+use std::collections::HashMap;
+use std::sync::Arc;
 
+use axum::extract::{Path, Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::Response;
 use axum::routing::{get, put};
 
 use axum_extra::middleware::option_layer;
+use bytes::Bytes;
+use dashmap::DashMap;
 use http::Method;
+use rand::distr::{Alphanumeric, SampleString};
+use sha2::{Digest, Sha256};
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
 use tower_http::cors::{self, CorsLayer};
 use typed_path::UnixPathBuf;
 
+use crate::cli::{EtagMode, RunConfig};
 use crate::{AppState, database};
+use put_bucket::versioning_payloads::VersioningStatus;
 
 pub mod authorization;
+mod checksum;
+mod delete_bucket;
 mod delete_object;
 mod get_bucket;
 mod get_object;
 mod head_object;
+pub mod lifecycle;
+mod object_attributes;
+mod object_lock;
 mod post_bucket;
 mod post_object;
+mod put_bucket;
 mod put_object;
+pub mod virtual_host;
 
-fn normalized_path(
+/// Locks of the buckets, shared by writes and held exclusively while restoring a snapshot,
+/// as it replaces the bucket directory and every current object
+#[derive(Default)]
+pub struct BucketLocks(DashMap<String, Arc<RwLock<()>>>);
+
+impl BucketLocks {
+    fn lock(&self, bucket: &str) -> Arc<RwLock<()>> {
+        self.0.entry(bucket.to_string()).or_default().clone()
+    }
+
+    /// Waits for any restore of the bucket to finish
+    pub async fn write(&self, bucket: &str) -> OwnedRwLockReadGuard<()> {
+        self.lock(bucket).read_owned().await
+    }
+
+    /// Waits for writes in progress to finish, holding new ones until the guard is dropped
+    pub async fn restore(&self, bucket: &str) -> OwnedRwLockWriteGuard<()> {
+        self.lock(bucket).write_owned().await
+    }
+}
+
+/// Holds requests modifying a bucket while a snapshot is restored
+async fn hold_writes(
+    State(state): State<AppState>,
+    Path(params): Path<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+    let Some(bucket) = params.get("bucket") else {
+        return next.run(request).await;
+    };
+
+    let _write = state.bucket_locks.write(bucket).await;
+    next.run(request).await
+}
+
+/// Whether the bucket is in `private_buckets` or has private objects, which its MFS directory links
+pub(crate) async fn is_private_bucket(
+    db: &database::Database,
+    config: &RunConfig,
+    bucket: &str,
+) -> Result<bool, database::DatabaseError> {
+    if config.private_buckets.iter().any(|b| b == bucket) {
+        return Ok(true);
+    }
+
+    Ok(db.count_private_objects(bucket).await? > 0)
+}
+
+/// In-memory staging of a `MultiPartUpload`
+pub struct MultipartUpload {
+    pub parts: DashMap<i8, Bytes>,
+    /// Headers of `CreateMultipartUpload`, applied to the object on completion
+    pub headers: HeaderMap,
+}
+
+/// Content type guessed from the key extension, defaulting to application/octet-stream
+pub(crate) fn guessed_content_type(key: &str) -> String {
+    mime_guess::from_path(key)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+/// MFS directory holding every bucket
+pub(crate) fn mfs_root(start: &str) -> Result<UnixPathBuf, typed_path::CheckedPathError> {
+    let mut root = UnixPathBuf::from("/");
+    root.push_checked(start)?;
+    Ok(root.normalize())
+}
+
+/// MFS directory of a bucket
+pub(crate) fn bucket_path(
+    start: &str,
+    bucket: &str,
+) -> Result<UnixPathBuf, typed_path::CheckedPathError> {
+    let mut root = mfs_root(start)?;
+    root.push_checked(bucket)?;
+    Ok(root)
+}
+
+pub(crate) fn normalized_path(
     start: &str,
     bucket: &str,
     key: &str,
@@ -30,10 +136,122 @@ fn normalized_path(
     Ok(root.normalize())
 }
 
-/// Returns a "weak" etag value
+/// Adds `x-ipfs-path` and `x-ipfs-roots` headers, used by IPFS-aware clients (eg: IPFS Companion)
+/// to fetch the content from the IPFS network.
+/// Private objects don't advertise their CID.
+fn ipfs_headers(
+    builder: http::response::Builder,
+    cid: &str,
+    private: bool,
+) -> http::response::Builder {
+    if private {
+        builder
+    } else {
+        builder
+            .header("x-ipfs-path", format!("/ipfs/{cid}"))
+            .header("x-ipfs-roots", cid)
+    }
+}
+
+/// Content is immutable, but private objects must not be stored on shared caches
+fn cache_control(private: bool) -> &'static str {
+    if private {
+        "private, max-age=29030400, immutable"
+    } else {
+        "public, max-age=29030400, immutable"
+    }
+}
+
+/// Returns the S3 "strong" etag value, with the MD5 of the content,
+/// or a "weak" etag value with the CID when running with `etag-mode: cid` or for objects stored without MD5
+/// Private CIDs are never advertised, so private objects use their MD5, or a hash of the CID when unknown
 /// <https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Headers/ETag>
-fn etag_value(cid: &str) -> String {
-    format!("W/{cid}")
+fn etag_value(mode: EtagMode, cid: &str, etag: Option<&str>, private: bool) -> String {
+    match (mode, etag) {
+        (EtagMode::Md5, Some(etag)) => format!("\"{etag}\""),
+        (_, Some(etag)) if private => format!("\"{etag}\""),
+        _ if private => format!("W/{}", hex::encode(Sha256::digest(cid.as_bytes()))),
+        _ => format!("W/{cid}"),
+    }
+}
+
+/// Adds `x-amz-version-id` to objects stored while versioning was enabled
+fn version_header(
+    builder: http::response::Builder,
+    version_id: Option<&str>,
+) -> http::response::Builder {
+    match version_id {
+        Some(version_id) => builder.header("x-amz-version-id", version_id),
+        None => builder,
+    }
+}
+
+/// Refuses tagging other versions than the current object, as only its tags are kept
+fn check_tagged_version(
+    metadata: &database::MetadataResponse,
+    version_id: Option<&str>,
+) -> Result<(), StatusCode> {
+    let current = metadata.version_id.as_deref().unwrap_or("null");
+    match version_id {
+        Some(version_id) if version_id != current => {
+            tracing::warn!(version_id, "Tagging non-current versions is not supported");
+            Err(StatusCode::NOT_IMPLEMENTED)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Version id of a new object or delete marker, depending on the bucket versioning state.
+/// Suspended buckets reuse the `null` version id, replacing the previous `null` version.
+///
+/// Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/Versioning.html>
+async fn new_version_id(state: &AppState, bucket: &str) -> Result<Option<String>, StatusCode> {
+    let versioning = match state.db.get_bucket_versioning(bucket).await {
+        Ok(versioning) => versioning,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve bucket versioning");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(match versioning.as_deref().map(str::parse) {
+        Some(Ok(VersioningStatus::Enabled)) => {
+            Some(Alphanumeric.sample_string(&mut rand::rng(), 32))
+        }
+        Some(Ok(VersioningStatus::Suspended)) => Some("null".to_string()),
+        Some(Err(())) | None => None,
+    })
+}
+
+/// Retrieve the current object metadata, or a specific version of it when requested
+async fn object_metadata(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<database::MetadataResponse, StatusCode> {
+    let metadata = match version_id {
+        Some(version_id) => match state.db.get_object_version(bucket, key, version_id).await {
+            Ok(Some(version)) if version.delete_marker => {
+                tracing::warn!(bucket, key, version_id, "Version is a delete marker");
+                return Err(StatusCode::METHOD_NOT_ALLOWED);
+            }
+            result => result.map(|version| version.map(Into::into)),
+        },
+        None => state.db.get_object_metadata(bucket, key).await,
+    };
+
+    match metadata {
+        Ok(Some(metadata)) => Ok(metadata),
+        Ok(None) => {
+            tracing::warn!(bucket, key, version_id, "Object not found");
+            Err(StatusCode::NOT_FOUND)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve object metadata");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
 
 async fn unpin_if_orphan(
@@ -54,26 +272,55 @@ async fn unpin_if_orphan(
         "Checking for remaining CID reference before unpin"
     );
 
-    if remaining == 0
-        && let Err(e) = state.ipfs_client.unpin(metadata).await
-    {
-        tracing::error!(error = %e, "Failed to unpin content from IPFS");
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    // Removal already checked for Object Lock, bypassing `GOVERNANCE` retention if requested
+    if remaining == 0 && object_lock::check_removable(metadata, true).is_err() {
+        tracing::warn!(cid = &metadata.cid, "Refusing to unpin locked content");
+        return Ok(());
+    }
+
+    // Private content is only kept by its MFS link
+    if remaining == 0 && !metadata.private {
+        let unpinned = match &state.cluster {
+            Some(cluster) => cluster
+                .unpin(&metadata.cid)
+                .await
+                .map_err(|e| e.to_string()),
+            None => state
+                .ipfs_client
+                .unpin(&metadata.cid)
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        if let Err(e) = unpinned {
+            tracing::error!(error = %e, "Failed to unpin content from IPFS");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if remaining == 0 {
+        crate::pinning::queue_unpin(&state, &metadata.cid).await;
     }
 
     Ok(())
 }
 
-pub fn routes(config: &crate::cli::RunConfig) -> axum::Router<AppState> {
+pub fn routes(state: &AppState) -> axum::Router<AppState> {
+    let config = &state.config;
     axum::Router::new()
         // S3-like proxy service endpoints
         .route(
             "/{bucket}",
-            get(get_bucket::get_bucket).post(post_bucket::modify_bucket),
+            get(get_bucket::get_bucket)
+                .put(put_bucket::put_bucket)
+                .post(post_bucket::modify_bucket)
+                .delete(delete_bucket::delete_bucket),
         )
         .route(
             "/{bucket}/",
-            get(get_bucket::get_bucket).post(post_bucket::modify_bucket),
+            get(get_bucket::get_bucket)
+                .put(put_bucket::put_bucket)
+                .post(post_bucket::modify_bucket)
+                .delete(delete_bucket::delete_bucket),
         )
         .route(
             "/{bucket}/{*key}",
@@ -83,6 +330,7 @@ pub fn routes(config: &crate::cli::RunConfig) -> axum::Router<AppState> {
                 .head(head_object::head_object_metadata)
                 .post(post_object::multipart_upload),
         )
+        .route_layer(middleware::from_fn_with_state(state.clone(), hold_writes))
         .layer(option_layer(
             config
                 .auth
@@ -104,6 +352,37 @@ pub fn routes(config: &crate::cli::RunConfig) -> axum::Router<AppState> {
                     header::CONTENT_RANGE,
                     header::HeaderName::from_static("x-ipfs-path"),
                     header::HeaderName::from_static("x-ipfs-roots"),
+                    header::HeaderName::from_static("x-amz-version-id"),
                 ]),
         )
 }
+
+#[cfg(test)]
+mod test {
+    use super::etag_value;
+    use crate::cli::EtagMode;
+
+    #[test]
+    fn test_private_etag_hides_cid() {
+        let cid = "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku";
+        let md5 = "5d41402abc4b2a76b9719d911017c592";
+
+        assert_eq!(
+            etag_value(EtagMode::Cid, cid, Some(md5), false),
+            format!("W/{cid}")
+        );
+        assert_eq!(
+            etag_value(EtagMode::Md5, cid, Some(md5), false),
+            format!("\"{md5}\"")
+        );
+        assert_eq!(
+            etag_value(EtagMode::Cid, cid, Some(md5), true),
+            format!("\"{md5}\"")
+        );
+        for mode in [EtagMode::Cid, EtagMode::Md5] {
+            let etag = etag_value(mode, cid, None, true);
+            assert!(etag.starts_with("W/"));
+            assert!(!etag.contains(cid));
+        }
+    }
+}
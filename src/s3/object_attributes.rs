@@ -0,0 +1,325 @@
+//! `GetObjectAttributes` and the parts of objects stored from multipart uploads
+//!
+//! Ref <https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObjectAttributes.html>
+
+use axum::body::Body;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::Response;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use super::checksum::{Checksum, ChecksumAlgorithm, ChecksumType};
+use crate::AppState;
+use crate::database::MetadataResponse;
+
+/// Objects are stored on the IPFS node, without storage tiers
+pub const STORAGE_CLASS: &str = "STANDARD";
+
+const ATTRIBUTES_HEADER: &str = "x-amz-object-attributes";
+const MAX_PARTS_HEADER: &str = "x-amz-max-parts";
+const PART_NUMBER_MARKER_HEADER: &str = "x-amz-part-number-marker";
+const DEFAULT_MAX_PARTS: usize = 1000;
+
+/// Part of an object stored from a multipart upload
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+pub struct ObjectPart {
+    pub number: i64,
+    pub size: i64,
+    /// Base64 encoded checksum of the part, for objects with a `COMPOSITE` checksum
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl ObjectPart {
+    /// Describes the parts of a multipart upload, ordered by part number
+    pub fn from_upload(parts: &[(i8, Bytes)], checksum: Option<&Checksum>) -> Vec<Self> {
+        let part_algorithm = checksum
+            .filter(|checksum| checksum.kind == ChecksumType::Composite)
+            .map(|checksum| checksum.algorithm);
+        parts
+            .iter()
+            .map(|(number, part)| ObjectPart {
+                number: i64::from(*number),
+                size: part.len() as i64,
+                checksum: part_algorithm.map(|algorithm| algorithm.encoded(part)),
+            })
+            .collect()
+    }
+
+    /// Parts of an object, `None` if it was not stored from a multipart upload
+    pub fn stored(metadata: &MetadataResponse) -> Option<Vec<Self>> {
+        serde_json::from_str(metadata.parts.as_deref()?)
+            .inspect_err(
+                |e| tracing::error!(error = %e, key = metadata.key, "Invalid stored parts"),
+            )
+            .ok()
+    }
+}
+
+pub(super) mod object_attributes_payloads {
+    use serde::Serialize;
+
+    use crate::s3::checksum::ChecksumAlgorithm;
+
+    /// Value of a checksum, on the element named after its algorithm
+    #[derive(Serialize, Default, Eq, PartialEq, Debug)]
+    pub struct ChecksumValue {
+        #[serde(rename = "ChecksumCRC32", skip_serializing_if = "Option::is_none")]
+        pub crc32: Option<String>,
+        #[serde(rename = "ChecksumCRC32C", skip_serializing_if = "Option::is_none")]
+        pub crc32c: Option<String>,
+        #[serde(rename = "ChecksumCRC64NVME", skip_serializing_if = "Option::is_none")]
+        pub crc64nvme: Option<String>,
+        #[serde(rename = "ChecksumSHA1", skip_serializing_if = "Option::is_none")]
+        pub sha1: Option<String>,
+        #[serde(rename = "ChecksumSHA256", skip_serializing_if = "Option::is_none")]
+        pub sha256: Option<String>,
+    }
+
+    impl ChecksumValue {
+        pub fn new(algorithm: ChecksumAlgorithm, value: String) -> Self {
+            let value = Some(value);
+            match algorithm {
+                ChecksumAlgorithm::Crc32 => ChecksumValue {
+                    crc32: value,
+                    ..Default::default()
+                },
+                ChecksumAlgorithm::Crc32c => ChecksumValue {
+                    crc32c: value,
+                    ..Default::default()
+                },
+                ChecksumAlgorithm::Crc64Nvme => ChecksumValue {
+                    crc64nvme: value,
+                    ..Default::default()
+                },
+                ChecksumAlgorithm::Sha1 => ChecksumValue {
+                    sha1: value,
+                    ..Default::default()
+                },
+                ChecksumAlgorithm::Sha256 => ChecksumValue {
+                    sha256: value,
+                    ..Default::default()
+                },
+            }
+        }
+    }
+
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct Checksum {
+        #[serde(flatten)]
+        pub value: ChecksumValue,
+        pub checksum_type: &'static str,
+    }
+
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct Part {
+        pub part_number: i64,
+        pub size: i64,
+        #[serde(flatten)]
+        pub checksum: ChecksumValue,
+    }
+
+    #[derive(Serialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct ObjectParts {
+        pub total_parts_count: usize,
+        pub part_number_marker: i64,
+        pub next_part_number_marker: i64,
+        pub max_parts: usize,
+        pub is_truncated: bool,
+        pub part: Vec<Part>,
+    }
+
+    /// Only the requested attributes are present
+    #[derive(Serialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct GetObjectAttributesResponse {
+        #[serde(rename = "ETag", skip_serializing_if = "Option::is_none")]
+        pub etag: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub checksum: Option<Checksum>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub object_parts: Option<ObjectParts>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub storage_class: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub object_size: Option<i64>,
+    }
+
+    impl GetObjectAttributesResponse {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("GetObjectAttributesResponse", self)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{Checksum, ChecksumValue, GetObjectAttributesResponse, ObjectParts, Part};
+        use crate::s3::checksum::ChecksumAlgorithm;
+
+        #[test]
+        fn test_encode_response() {
+            let response = GetObjectAttributesResponse {
+                etag: Some("d8399932ce6023ac3365f50fbbcde132-2".to_string()),
+                checksum: Some(Checksum {
+                    value: ChecksumValue::new(ChecksumAlgorithm::Crc32, "rBMwBw==-2".to_string()),
+                    checksum_type: "COMPOSITE",
+                }),
+                object_parts: Some(ObjectParts {
+                    total_parts_count: 2,
+                    part_number_marker: 0,
+                    next_part_number_marker: 1,
+                    max_parts: 1,
+                    is_truncated: true,
+                    part: vec![Part {
+                        part_number: 1,
+                        size: 4,
+                        checksum: ChecksumValue::new(
+                            ChecksumAlgorithm::Crc32,
+                            "m+MTJA==".to_string(),
+                        ),
+                    }],
+                }),
+                storage_class: Some("STANDARD"),
+                object_size: Some(9),
+            };
+
+            assert_eq!(
+                response.to_string().unwrap(),
+                "<GetObjectAttributesResponse>\
+                <ETag>d8399932ce6023ac3365f50fbbcde132-2</ETag>\
+                <Checksum><ChecksumCRC32>rBMwBw==-2</ChecksumCRC32><ChecksumType>COMPOSITE</ChecksumType></Checksum>\
+                <ObjectParts><TotalPartsCount>2</TotalPartsCount><PartNumberMarker>0</PartNumberMarker>\
+                <NextPartNumberMarker>1</NextPartNumberMarker><MaxParts>1</MaxParts><IsTruncated>true</IsTruncated>\
+                <Part><PartNumber>1</PartNumber><Size>4</Size><ChecksumCRC32>m+MTJA==</ChecksumCRC32></Part></ObjectParts>\
+                <StorageClass>STANDARD</StorageClass>\
+                <ObjectSize>9</ObjectSize>\
+                </GetObjectAttributesResponse>"
+            );
+        }
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Lists the requested page of parts, after `x-amz-part-number-marker`
+fn object_parts(
+    parts: Vec<ObjectPart>,
+    algorithm: Option<ChecksumAlgorithm>,
+    headers: &HeaderMap,
+) -> Option<object_attributes_payloads::ObjectParts> {
+    let max_parts = header(headers, MAX_PARTS_HEADER)
+        .map_or(Ok(DEFAULT_MAX_PARTS), str::parse)
+        .ok()?;
+    let part_number_marker = header(headers, PART_NUMBER_MARKER_HEADER)
+        .map_or(Ok(0), str::parse)
+        .ok()?;
+
+    let total_parts_count = parts.len();
+    let mut remaining = parts
+        .into_iter()
+        .filter(|part| part.number > part_number_marker)
+        .peekable();
+    let page = remaining.by_ref().take(max_parts).collect::<Vec<_>>();
+    let is_truncated = remaining.peek().is_some();
+
+    Some(object_attributes_payloads::ObjectParts {
+        total_parts_count,
+        part_number_marker,
+        next_part_number_marker: page.last().map_or(part_number_marker, |part| part.number),
+        max_parts,
+        is_truncated,
+        part: page
+            .into_iter()
+            .map(|part| object_attributes_payloads::Part {
+                part_number: part.number,
+                size: part.size,
+                checksum: part.checksum.zip(algorithm).map_or_else(
+                    Default::default,
+                    |(value, algorithm)| {
+                        object_attributes_payloads::ChecksumValue::new(algorithm, value)
+                    },
+                ),
+            })
+            .collect(),
+    })
+}
+
+/// Implements `GetObjectAttributes`, returning the attributes listed on `x-amz-object-attributes`
+pub(super) async fn get_object_attributes(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, version_id).await?;
+
+    let attributes = header(headers, ATTRIBUTES_HEADER)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|attribute| !attribute.is_empty())
+        .collect::<Vec<_>>();
+    if attributes.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let algorithm = Checksum::stored(&metadata).map(|checksum| checksum.algorithm);
+    let mut response = object_attributes_payloads::GetObjectAttributesResponse::default();
+    for attribute in attributes {
+        match attribute {
+            "ETag" => {
+                let etag = super::etag_value(
+                    state.config.etag_mode,
+                    &metadata.cid,
+                    metadata.etag.as_deref(),
+                    metadata.private,
+                );
+                response.etag = Some(etag.trim_matches('"').to_string());
+            }
+            "Checksum" => {
+                response.checksum = Checksum::stored(&metadata).map(|checksum| {
+                    object_attributes_payloads::Checksum {
+                        checksum_type: checksum.kind.as_str(),
+                        value: object_attributes_payloads::ChecksumValue::new(
+                            checksum.algorithm,
+                            checksum.value,
+                        ),
+                    }
+                });
+            }
+            "ObjectParts" => {
+                response.object_parts = match ObjectPart::stored(&metadata) {
+                    Some(parts) => Some(
+                        object_parts(parts, algorithm, headers).ok_or(StatusCode::BAD_REQUEST)?,
+                    ),
+                    None => None,
+                };
+            }
+            "StorageClass" => response.storage_class = Some(STORAGE_CLASS),
+            "ObjectSize" => response.object_size = Some(metadata.size),
+            _ => return Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/xml")
+            .header(
+                header::LAST_MODIFIED,
+                metadata
+                    .updated_at
+                    .and_utc()
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            )
+            .body(Body::from(response.to_string().unwrap_or_default()))
+            .unwrap_or_default(),
+    )
+}
@@ -0,0 +1,434 @@
+//! Object Lock: retention periods and legal holds preventing objects from being deleted,
+//! overwritten or unpinned (WORM)
+//!
+//! Unlike S3, it does not require Object Lock to be enabled on the bucket.
+//!
+//! Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-lock.html>
+
+use std::str::FromStr;
+
+use axum::body::Body;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::Response;
+use bytes::{Buf, Bytes};
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::{AppState, database};
+
+const BYPASS_GOVERNANCE_HEADER: &str = "x-amz-bypass-governance-retention";
+const MODE_HEADER: &str = "x-amz-object-lock-mode";
+const RETAIN_UNTIL_HEADER: &str = "x-amz-object-lock-retain-until-date";
+const LEGAL_HOLD_HEADER: &str = "x-amz-object-lock-legal-hold";
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum RetentionMode {
+    /// Can be removed or shortened with `x-amz-bypass-governance-retention: true`
+    Governance,
+    /// Can't be removed or shortened by anyone until it expires
+    Compliance,
+}
+
+impl RetentionMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RetentionMode::Governance => "GOVERNANCE",
+            RetentionMode::Compliance => "COMPLIANCE",
+        }
+    }
+}
+
+impl FromStr for RetentionMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GOVERNANCE" => Ok(RetentionMode::Governance),
+            "COMPLIANCE" => Ok(RetentionMode::Compliance),
+            _ => Err(()),
+        }
+    }
+}
+
+pub(super) mod object_lock_payloads {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct Retention {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub retain_until_date: Option<String>,
+    }
+
+    impl Retention {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("Retention", self)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "UPPERCASE")]
+    pub enum LegalHoldStatus {
+        On,
+        Off,
+    }
+
+    #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct LegalHold {
+        pub status: LegalHoldStatus,
+    }
+
+    impl LegalHold {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("LegalHold", self)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{LegalHold, LegalHoldStatus, Retention};
+
+        #[test]
+        fn test_parses_request() {
+            let payload = r#"<Retention xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+<Mode>COMPLIANCE</Mode>
+<RetainUntilDate>2030-01-01T00:00:00.000Z</RetainUntilDate>
+</Retention>"#;
+            assert_eq!(
+                quick_xml::de::from_str::<Retention>(payload).unwrap(),
+                Retention {
+                    mode: Some("COMPLIANCE".to_string()),
+                    retain_until_date: Some("2030-01-01T00:00:00.000Z".to_string()),
+                }
+            );
+
+            let payload = "<LegalHold><Status>ON</Status></LegalHold>";
+            assert_eq!(
+                quick_xml::de::from_str::<LegalHold>(payload).unwrap(),
+                LegalHold {
+                    status: LegalHoldStatus::On
+                }
+            );
+        }
+
+        #[test]
+        fn test_encode_response() {
+            let payload = LegalHold {
+                status: LegalHoldStatus::Off,
+            };
+            assert_eq!(
+                payload.to_string().unwrap(),
+                "<LegalHold><Status>OFF</Status></LegalHold>"
+            );
+        }
+    }
+}
+
+/// Object Lock requested on upload with `x-amz-object-lock-*` headers
+#[derive(Default, Debug)]
+pub struct ObjectLock {
+    pub mode: Option<RetentionMode>,
+    pub retain_until: Option<NaiveDateTime>,
+    pub legal_hold: bool,
+}
+
+impl ObjectLock {
+    /// Returns `None` if the headers are invalid, or only one of mode and retain until date is present
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .map_or(Some(""), |value| value.to_str().ok())
+        };
+
+        let mode = match header(MODE_HEADER)? {
+            "" => None,
+            mode => Some(mode.parse().ok()?),
+        };
+        let retain_until = match header(RETAIN_UNTIL_HEADER)? {
+            "" => None,
+            date => Some(parse_date(date)?),
+        };
+        let legal_hold = match header(LEGAL_HOLD_HEADER)? {
+            "ON" => true,
+            "" | "OFF" => false,
+            _ => return None,
+        };
+
+        if mode.is_some() != retain_until.is_some() {
+            return None;
+        }
+
+        Some(ObjectLock {
+            mode,
+            retain_until,
+            legal_hold,
+        })
+    }
+}
+
+fn parse_date(date: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(date)
+        .ok()
+        .map(|date| date.with_timezone(&Utc).naive_utc())
+}
+
+fn format_date(date: NaiveDateTime) -> String {
+    date.and_utc().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Retention of an object, if not expired yet
+fn active_retention(
+    metadata: &database::MetadataResponse,
+) -> Option<(RetentionMode, NaiveDateTime)> {
+    let mode = metadata.retention_mode.as_deref()?.parse().ok()?;
+    let retain_until = metadata.retain_until?;
+    (retain_until > Utc::now().naive_utc()).then_some((mode, retain_until))
+}
+
+fn bypass_governance(headers: &HeaderMap) -> bool {
+    headers
+        .get(BYPASS_GOVERNANCE_HEADER)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"true"))
+}
+
+/// Refuses to remove an object under legal hold or an active retention
+pub(super) fn check_removable(
+    metadata: &database::MetadataResponse,
+    bypass_governance: bool,
+) -> Result<(), StatusCode> {
+    if metadata.legal_hold {
+        tracing::warn!(
+            bucket = metadata.bucket,
+            key = metadata.key,
+            "Object under legal hold"
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match active_retention(metadata) {
+        Some((RetentionMode::Governance, _)) if bypass_governance => Ok(()),
+        Some((mode, retain_until)) => {
+            tracing::warn!(
+                bucket = metadata.bucket,
+                key = metadata.key,
+                mode = mode.as_str(),
+                %retain_until,
+                "Object under retention"
+            );
+            Err(StatusCode::FORBIDDEN)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Refuses to replace a locked object: the current one on unversioned buckets, or the `null` version
+/// on suspended buckets. New versions on versioning-enabled buckets keep previous ones, and are always allowed
+pub(super) async fn check_replaceable(
+    state: &AppState,
+    current: Option<&database::MetadataResponse>,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<(), StatusCode> {
+    match version_id {
+        None => current.map_or(Ok(()), |current| {
+            check_removable(current, bypass_governance(headers))
+        }),
+        Some(version_id @ "null") => {
+            check_version_removable(state, bucket, key, version_id, headers).await
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Refuses to permanently delete a locked version
+pub(super) async fn check_version_removable(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+    headers: &HeaderMap,
+) -> Result<(), StatusCode> {
+    match state.db.get_object_version(bucket, key, version_id).await {
+        Ok(Some(version)) if !version.delete_marker => {
+            check_removable(&version.into(), bypass_governance(headers))
+        }
+        Ok(_) => Ok(()),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to retrieve object version");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Adds `x-amz-object-lock-*` headers of locked objects
+pub(super) fn response_headers(
+    mut builder: http::response::Builder,
+    metadata: &database::MetadataResponse,
+) -> http::response::Builder {
+    if let (Some(mode), Some(retain_until)) = (&metadata.retention_mode, metadata.retain_until) {
+        builder = builder
+            .header(MODE_HEADER, mode)
+            .header(RETAIN_UNTIL_HEADER, format_date(retain_until));
+    }
+    if metadata.legal_hold {
+        builder = builder.header(LEGAL_HOLD_HEADER, "ON");
+    }
+    builder
+}
+
+/// Implements `PutObjectRetention`
+///
+/// Active retentions can only be extended, unless on `GOVERNANCE` mode with `x-amz-bypass-governance-retention: true`
+pub(super) async fn put_object_retention(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, version_id).await?;
+
+    let retention: object_lock_payloads::Retention =
+        quick_xml::de::from_reader(body.reader()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mode = retention
+        .mode
+        .map(|mode| mode.parse::<RetentionMode>())
+        .transpose()
+        .map_err(|()| StatusCode::BAD_REQUEST)?;
+    let retain_until = retention
+        .retain_until_date
+        .map(|date| parse_date(&date).ok_or(StatusCode::BAD_REQUEST))
+        .transpose()?;
+
+    if mode.is_some() != retain_until.is_some()
+        || retain_until.is_some_and(|date| date <= Utc::now().naive_utc())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Some((current_mode, current_until)) = active_retention(&metadata) {
+        let weakened = match (current_mode, mode, retain_until) {
+            (RetentionMode::Compliance, Some(RetentionMode::Governance), _) => true,
+            (_, Some(_), Some(retain_until)) => retain_until < current_until,
+            _ => true,
+        };
+        if weakened && (current_mode == RetentionMode::Compliance || !bypass_governance(headers)) {
+            tracing::warn!(bucket, key, "Refusing to weaken active retention");
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    if let Err(e) = state
+        .db
+        .put_object_retention(
+            bucket,
+            key,
+            metadata.version_id.as_deref(),
+            mode.map(RetentionMode::as_str),
+            retain_until,
+        )
+        .await
+    {
+        tracing::error!(error = %e, "Failed to store object retention");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .unwrap_or_default(),
+    )
+}
+
+/// Implements `GetObjectRetention`
+pub(super) async fn get_object_retention(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, version_id).await?;
+
+    let (Some(mode), Some(retain_until)) = (metadata.retention_mode, metadata.retain_until) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let retention = object_lock_payloads::Retention {
+        mode: Some(mode),
+        retain_until_date: Some(format_date(retain_until)),
+    };
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(Body::from(retention.to_string().unwrap_or_default()))
+            .unwrap_or_default(),
+    )
+}
+
+/// Implements `PutObjectLegalHold`
+pub(super) async fn put_object_legal_hold(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    body: Bytes,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, version_id).await?;
+
+    let legal_hold: object_lock_payloads::LegalHold =
+        quick_xml::de::from_reader(body.reader()).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    if let Err(e) = state
+        .db
+        .put_object_legal_hold(
+            bucket,
+            key,
+            metadata.version_id.as_deref(),
+            legal_hold.status == object_lock_payloads::LegalHoldStatus::On,
+        )
+        .await
+    {
+        tracing::error!(error = %e, "Failed to store object legal hold");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .unwrap_or_default(),
+    )
+}
+
+/// Implements `GetObjectLegalHold`
+pub(super) async fn get_object_legal_hold(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, version_id).await?;
+
+    let legal_hold = object_lock_payloads::LegalHold {
+        status: if metadata.legal_hold {
+            object_lock_payloads::LegalHoldStatus::On
+        } else {
+            object_lock_payloads::LegalHoldStatus::Off
+        },
+    };
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(Body::from(legal_hold.to_string().unwrap_or_default()))
+            .unwrap_or_default(),
+    )
+}
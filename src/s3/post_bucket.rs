@@ -5,7 +5,7 @@ use axum::{
 };
 use bytes::{Buf, Bytes};
 use futures::TryFutureExt;
-use http::{StatusCode, header};
+use http::{HeaderMap, StatusCode, header};
 use serde::Deserialize;
 use tracing_futures::Instrument;
 
@@ -138,6 +138,7 @@ pub async fn modify_bucket(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
     Query(query): Query<DeleteBucketParams>,
+    headers: HeaderMap,
     body: Bytes,
 ) -> Result<Response<Body>, StatusCode> {
     if query.delete.is_some() {
@@ -152,6 +153,7 @@ pub async fn modify_bucket(
                 State(state.clone()),
                 Path((bucket.clone(), entry.key.clone())),
                 Query::default(),
+                headers.clone(),
             )
             .inspect_ok(|_| tracing::trace!("Deleted object"))
             .inspect_err(|e| tracing::error!(error = %e, "Failed to delete object"))
@@ -1,15 +1,20 @@
 use axum::body::Body;
 use axum::extract::{Path, Query, State};
-use axum::http::{StatusCode, header};
+use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::Response;
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use dashmap::DashMap;
 use itertools::Itertools;
 use rand::distr::{Alphanumeric, SampleString};
 use serde::Deserialize;
 
 use crate::AppState;
+use crate::encryption::Encryption;
+
+use super::checksum::{self, Checksum};
+use super::object_attributes::ObjectPart;
+use super::put_object::{Content, Source};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -20,20 +25,96 @@ pub struct PostObjectParams {
     upload_id: Option<String>,
 }
 
+/// Implements `CompleteMultiPartUpload`, storing the concatenated parts as a single object
+async fn complete_upload(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    upload: super::MultipartUpload,
+) -> Result<Response<Body>, StatusCode> {
+    let numbered = upload
+        .parts
+        .into_iter()
+        .sorted_by_key(|(k, _)| *k)
+        .collect::<Vec<_>>();
+    let parts = numbered.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>();
+    let checksum = match Checksum::multipart(&upload.headers, &parts) {
+        Ok(checksum) => checksum,
+        Err(e) => {
+            tracing::warn!(error = %e, "Invalid checksum request");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    let etag = checksum::multipart_etag(&parts);
+    let object_parts = ObjectPart::from_upload(&numbered, checksum.as_ref());
+    let object_parts = serde_json::to_string(&object_parts).ok();
+    let body: Bytes = parts.into_iter().map(BytesMut::from).concat().into();
+
+    let content = Content {
+        source: Source::Body(&body),
+        etag: Some(&etag),
+        checksum: checksum.as_ref(),
+        parts: object_parts.as_deref(),
+    };
+    let upload =
+        super::put_object::store_object(state, bucket, key, &upload.headers, content).await?;
+    let etag = upload
+        .headers()
+        .get(header::ETAG)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let mut response = Response::builder();
+    for (name, value) in upload.headers() {
+        if name.as_str().starts_with("x-amz-server-side-encryption") || name == "x-amz-version-id" {
+            response = response.header(name, value);
+        }
+    }
+
+    Ok(response
+        .status(StatusCode::OK)
+        .body(Body::from(format!(
+            r#"
+    <?xml version="1.0" encoding="UTF-8"?>
+    <CompleteMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+        <Bucket>{bucket}</Bucket>
+        <Key>{key}</Key>
+        <ETag>{etag}</ETag>{checksum}
+    </CompleteMultipartUploadResult>"#,
+            checksum = checksum.as_ref().map_or_else(String::new, |checksum| {
+                format!(
+                    "<Checksum{algorithm}>{value}</Checksum{algorithm}><ChecksumType>{kind}</ChecksumType>",
+                    algorithm = checksum.algorithm.as_str(),
+                    value = checksum.value,
+                    kind = checksum.kind.as_str(),
+                )
+            })
+        )))
+        .unwrap_or_default())
+}
+
 #[axum::debug_handler]
 /// Handles `CreateMultiPartUpload` and `CompleteMultiPartUpload` depending on query parameters
 pub async fn multipart_upload(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
     Query(params): Query<PostObjectParams>,
+    headers: HeaderMap,
 ) -> Result<Response<Body>, StatusCode> {
     if params.uploads.is_some() {
         let _ = tracing::debug_span!("Starting multipart upload", bucket, key).entered();
+        // Fail early instead of only on `CompleteMultipartUpload`
+        if let Err(e) = Encryption::requested(&state.config.encryption, &headers) {
+            tracing::warn!(error = %e, "Invalid encryption request");
+            return Err(e.status_code());
+        }
+
         let upload_id = Alphanumeric.sample_string(&mut rand::rng(), 12);
-        match state
-            .multipart_slots
-            .insert(upload_id.clone(), DashMap::new())
-        {
+        let upload = super::MultipartUpload {
+            parts: DashMap::new(),
+            headers,
+        };
+        match state.multipart_slots.insert(upload_id.clone(), upload) {
             Ok(_) => {
                 return Ok(Response::builder()
                     .status(StatusCode::OK)
@@ -56,40 +137,8 @@ pub async fn multipart_upload(
     if let Some(upload_id) = params.upload_id {
         let _ = tracing::debug_span!("Finishing multipart upload", bucket, key).entered();
         match state.multipart_slots.remove(&upload_id) {
-            Some((_, parts)) => {
-                let body = parts
-                    .into_iter()
-                    .sorted_by_key(|(k, _)| *k)
-                    .map(|(_, v)| BytesMut::from(v))
-                    .concat()
-                    .into();
-
-                let upload = super::put_object::put_object(
-                    State(state),
-                    Path((bucket.clone(), key.clone())),
-                    None,
-                    Query::default(),
-                    body,
-                )
-                .await?;
-                let etag = upload
-                    .headers()
-                    .get(header::ETAG)
-                    .and_then(|h| h.to_str().ok())
-                    .unwrap_or_default();
-
-                return Ok(Response::builder()
-                    .status(StatusCode::OK)
-                    .body(Body::from(format!(
-                        r#"
-                <?xml version="1.0" encoding="UTF-8"?>
-                <CompleteMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
-                    <Bucket>{bucket}</Bucket>
-                    <Key>{key}</Key>
-                    <ETag>{etag}</ETag>
-                </CompleteMultipartUploadResult>"#
-                    )))
-                    .unwrap_or_default());
+            Some((_, upload)) => {
+                return complete_upload(&state, &bucket, &key, upload).await;
             }
             None => {
                 return Err(StatusCode::BAD_REQUEST);
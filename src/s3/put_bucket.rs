@@ -0,0 +1,395 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    response::Response,
+};
+use bytes::{Buf, Bytes};
+use http::StatusCode;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::AppState;
+
+pub(super) mod versioning_payloads {
+    use std::str::FromStr;
+
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+    pub enum VersioningStatus {
+        Enabled,
+        Suspended,
+    }
+
+    impl VersioningStatus {
+        pub fn as_str(self) -> &'static str {
+            match self {
+                VersioningStatus::Enabled => "Enabled",
+                VersioningStatus::Suspended => "Suspended",
+            }
+        }
+    }
+
+    impl FromStr for VersioningStatus {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "Enabled" => Ok(VersioningStatus::Enabled),
+                "Suspended" => Ok(VersioningStatus::Suspended),
+                _ => Err(()),
+            }
+        }
+    }
+
+    /// Both the `PutBucketVersioning` payload and the `GetBucketVersioning` response
+    #[derive(Serialize, Deserialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct VersioningConfiguration {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub status: Option<VersioningStatus>,
+    }
+
+    impl VersioningConfiguration {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("VersioningConfiguration", self)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{VersioningConfiguration, VersioningStatus};
+
+        #[test]
+        fn test_parses_request() {
+            let payload = r#"<VersioningConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+<Status>Enabled</Status>
+</VersioningConfiguration>"#;
+
+            assert_eq!(
+                quick_xml::de::from_str::<VersioningConfiguration>(payload).unwrap(),
+                VersioningConfiguration {
+                    status: Some(VersioningStatus::Enabled)
+                }
+            );
+        }
+
+        #[test]
+        fn test_encode_response() {
+            assert_eq!(
+                VersioningConfiguration::default().to_string().unwrap(),
+                "<VersioningConfiguration/>"
+            );
+            assert_eq!(
+                VersioningConfiguration {
+                    status: Some(VersioningStatus::Suspended)
+                }
+                .to_string()
+                .unwrap(),
+                "<VersioningConfiguration><Status>Suspended</Status></VersioningConfiguration>"
+            );
+        }
+    }
+}
+
+pub(super) mod lifecycle_payloads {
+    use rand::distr::{Alphanumeric, SampleString};
+    use serde::{Deserialize, Serialize};
+
+    use crate::database::LifecycleRule;
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+    pub enum RuleStatus {
+        Enabled,
+        Disabled,
+    }
+
+    /// Only prefix filters are supported. Unknown filters are rejected instead of ignored,
+    /// as they would expire more objects than intended
+    #[derive(Serialize, Deserialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase", deny_unknown_fields)]
+    pub struct Filter {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub prefix: Option<String>,
+    }
+
+    /// Longest expiration accepted, about a hundred years, so expiration dates never overflow
+    pub const MAX_DAYS: i64 = 36500;
+
+    #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase", deny_unknown_fields)]
+    pub struct Expiration {
+        pub days: i64,
+    }
+
+    #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase", deny_unknown_fields)]
+    pub struct NoncurrentVersionExpiration {
+        pub noncurrent_days: i64,
+    }
+
+    #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct Rule {
+        #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+        pub id: Option<String>,
+        /// Deprecated in favor of `Filter`, but still sent by older clients
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub prefix: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub filter: Option<Filter>,
+        pub status: RuleStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub expiration: Option<Expiration>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub noncurrent_version_expiration: Option<NoncurrentVersionExpiration>,
+    }
+
+    impl Rule {
+        /// Converts into a stored rule, returning `None` if the rule is invalid
+        pub fn into_stored(self, bucket: &str) -> Option<LifecycleRule> {
+            let expiration_days = self.expiration.map(|e| e.days);
+            let noncurrent_days = self
+                .noncurrent_version_expiration
+                .map(|e| e.noncurrent_days);
+            let valid = |days: i64| (1..=MAX_DAYS).contains(&days);
+            if !expiration_days.is_none_or(valid) || !noncurrent_days.is_none_or(valid) {
+                return None;
+            }
+
+            Some(LifecycleRule {
+                bucket: bucket.to_string(),
+                rule_id: self
+                    .id
+                    .unwrap_or_else(|| Alphanumeric.sample_string(&mut rand::rng(), 12)),
+                prefix: self
+                    .filter
+                    .and_then(|filter| filter.prefix)
+                    .or(self.prefix)
+                    .unwrap_or_default(),
+                enabled: self.status == RuleStatus::Enabled,
+                expiration_days,
+                noncurrent_days,
+            })
+        }
+    }
+
+    impl From<LifecycleRule> for Rule {
+        fn from(rule: LifecycleRule) -> Self {
+            Rule {
+                id: Some(rule.rule_id),
+                prefix: None,
+                filter: Some(Filter {
+                    prefix: Some(rule.prefix),
+                }),
+                status: if rule.enabled {
+                    RuleStatus::Enabled
+                } else {
+                    RuleStatus::Disabled
+                },
+                expiration: rule.expiration_days.map(|days| Expiration { days }),
+                noncurrent_version_expiration: rule
+                    .noncurrent_days
+                    .map(|noncurrent_days| NoncurrentVersionExpiration { noncurrent_days }),
+            }
+        }
+    }
+
+    /// Both the `PutBucketLifecycleConfiguration` payload and the `GetBucketLifecycleConfiguration` response
+    #[derive(Serialize, Deserialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct LifecycleConfiguration {
+        #[serde(default)]
+        pub rule: Vec<Rule>,
+    }
+
+    impl LifecycleConfiguration {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("LifecycleConfiguration", self)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{
+            Expiration, Filter, LifecycleConfiguration, MAX_DAYS, NoncurrentVersionExpiration,
+            Rule, RuleStatus,
+        };
+
+        #[test]
+        fn test_parses_request() {
+            let payload = r#"<LifecycleConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+<Rule>
+<ID>tmp</ID>
+<Filter><Prefix>tmp/</Prefix></Filter>
+<Status>Enabled</Status>
+<Expiration><Days>1</Days></Expiration>
+</Rule>
+<Rule>
+<Prefix>thumbnails-cache/</Prefix>
+<Status>Disabled</Status>
+<NoncurrentVersionExpiration><NoncurrentDays>30</NoncurrentDays></NoncurrentVersionExpiration>
+</Rule>
+</LifecycleConfiguration>"#;
+
+            let configuration = quick_xml::de::from_str::<LifecycleConfiguration>(payload).unwrap();
+            assert_eq!(
+                configuration.rule[0],
+                Rule {
+                    id: Some("tmp".to_string()),
+                    prefix: None,
+                    filter: Some(Filter {
+                        prefix: Some("tmp/".to_string())
+                    }),
+                    status: RuleStatus::Enabled,
+                    expiration: Some(Expiration { days: 1 }),
+                    noncurrent_version_expiration: None,
+                }
+            );
+
+            let mut rules = configuration.rule.into_iter();
+            let first = rules.next().unwrap().into_stored("bucket").unwrap();
+            assert_eq!(first.prefix, "tmp/");
+            assert_eq!(first.expiration_days, Some(1));
+
+            let second = rules.next().unwrap().into_stored("bucket").unwrap();
+            assert_eq!(second.prefix, "thumbnails-cache/");
+            assert!(!second.enabled);
+            assert_eq!(second.noncurrent_days, Some(30));
+        }
+
+        #[test]
+        fn test_rejects_unsupported_filters() {
+            let payload = r#"<LifecycleConfiguration>
+<Rule>
+<Filter><Tag><Key>status</Key><Value>quarantine</Value></Tag></Filter>
+<Status>Enabled</Status>
+<Expiration><Days>1</Days></Expiration>
+</Rule>
+</LifecycleConfiguration>"#;
+
+            assert!(quick_xml::de::from_str::<LifecycleConfiguration>(payload).is_err());
+        }
+
+        #[test]
+        fn test_rejects_out_of_range_days() {
+            for days in [0, -1, MAX_DAYS + 1, i64::MAX] {
+                let rule = Rule {
+                    id: None,
+                    prefix: None,
+                    filter: None,
+                    status: RuleStatus::Enabled,
+                    expiration: Some(Expiration { days }),
+                    noncurrent_version_expiration: None,
+                };
+                assert!(rule.into_stored("bucket").is_none());
+
+                let rule = Rule {
+                    id: None,
+                    prefix: None,
+                    filter: None,
+                    status: RuleStatus::Enabled,
+                    expiration: None,
+                    noncurrent_version_expiration: Some(NoncurrentVersionExpiration {
+                        noncurrent_days: days,
+                    }),
+                };
+                assert!(rule.into_stored("bucket").is_none());
+            }
+
+            let rule = Rule {
+                id: None,
+                prefix: None,
+                filter: None,
+                status: RuleStatus::Enabled,
+                expiration: Some(Expiration { days: MAX_DAYS }),
+                noncurrent_version_expiration: None,
+            };
+            assert!(rule.into_stored("bucket").is_some());
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PutBucketParams {
+    versioning: Option<String>,
+    lifecycle: Option<String>,
+}
+
+/// Implements `PutBucketLifecycleConfiguration`, replacing every rule of the bucket
+async fn put_bucket_lifecycle(
+    state: &AppState,
+    bucket: &str,
+    body: Bytes,
+) -> Result<Response<Body>, StatusCode> {
+    let configuration: lifecycle_payloads::LifecycleConfiguration =
+        quick_xml::de::from_reader(body.reader()).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let rules = configuration
+        .rule
+        .into_iter()
+        .map(|rule| rule.into_stored(bucket))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if !rules.iter().map(|rule| &rule.rule_id).all_unique() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(e) = state.db.put_lifecycle_rules(bucket, &rules).await {
+        tracing::error!(error = %e, "Failed to store lifecycle rules");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    tracing::info!(
+        bucket,
+        total = rules.len(),
+        "Updated bucket lifecycle rules"
+    );
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(Body::empty())
+        .unwrap_or_default())
+}
+
+#[axum::debug_handler]
+/// Only implements `PutBucketVersioning` and `PutBucketLifecycleConfiguration` as Buckets are created on upload
+pub async fn put_bucket(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Query(query): Query<PutBucketParams>,
+    body: Bytes,
+) -> Result<Response<Body>, StatusCode> {
+    if query.versioning.is_some() {
+        let configuration: versioning_payloads::VersioningConfiguration =
+            quick_xml::de::from_reader(body.reader()).map_err(|_| StatusCode::BAD_REQUEST)?;
+        // Versioning can be suspended, but never disabled once enabled
+        let Some(status) = configuration.status else {
+            return Err(StatusCode::BAD_REQUEST);
+        };
+
+        if let Err(e) = state
+            .db
+            .put_bucket_versioning(&bucket, status.as_str())
+            .await
+        {
+            tracing::error!(error = %e, "Failed to store bucket versioning");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        tracing::info!(
+            bucket,
+            status = status.as_str(),
+            "Updated bucket versioning"
+        );
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .unwrap_or_default());
+    }
+
+    if query.lifecycle.is_some() {
+        return put_bucket_lifecycle(&state, &bucket, body).await;
+    }
+
+    Err(StatusCode::NOT_IMPLEMENTED)
+}
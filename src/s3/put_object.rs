@@ -1,18 +1,179 @@
 use axum::body::{Body, Bytes};
 use axum::extract::{Path, Query, State};
-use axum::http::{StatusCode, header};
+use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::Response;
 
-use axum_extra::extract::TypedHeader;
-
-use axum_extra::headers::ContentType;
-use axum_extra::typed_header;
+use axum_extra::headers::{ContentType, HeaderMapExt};
+use bytes::Buf;
 use futures::TryFutureExt;
 use serde::Deserialize;
 use serde_with::{DisplayFromStr, serde_as};
 use tracing::Instrument;
+use typed_path::UnixPath;
 
 use crate::AppState;
+use crate::cli::PinningBackend;
+use crate::database::{MetadataResponse, NewObjectMetadata, ObjectTag};
+use crate::encryption::Encryption;
+use crate::ipfs;
+
+use super::checksum::{self, Checksum, TrailedBody};
+use super::object_lock::{self, ObjectLock, RetentionMode};
+
+pub(super) mod tagging_payloads {
+    use itertools::Itertools;
+    use serde::{Deserialize, Serialize};
+
+    use crate::database::ObjectTag;
+
+    /// Limits of tags on a single object
+    /// Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-tagging.html>
+    const MAX_TAGS: usize = 10;
+    const MAX_KEY_LENGTH: usize = 128;
+    const MAX_VALUE_LENGTH: usize = 256;
+
+    #[derive(Serialize, Deserialize, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct Tag {
+        pub key: String,
+        #[serde(default)]
+        pub value: String,
+    }
+
+    #[derive(Serialize, Deserialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct TagSet {
+        #[serde(default)]
+        pub tag: Vec<Tag>,
+    }
+
+    /// Both the `PutObjectTagging` payload and the `GetObjectTagging` response
+    #[derive(Serialize, Deserialize, Default, Eq, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    pub struct Tagging {
+        #[serde(default)]
+        pub tag_set: TagSet,
+    }
+
+    impl Tagging {
+        pub fn to_string(&self) -> Result<String, quick_xml::SeError> {
+            quick_xml::se::to_string_with_root("Tagging", self)
+        }
+
+        /// Converts into stored tags, returning `None` if over the limits or with duplicated keys
+        pub fn into_tags(self) -> Option<Vec<ObjectTag>> {
+            let tags = self
+                .tag_set
+                .tag
+                .into_iter()
+                .map(|tag| ObjectTag {
+                    key: tag.key,
+                    value: tag.value,
+                })
+                .collect::<Vec<_>>();
+            is_valid(&tags).then_some(tags)
+        }
+    }
+
+    impl From<Vec<ObjectTag>> for Tagging {
+        fn from(tags: Vec<ObjectTag>) -> Self {
+            Tagging {
+                tag_set: TagSet {
+                    tag: tags
+                        .into_iter()
+                        .map(|tag| Tag {
+                            key: tag.key,
+                            value: tag.value,
+                        })
+                        .collect(),
+                },
+            }
+        }
+    }
+
+    fn is_valid(tags: &[ObjectTag]) -> bool {
+        tags.len() <= MAX_TAGS
+            && tags.iter().map(|tag| &tag.key).all_unique()
+            && tags.iter().all(|tag| {
+                !tag.key.is_empty()
+                    && tag.key.chars().count() <= MAX_KEY_LENGTH
+                    && tag.value.chars().count() <= MAX_VALUE_LENGTH
+            })
+    }
+
+    /// Parses the URL-encoded `x-amz-tagging` header of `PutObject`
+    ///
+    /// Eg: `status=quarantine&owner=banana`
+    pub fn from_header(header: &str) -> Option<Vec<ObjectTag>> {
+        let tags = url::form_urlencoded::parse(header.as_bytes())
+            .map(|(key, value)| ObjectTag {
+                key: key.into_owned(),
+                value: value.into_owned(),
+            })
+            .collect::<Vec<_>>();
+        is_valid(&tags).then_some(tags)
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::{Tag, TagSet, Tagging, from_header};
+        use crate::database::ObjectTag;
+
+        #[test]
+        fn test_parses_request() {
+            let payload = r#"<Tagging xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+<TagSet>
+<Tag>
+<Key>status</Key>
+<Value>quarantine</Value>
+</Tag>
+</TagSet>
+</Tagging>"#;
+
+            let expected = Tagging {
+                tag_set: TagSet {
+                    tag: vec![Tag {
+                        key: "status".to_string(),
+                        value: "quarantine".to_string(),
+                    }],
+                },
+            };
+            assert_eq!(
+                quick_xml::de::from_str::<Tagging>(payload).unwrap(),
+                expected
+            );
+        }
+
+        #[test]
+        fn test_encode_response() {
+            assert_eq!(
+                Tagging::default().to_string().unwrap(),
+                "<Tagging><TagSet/></Tagging>"
+            );
+        }
+
+        #[test]
+        fn test_parses_header() {
+            assert_eq!(
+                from_header("status=quarantine&owner=banana%20split").unwrap(),
+                vec![
+                    ObjectTag {
+                        key: "status".to_string(),
+                        value: "quarantine".to_string(),
+                    },
+                    ObjectTag {
+                        key: "owner".to_string(),
+                        value: "banana split".to_string(),
+                    },
+                ]
+            );
+            assert!(from_header("status=a&status=b").is_none());
+        }
+    }
+}
+
+/// Non-standard header creating the object from a CID already on IPFS, ignoring the request body
+const IMPORT_CID_HEADER: &str = "x-ipfs-import-cid";
 
 #[serde_as]
 #[derive(Deserialize, Debug)]
@@ -27,109 +188,466 @@ pub struct PutObjectMultiPartParams {
 pub struct PutObjectParams {
     #[serde(flatten)]
     upload_part: Option<PutObjectMultiPartParams>,
+    tagging: Option<String>,
+    retention: Option<String>,
+    #[serde(rename = "legal-hold")]
+    legal_hold: Option<String>,
+    #[serde(rename = "versionId")]
+    version_id: Option<String>,
 }
 
-#[axum::debug_handler]
-/// `PutObject` endpoint - stores object in IPFS and metadata in `SQLite`
-pub async fn put_object(
-    State(state): State<AppState>,
-    Path((bucket, key)): Path<(String, String)>,
-    content_type: Option<typed_header::TypedHeader<ContentType>>,
-    Query(params): Query<PutObjectParams>,
+/// Get content type from header or default to application/octet-stream
+fn content_type(state: &AppState, headers: &HeaderMap, key: &str) -> String {
+    if let Some(content_type) = headers.typed_get::<ContentType>() {
+        content_type.to_string()
+    } else if state.config.experimental.auto_mime.unwrap_or_default() {
+        super::guessed_content_type(key)
+    } else {
+        ContentType::octet_stream().to_string()
+    }
+}
+
+/// Tags from the `x-amz-tagging` header, `None` if invalid
+fn tags(headers: &HeaderMap) -> Option<Vec<ObjectTag>> {
+    match headers.get("x-amz-tagging") {
+        Some(header) => tagging_payloads::from_header(header.to_str().ok()?),
+        None => Some(Vec::new()),
+    }
+}
+
+/// Objects are public unless uploaded with a non-public `x-amz-acl` or into a private bucket
+///
+/// Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/acl-overview.html#canned-acl>
+fn is_private(state: &AppState, headers: &HeaderMap, bucket: &str) -> bool {
+    match headers.get("x-amz-acl").and_then(|acl| acl.to_str().ok()) {
+        Some("public-read" | "public-read-write") => false,
+        Some(_) => true,
+        None => state.config.private_buckets.iter().any(|b| b == bucket),
+    }
+}
+
+/// Implements `UploadPart`, staging the part in memory until `CompleteMultipartUpload`
+fn stage_part(
+    state: &AppState,
+    upload_part: &PutObjectMultiPartParams,
+    headers: &HeaderMap,
     body: Bytes,
 ) -> Result<Response, StatusCode> {
-    if let Some(upload_part) = params.upload_part {
-        if let Some(slot) = state.multipart_slots.get(&upload_part.upload_id) {
-            slot.value().insert(upload_part.part_number, body);
-            return Ok(Response::builder()
-                .status(StatusCode::OK)
-                .body(Body::empty())
-                .unwrap_or_default());
-        }
+    let Some(slot) = state.multipart_slots.get(&upload_part.upload_id) else {
         return Err(StatusCode::BAD_REQUEST);
+    };
+
+    let (etag, checksum) = verify_integrity(headers, &body)?;
+    slot.value().parts.insert(upload_part.part_number, body);
+
+    let mut response = Response::builder();
+    if let Some(checksum) = &checksum {
+        response = checksum.response_headers(response);
     }
+    Ok(response
+        .status(StatusCode::OK)
+        .header(header::ETAG, format!("\"{etag}\""))
+        .body(Body::empty())
+        .unwrap_or_default())
+}
+
+/// Computes the `ETag` and the requested checksum of an upload, verifying them against the ones sent by the client
+fn verify_integrity(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(String, Option<Checksum>), StatusCode> {
+    checksum::etag(headers, body)
+        .and_then(|etag| Ok((etag, Checksum::verified(headers, body)?)))
+        .map_err(|e| {
+            tracing::warn!(error = %e, "Invalid checksum");
+            StatusCode::BAD_REQUEST
+        })
+}
 
+/// Implements `PutObjectTagging`, replacing every tag of the current object
+async fn put_object_tagging(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    body: Bytes,
+) -> Result<Response, StatusCode> {
+    let metadata = super::object_metadata(state, bucket, key, None).await?;
+    super::check_tagged_version(&metadata, version_id)?;
+
+    let tags = quick_xml::de::from_reader::<_, tagging_payloads::Tagging>(body.reader())
+        .ok()
+        .and_then(tagging_payloads::Tagging::into_tags)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    if let Err(e) = state.db.put_object_tags(bucket, key, &tags).await {
+        tracing::error!(error = %e, "Failed to store object tags");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(
+        super::version_header(Response::builder(), metadata.version_id.as_deref())
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .unwrap_or_default(),
+    )
+}
+
+/// Retrieves the current object and the version id of its replacement,
+/// rejecting the upload if Object Lock protects the current object
+async fn replaceable_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    headers: &HeaderMap,
+) -> Result<(Option<MetadataResponse>, Option<String>), StatusCode> {
     // Unpin previous CID if already present, ingore errors to avoid impacting
     // Can't task::spawn as if the CID is the same it might unpin the entry
     let old = state
         .db
-        .get_object_metadata(&bucket, &key)
+        .get_object_metadata(bucket, key)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
         .await?;
 
-    // Get content type from header or default to application/octet-stream
-    let content_type = if let Some(TypedHeader(content_type)) = content_type {
-        content_type.to_string()
-    } else if state.config.experimental.auto_mime.unwrap_or_default() {
-        mime_guess::from_path(&key)
-            .first_or_octet_stream()
-            .essence_str()
-            .to_string()
-    } else {
-        ContentType::octet_stream().to_string()
+    let version_id = super::new_version_id(state, bucket).await?;
+    object_lock::check_replaceable(
+        state,
+        old.as_ref(),
+        bucket,
+        key,
+        version_id.as_deref(),
+        headers,
+    )
+    .await?;
+
+    Ok((old, version_id))
+}
+
+/// Unpins the replaced objects in the background, unless still referenced.
+/// Old versions remain pinned while versioning keeps them referenced
+fn unpin_replaced(state: AppState, replaced: Vec<MetadataResponse>, cid: String) {
+    tokio::task::spawn(
+        async move {
+            for old in &replaced {
+                if old.cid == cid {
+                    continue;
+                }
+                let _ = super::unpin_if_orphan(state.clone(), old)
+                    .inspect_ok(|()| tracing::trace!("unpinned old ref"))
+                    .instrument(tracing::debug_span!("Unpin old ref", cid = old.cid))
+                    .await;
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+#[axum::debug_handler]
+/// `PutObject` endpoint - stores object in IPFS and metadata in `SQLite`
+/// Also provides `UploadPart`, `PutObjectTagging`, `PutObjectRetention` and `PutObjectLegalHold` depending on query parameters
+/// Objects are imported from existing CIDs with the `x-ipfs-import-cid` header
+pub async fn put_object(
+    State(state): State<AppState>,
+    Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<PutObjectParams>,
+    mut headers: HeaderMap,
+    TrailedBody { body, trailers }: TrailedBody,
+) -> Result<Response, StatusCode> {
+    // Checksums of `aws-chunked` uploads are sent as trailers
+    headers.extend(trailers);
+
+    if let Some(upload_part) = params.upload_part {
+        return stage_part(&state, &upload_part, &headers, body);
+    }
+
+    if params.tagging.is_some() {
+        return put_object_tagging(&state, &bucket, &key, params.version_id.as_deref(), body).await;
+    }
+
+    let version = params.version_id.as_deref();
+    if params.retention.is_some() {
+        return object_lock::put_object_retention(&state, &bucket, &key, version, &headers, body)
+            .await;
+    }
+
+    if params.legal_hold.is_some() {
+        return object_lock::put_object_legal_hold(&state, &bucket, &key, version, body).await;
+    }
+
+    if let Some(cid) = headers.get(IMPORT_CID_HEADER) {
+        let cid = cid
+            .to_str()
+            .map_err(|_| StatusCode::BAD_REQUEST)?
+            .to_string();
+        let content = Content {
+            source: Source::Import(&cid),
+            etag: None,
+            checksum: None,
+            parts: None,
+        };
+        return store_object(&state, &bucket, &key, &headers, content).await;
+    }
+
+    let (etag, checksum) = verify_integrity(&headers, &body)?;
+    let content = Content {
+        source: Source::Body(&body),
+        etag: Some(&etag),
+        checksum: checksum.as_ref(),
+        parts: None,
     };
+    store_object(&state, &bucket, &key, &headers, content).await
+}
 
-    let path = match super::normalized_path(&state.config.folder_prefix, &bucket, &key) {
-        Ok(path) => path,
+/// Adds content to IPFS, pinned on the configured pinning backend, and returns its CID
+async fn add_content(
+    state: &AppState,
+    bucket: &str,
+    path: &UnixPath,
+    file: Vec<u8>,
+    private: bool,
+) -> Result<String, StatusCode> {
+    let options = match state.db.get_bucket_add_options(bucket).await {
+        Ok(options) => options.or(&state.config.add),
         Err(e) => {
-            tracing::error!(error = %e, "Invalid key value");
-            return Err(StatusCode::BAD_REQUEST);
+            tracing::error!(error = %e, "Failed to retrieve bucket add options");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
-
-    // Add content to IPFS and get CID
-    let file = body.to_vec();
-    let file_size = file.len();
-    let add_response = match state.ipfs_client.add_content(&path, file).await {
-        Ok(cid) => cid,
+    let pin = !private && matches!(state.config.pinning_backend, PinningBackend::Kubo);
+    let cid = match state
+        .ipfs_client
+        .add_content(path, file, pin, &options)
+        .await
+    {
+        Ok(add_response) => add_response.hash,
         Err(e) => {
             tracing::error!(error = %e, "Failed to add content to IPFS");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
 
-    let cid = add_response.hash.clone();
+    pin_content(state, bucket, path, &cid, private).await?;
+    Ok(cid)
+}
+
+/// Imports a file already on IPFS, pinned on the configured pinning backend unless private, and returns its CID and size
+async fn import_content(
+    state: &AppState,
+    bucket: &str,
+    path: &UnixPath,
+    cid: &str,
+    private: bool,
+) -> Result<(String, u64), StatusCode> {
+    let pin = !private && matches!(state.config.pinning_backend, PinningBackend::Kubo);
+    let import = state.ipfs_client.import_content(cid, path, pin);
+    let stat = match tokio::time::timeout(state.config.import_timeout.into(), import).await {
+        Ok(Ok(stat)) => stat,
+        Ok(Err(e @ (ipfs::Error::NotFile(_) | ipfs::Error::InvalidCid(_)))) => {
+            tracing::warn!(error = %e, "Invalid CID to import");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(Err(e @ (ipfs::Error::Client(_) | ipfs::Error::Unavailable(_)))) => {
+            tracing::error!(error = %e, "Failed to import CID");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        Ok(Err(e)) => {
+            tracing::error!(error = %e, "Failed to import CID");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        Err(_) => {
+            tracing::warn!(cid, "Timed out importing CID");
+            return Err(StatusCode::GATEWAY_TIMEOUT);
+        }
+    };
+
+    pin_content(state, bucket, path, &stat.hash, private).await?;
+    Ok((stat.hash, stat.size))
+}
+
+/// Pins content on IPFS Cluster, when configured, and queues it for remote pinning
+/// Private content is only kept by its MFS link, so it isn't announced by the IPFS Node, Cluster peers
+/// or the Pinning Service
+async fn pin_content(
+    state: &AppState,
+    bucket: &str,
+    path: &UnixPath,
+    cid: &str,
+    private: bool,
+) -> Result<(), StatusCode> {
+    if private {
+        return Ok(());
+    }
+
+    crate::pinning::pin_content(
+        &state.db,
+        state.cluster.as_ref(),
+        &state.config,
+        bucket,
+        &path.to_string_lossy(),
+        cid,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!(error = %e, "Failed to pin content");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Where the content of an object comes from
+pub(super) enum Source<'a> {
+    /// Uploaded bytes, added to IPFS
+    Body(&'a [u8]),
+    /// CID of a file already on IPFS, from the `x-ipfs-import-cid` header
+    Import(&'a str),
+}
+
+/// Verified content of an object, uploaded at once, from a multipart upload or imported from IPFS
+pub(super) struct Content<'a> {
+    pub source: Source<'a>,
+    /// MD5 of the content, unknown for imported CIDs
+    pub etag: Option<&'a str>,
+    pub checksum: Option<&'a Checksum>,
+    /// JSON encoded parts of multipart uploads
+    pub parts: Option<&'a str>,
+}
+
+/// Adds or imports the content of an object, returning its CID and size
+async fn store_content(
+    state: &AppState,
+    bucket: &str,
+    path: &UnixPath,
+    source: Source<'_>,
+    encryption: Option<&Encryption>,
+    private: bool,
+) -> Result<(String, i64), StatusCode> {
+    match (source, encryption) {
+        (Source::Body(body), encryption) => {
+            let file = match encryption {
+                Some(encryption) => encryption.encrypt(body),
+                None => body.to_vec(),
+            };
+            let file_size = body.len() as i64;
+            Ok((
+                add_content(state, bucket, path, file, private).await?,
+                file_size,
+            ))
+        }
+        (Source::Import(_), Some(_)) => {
+            tracing::warn!("Imported content can't be encrypted");
+            Err(StatusCode::BAD_REQUEST)
+        }
+        (Source::Import(cid), None) => {
+            let (cid, size) = import_content(state, bucket, path, cid, private).await?;
+            Ok((
+                cid,
+                i64::try_from(size).map_err(|_| StatusCode::BAD_REQUEST)?,
+            ))
+        }
+    }
+}
+
+/// Stores the object in IPFS and its metadata in `SQLite`, either uploaded at once or from a multipart upload
+pub(super) async fn store_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    headers: &HeaderMap,
+    content: Content<'_>,
+) -> Result<Response, StatusCode> {
+    let Content {
+        source,
+        etag,
+        checksum,
+        parts,
+    } = content;
+    let tags = tags(headers).ok_or(StatusCode::BAD_REQUEST)?;
+    let lock = ObjectLock::from_headers(headers).ok_or(StatusCode::BAD_REQUEST)?;
+
+    let (old, version_id) = replaceable_object(state, bucket, key, headers).await?;
+
+    let encryption = match Encryption::requested(&state.config.encryption, headers) {
+        Ok(encryption) => encryption,
+        Err(e) => {
+            tracing::warn!(error = %e, "Invalid encryption request");
+            return Err(e.status_code());
+        }
+    };
+
+    let content_type = content_type(state, headers, key);
+
+    let path = match super::normalized_path(&state.config.folder_prefix, bucket, key) {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::error!(error = %e, "Invalid key value");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    // Add content to IPFS and get CID
+    let private = is_private(state, headers, bucket);
+    let (cid, file_size) =
+        store_content(state, bucket, &path, source, encryption.as_ref(), private).await?;
     // Store metadata in SQLite
-    match state
-        .db
-        .store_object_metadata(&bucket, &key, &cid, file_size as i64, &content_type)
-        .await
-    {
-        Ok(()) => {
+    let object = NewObjectMetadata {
+        bucket,
+        key,
+        cid: &cid,
+        size: file_size,
+        content_type: &content_type,
+        encryption: encryption.as_ref().map(Encryption::mode),
+        encryption_key_md5: encryption.as_ref().and_then(Encryption::key_md5),
+        private,
+        version_id: version_id.as_deref(),
+        retention_mode: lock.mode.map(RetentionMode::as_str),
+        retain_until: lock.retain_until,
+        legal_hold: lock.legal_hold,
+        checksum_algorithm: checksum.map(|checksum| checksum.algorithm.as_str()),
+        checksum: checksum.map(|checksum| checksum.value.as_str()),
+        checksum_type: checksum.map(|checksum| checksum.kind.as_str()),
+        etag,
+        parts,
+        tags: &tags,
+    };
+    let replaced = match state.db.store_object_metadata(&object).await {
+        Ok(replaced) => {
             tracing::debug!(
                 cid,
                 bucket,
                 key,
+                version_id,
                 "Successfully stored object metadata for CID"
             );
+            replaced
         }
         Err(e) => {
             tracing::error!(error = %e, "Failed to store object metadata");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
-    }
+    };
 
-    tokio::task::spawn(
-        async move {
-            if let Some(ref old) = old
-                && old.cid != add_response.hash
-            {
-                let _ = super::unpin_if_orphan(state, old)
-                    .inspect_ok(|()| tracing::trace!("unpinned old ref"))
-                    .instrument(tracing::debug_span!("Unpin old ref", cid = old.cid))
-                    .await;
-            }
-        }
-        .in_current_span(),
+    state.ipns.notify(bucket);
+    unpin_replaced(
+        state.clone(),
+        old.into_iter().chain(replaced).collect(),
+        cid.clone(),
     );
 
     // Return success response with S3-like headers
-    let response = Response::builder()
+    let mut response = Response::builder();
+    if let Some(encryption) = &encryption {
+        response = encryption.response_headers(response);
+    }
+    if let Some(checksum) = checksum {
+        response = checksum.response_headers(response);
+    }
+    response = super::version_header(response, object.version_id);
+    let response = super::ipfs_headers(response, &cid, object.private)
         .status(StatusCode::OK)
         .header(header::CONTENT_LENGTH, 0)
-        .header(header::ETAG, super::etag_value(&cid))
-        .header("x-ipfs-roots", &cid)
-        .header("x-ipfs-path", format!("/ipfs/{cid}"))
+        .header(
+            header::ETAG,
+            super::etag_value(state.config.etag_mode, &cid, etag, object.private),
+        )
         .body(axum::body::Body::empty())
         .unwrap();
 
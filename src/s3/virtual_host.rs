@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use axum::extract::{OriginalUri, Request, State};
+use http::{Uri, header};
+
+/// Extracts the bucket name of a virtual-hosted-style `Host` header
+///
+/// Eg: `bucket.s3.example.com:3000` with `s3.example.com` as base domain returns `bucket`
+fn bucket_from_host<'a>(host: &'a str, base_domain: &str) -> Option<&'a str> {
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
+    };
+    let bucket = host
+        .strip_suffix(base_domain.trim_start_matches('.'))?
+        .strip_suffix('.')?;
+    (!bucket.is_empty()).then_some(bucket)
+}
+
+/// Rewrites virtual-hosted-style requests (`bucket.base_domain/key`) into path-style (`/bucket/key`)
+/// before routing, so both styles are served by the same handlers.
+///
+/// The URI sent by the client is kept as [`OriginalUri`], as it is the one used on `SigV4` signatures.
+///
+/// Ref <https://docs.aws.amazon.com/AmazonS3/latest/userguide/VirtualHosting.html>
+pub async fn rewrite(State(base_domain): State<Option<Arc<str>>>, mut request: Request) -> Request {
+    let Some(base_domain) = base_domain else {
+        return request;
+    };
+
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| request.uri().host());
+
+    let Some(bucket) = host.and_then(|host| bucket_from_host(host, &base_domain)) else {
+        return request;
+    };
+
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str());
+    match format!("/{bucket}{path_and_query}").parse::<Uri>() {
+        Ok(uri) => {
+            tracing::trace!(bucket, %uri, "Virtual-hosted-style request");
+            let original = std::mem::replace(request.uri_mut(), uri);
+            request.extensions_mut().insert(OriginalUri(original));
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, bucket, "Failed to rewrite virtual-hosted-style request");
+        }
+    }
+
+    request
+}
+
+#[cfg(test)]
+mod test {
+    use super::bucket_from_host;
+
+    #[test]
+    fn test_bucket_from_host() {
+        let base = "s3.example.com";
+        assert_eq!(
+            bucket_from_host("banana.s3.example.com", base),
+            Some("banana")
+        );
+        assert_eq!(
+            bucket_from_host("banana.s3.example.com:3000", base),
+            Some("banana")
+        );
+        assert_eq!(
+            bucket_from_host("my.banana.s3.example.com", base),
+            Some("my.banana")
+        );
+        assert_eq!(bucket_from_host("s3.example.com", base), None);
+        assert_eq!(bucket_from_host("s3.example.com:3000", base), None);
+        assert_eq!(bucket_from_host("bananas3.example.com", base), None);
+        assert_eq!(bucket_from_host("localhost:3000", base), None);
+    }
+}
@@ -0,0 +1,391 @@
+// Point-in-time snapshots of buckets
+// Buckets are MFS directories, so a snapshot is the CID of the bucket directory, recorded with a label and pinned
+// on the configured pinning backend. Buckets with private objects can't be snapshotted, as pinning would announce them.
+// Restoring links a snapshot back as the bucket directory and recreates object metadata from it, as `rebuild` does:
+// versions, tags, checksums and ETags of the snapshot objects are not recovered.
+
+use std::collections::HashSet;
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use typed_path::{UnixPath, UnixPathBuf};
+
+use crate::cli::{RunConfig, SnapshotAction, SnapshotCommand};
+use crate::cluster::ClusterClient;
+use crate::database::{self, Database, DatabaseError};
+use crate::ipfs::{self, IpfsClient};
+use crate::rebuild;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("IPFS error: {0}")]
+    Ipfs(#[from] ipfs::Error),
+
+    #[error("Invalid path: {0}")]
+    Path(#[from] typed_path::CheckedPathError),
+
+    #[error("Snapshot {0} not found")]
+    NotFound(String),
+
+    #[error("Snapshot {0} already exists")]
+    Exists(String),
+
+    #[error("{0} is not a directory")]
+    NotDirectory(String),
+
+    #[error("Bucket {0} has versioning, which restoring would bypass")]
+    Versioned(String),
+
+    #[error("Bucket {0} has objects under retention or legal hold")]
+    Locked(String),
+
+    #[error("Bucket {0} has private objects, which pinning a snapshot would announce")]
+    Private(String),
+
+    #[error("Failed to pin: {0}")]
+    Pin(String),
+
+    #[error("Failed to read snapshot: {0}")]
+    Rebuild(#[from] rebuild::Error),
+
+    #[error("Either a snapshot label or a CID must be provided")]
+    Source,
+}
+
+impl Error {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Exists(_) | Error::Versioned(_) | Error::Locked(_) | Error::Private(_) => {
+                StatusCode::CONFLICT
+            }
+            Error::NotDirectory(_) | Error::Source => StatusCode::BAD_REQUEST,
+            Error::Rebuild(rebuild::Error::Timeout(_)) => StatusCode::GATEWAY_TIMEOUT,
+            Error::Database(_)
+            | Error::Ipfs(_)
+            | Error::Path(_)
+            | Error::Pin(_)
+            | Error::Rebuild(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub bucket: String,
+    pub label: String,
+    pub cid: String,
+    pub created_at: i64,
+}
+
+impl From<database::Snapshot> for Snapshot {
+    fn from(snapshot: database::Snapshot) -> Self {
+        Snapshot {
+            bucket: snapshot.bucket,
+            label: snapshot.label,
+            cid: snapshot.cid,
+            created_at: snapshot.created_at.and_utc().timestamp(),
+        }
+    }
+}
+
+/// Content a bucket is restored from
+pub enum Source<'a> {
+    Label(&'a str),
+    Cid(&'a str),
+}
+
+impl<'a> Source<'a> {
+    /// Either a recorded snapshot or a CID, but not both
+    pub fn new(label: Option<&'a str>, cid: Option<&'a str>) -> Result<Self, Error> {
+        match (label, cid) {
+            (Some(label), None) => Ok(Source::Label(label)),
+            (None, Some(cid)) => Ok(Source::Cid(cid)),
+            _ => Err(Error::Source),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub bucket: String,
+    pub cid: String,
+    /// Objects replaced by the snapshot. Their content stays pinned until unpinned with `fsck`
+    pub replaced: u64,
+    #[serde(flatten)]
+    pub objects: rebuild::Report,
+}
+
+/// Runs `aricanduva snapshot`, printing the result as JSON
+/// Returns the exit code, failing when objects could not be restored
+pub async fn command(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    command: &SnapshotCommand,
+) -> i32 {
+    let config = &command.config;
+    let result = match &command.action {
+        SnapshotAction::Create(create) => create_snapshot(
+            db,
+            ipfs,
+            cluster,
+            config,
+            &create.bucket,
+            create.label.as_deref(),
+        )
+        .await
+        .map(|snapshot| serde_json::to_string_pretty(&snapshot)),
+        SnapshotAction::List(list) => list_snapshots(db, &list.bucket)
+            .await
+            .map(|snapshots| serde_json::to_string_pretty(&snapshots)),
+        SnapshotAction::Restore(restore) => {
+            match Source::new(restore.label.as_deref(), restore.cid.as_deref()) {
+                Ok(source) => restore_snapshot(db, ipfs, cluster, config, &restore.bucket, source)
+                    .await
+                    .map(|report| serde_json::to_string_pretty(&report)),
+                Err(e) => Err(e),
+            }
+        }
+    };
+
+    match result {
+        Ok(output) => {
+            println!("{}", output.unwrap_or_default());
+            0
+        }
+        Err(e) => {
+            eprintln!("Snapshot failed: {e}");
+            1
+        }
+    }
+}
+
+/// Records the current root CID of a bucket, pinning it
+/// Labels default to the current time. Callers hold writes to the bucket meanwhile, so no private object is linked
+/// to the root after checking the bucket has none
+pub async fn create_snapshot(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    label: Option<&str>,
+) -> Result<Snapshot, Error> {
+    let label = label.map_or_else(
+        || chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ToString::to_string,
+    );
+    if db.get_snapshot(bucket, &label).await?.is_some() {
+        return Err(Error::Exists(label));
+    }
+    if crate::s3::is_private_bucket(db, config, bucket).await? {
+        return Err(Error::Private(bucket.to_string()));
+    }
+
+    let path = crate::s3::bucket_path(&config.folder_prefix, bucket)?;
+    let cid = ipfs.directory(&path).await?;
+    let name = format!("{}@{label}", path.to_string_lossy());
+    crate::pinning::pin_existing(db, ipfs, cluster, config, bucket, &name, &cid)
+        .await
+        .map_err(Error::Pin)?;
+
+    match db.create_snapshot(bucket, &label, &cid).await? {
+        Some(snapshot) => {
+            tracing::info!(bucket, label, cid, "Created snapshot");
+            Ok(snapshot.into())
+        }
+        None => Err(Error::Exists(label)),
+    }
+}
+
+/// Snapshots of a bucket, oldest first
+pub async fn list_snapshots(db: &Database, bucket: &str) -> Result<Vec<Snapshot>, Error> {
+    Ok(db
+        .list_snapshots(bucket)
+        .await?
+        .into_iter()
+        .map(Snapshot::from)
+        .collect())
+}
+
+/// Temporary MFS path a snapshot is linked to before replacing the bucket directory
+/// Bucket names can't start with a dot, so it never clashes with a bucket
+fn restoring_path(start: &str, bucket: &str) -> Result<UnixPathBuf, typed_path::CheckedPathError> {
+    let mut path = crate::s3::mfs_root(start)?;
+    path.push_checked(format!(".restore-{bucket}"))?;
+    Ok(path)
+}
+
+/// Replaces the MFS directory and current objects of a bucket with a snapshot
+///
+/// Buckets with versioning or locked objects are refused, as their history and retention would be lost.
+/// The snapshot is linked to a temporary path and every file read first, so the bucket directory is only replaced,
+/// in a single MFS copy, once its content is reachable. Objects are then replaced in a single transaction, linking
+/// the previous directory back if it fails. Callers hold writes to the bucket meanwhile.
+/// Restored objects keep being public when a public object had the same key and CID, and are pinned like uploads.
+pub async fn restore_snapshot(
+    db: &Database,
+    ipfs: &IpfsClient,
+    cluster: Option<&ClusterClient>,
+    config: &RunConfig,
+    bucket: &str,
+    source: Source<'_>,
+) -> Result<RestoreReport, Error> {
+    let cid = match source {
+        Source::Label(label) => match db.get_snapshot(bucket, label).await? {
+            Some(snapshot) => snapshot.cid,
+            None => return Err(Error::NotFound(label.to_string())),
+        },
+        Source::Cid(cid) => cid.to_string(),
+    };
+
+    if db.get_bucket_versioning(bucket).await?.is_some() {
+        return Err(Error::Versioned(bucket.to_string()));
+    }
+    if db
+        .count_locked_objects(bucket, chrono::Utc::now().naive_utc())
+        .await?
+        > 0
+    {
+        return Err(Error::Locked(bucket.to_string()));
+    }
+
+    let stat = ipfs.stat(&format!("/ipfs/{cid}")).await?;
+    if stat.typ != "directory" {
+        return Err(Error::NotDirectory(cid));
+    }
+
+    let public: HashSet<(String, String)> = db
+        .list_bucket_objects(bucket)
+        .await?
+        .into_iter()
+        .filter(|object| !object.private)
+        .map(|object| (object.key, object.cid))
+        .collect();
+
+    let path = crate::s3::bucket_path(&config.folder_prefix, bucket)?;
+    let temporary = restoring_path(&config.folder_prefix, bucket)?;
+    ipfs.link(&stat.hash, &temporary).await?;
+    let visibility = rebuild::Visibility::Known(&public);
+    let inspected = inspect_snapshot(ipfs, config, bucket, &temporary, visibility).await;
+    let swapped = match inspected {
+        Ok(inspected) => swap(ipfs, &stat.hash, &path)
+            .await
+            .map(|previous| (inspected, previous)),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = ipfs.unlink(&temporary).await {
+        tracing::warn!(bucket, error = %e, "Failed to unlink restored snapshot");
+    }
+    let ((restored, encrypted), previous) = swapped?;
+
+    let objects: Vec<_> = restored
+        .iter()
+        .map(|object| object.metadata(bucket))
+        .collect();
+    let replaced = match db.replace_bucket_objects(bucket, &objects).await {
+        Ok(replaced) => replaced,
+        Err(e) => {
+            // Links the previous directory back, so it keeps matching the objects
+            let relinked = match &previous {
+                Some(previous) => ipfs.link(previous, &path).await,
+                None => ipfs.unlink(&path).await,
+            };
+            if let Err(e) = relinked {
+                tracing::error!(bucket, error = %e, "Failed to relink the bucket directory");
+            }
+            return Err(e.into());
+        }
+    };
+
+    let mut report = rebuild::Report {
+        encrypted,
+        ..Default::default()
+    };
+    for object in &restored {
+        report.restored.push(format!("{bucket}/{}", object.key));
+        if object.private {
+            continue;
+        }
+
+        let name = format!("{}/{}", path.to_string_lossy(), object.key);
+        if let Err(e) =
+            crate::pinning::pin_existing(db, ipfs, cluster, config, bucket, &name, &object.cid)
+                .await
+        {
+            report.errors.push(format!("{name}: {e}"));
+        }
+    }
+
+    tracing::info!(bucket, cid = stat.hash, replaced, "Restored snapshot");
+    Ok(RestoreReport {
+        bucket: bucket.to_string(),
+        cid: stat.hash,
+        replaced,
+        objects: report,
+    })
+}
+
+/// Recreates the objects of a snapshot linked at `temporary`, along with the encrypted ones that can't be restored
+/// Fails when any file can't be read within `import_timeout`, before the bucket is replaced
+async fn inspect_snapshot(
+    ipfs: &IpfsClient,
+    config: &RunConfig,
+    bucket: &str,
+    temporary: &UnixPath,
+    visibility: rebuild::Visibility<'_>,
+) -> Result<(Vec<rebuild::Restored>, Vec<String>), Error> {
+    let mut restored = Vec::new();
+    let mut encrypted = Vec::new();
+    for entry in ipfs.walk(temporary).await? {
+        let Some(key) = entry
+            .path
+            .strip_prefix(temporary)
+            .ok()
+            .filter(|_| !entry.directory)
+            .map(|key| key.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+
+        let private = visibility.is_private(config, bucket, &key, &entry.cid);
+        match rebuild::inspect(ipfs, config, &key, &entry, private).await? {
+            Some(object) => restored.push(object),
+            None => encrypted.push(format!("{bucket}/{key}")),
+        }
+    }
+    Ok((restored, encrypted))
+}
+
+/// Replaces the bucket directory with `cid` in a single MFS copy, returning the previous directory
+async fn swap(ipfs: &IpfsClient, cid: &str, path: &UnixPath) -> Result<Option<String>, Error> {
+    let previous = ipfs.directory(path).await.ok();
+    ipfs.link(cid, path).await?;
+    Ok(previous)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, Source};
+
+    #[test]
+    fn test_source_requires_label_or_cid() {
+        assert!(matches!(
+            Source::new(Some("daily"), None),
+            Ok(Source::Label("daily"))
+        ));
+        assert!(matches!(
+            Source::new(None, Some("bafybeigdyrzt")),
+            Ok(Source::Cid("bafybeigdyrzt"))
+        ));
+        assert!(matches!(Source::new(None, None), Err(Error::Source)));
+        assert!(matches!(
+            Source::new(Some("daily"), Some("bafybeigdyrzt")),
+            Err(Error::Source)
+        ));
+    }
+}